@@ -0,0 +1,61 @@
+//! A Prometheus exporter that periodically runs RCON commands and serves the values parsed from the responses on `/metrics`.
+//!
+//! Usage: `rcon-exporter <listen address> <server address> <name=command=parser>...`
+//!
+//! The parser is one of `number`, `minecraft-list`, `tps`, `source-stats:<column>` or `regex:<pattern>`, see [`ValueParser`].
+//! The password is read from the `RCON_PASSWORD` environment variable, `RCON_INTERVAL` sets the seconds between collections (15 by default).
+
+use std::{
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use ya_rcon::metrics::{Exporter, Metric, ValueParser};
+use ya_rcon::{RCONClient, SimpleIDGenerator};
+
+const USAGE: &str =
+    "Usage: rcon-exporter <listen address> <server address> <name=command=parser>...";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(listen), Some(server)) = (args.next(), args.next()) else {
+        return Err(USAGE.into());
+    };
+    let metrics = args
+        .map(|arg| {
+            let mut parts = arg.splitn(3, '=');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), Some(command), Some(parser)) => Ok(Metric::new(
+                    name.to_string(),
+                    command.to_string(),
+                    parser.parse::<ValueParser>()?,
+                )?),
+                _ => Err(format!("Invalid metric {arg:?}, expected name=command=parser").into()),
+            }
+        })
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    if metrics.is_empty() {
+        return Err(USAGE.into());
+    }
+    let password = std::env::var("RCON_PASSWORD")
+        .map_err(|_| "The RCON_PASSWORD environment variable must be set")?;
+    let interval = match std::env::var("RCON_INTERVAL") {
+        Ok(seconds) => Duration::from_secs(seconds.parse()?),
+        Err(_) => Duration::from_secs(15),
+    };
+
+    let exporter = Exporter::new(metrics)?.with_label("server".to_string(), server.clone())?;
+    let collector = exporter.clone();
+    std::thread::spawn(move || {
+        collector.run(
+            || {
+                let stream = TcpStream::connect(server.as_str())?;
+                stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+                RCONClient::new(stream, SimpleIDGenerator::new(), password.clone())
+            },
+            interval,
+        )
+    });
+    exporter.serve(TcpListener::bind(listen.as_str())?)?;
+    Ok(())
+}
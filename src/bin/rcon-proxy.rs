@@ -0,0 +1,40 @@
+//! An RCON proxy that lets many clients share one connection to a game server.
+//!
+//! Usage: `rcon-proxy <listen address> <upstream address> <name:password>...`
+//!
+//! The password for the upstream server is read from the `RCON_PASSWORD` environment variable. Every `name:password` argument adds a downstream user that may run any command.
+//! If the `RCON_AUDIT_LOG` environment variable is set, every command is appended to the file it names.
+
+use std::{net::TcpListener, sync::Arc};
+
+use ya_rcon::policy::{Action, Policy, UserPolicy, WriterAuditLog};
+use ya_rcon::proxy::Proxy;
+
+const USAGE: &str = "Usage: rcon-proxy <listen address> <upstream address> <name:password>...";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(listen), Some(upstream)) = (args.next(), args.next()) else {
+        return Err(USAGE.into());
+    };
+    let users = args
+        .map(|arg| match arg.split_once(':') {
+            Some((name, password)) => Ok(UserPolicy::new(name.to_string(), password.to_string())
+                .with_default_action(Action::Allow)),
+            None => Err(format!("Invalid user {arg:?}, expected name:password")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if users.is_empty() {
+        return Err(USAGE.into());
+    }
+    let password = std::env::var("RCON_PASSWORD")
+        .map_err(|_| "The RCON_PASSWORD environment variable must be set")?;
+
+    let mut policy = Policy::new(users);
+    if let Ok(path) = std::env::var("RCON_AUDIT_LOG") {
+        policy = policy.with_audit_log(Arc::new(WriterAuditLog::open(path)?));
+    }
+    let proxy = Proxy::connect_with_policy(upstream.as_str(), password, policy)?;
+    proxy.serve(TcpListener::bind(listen.as_str())?)?;
+    Ok(())
+}
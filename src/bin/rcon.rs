@@ -0,0 +1,196 @@
+//! A command line RCON client.
+//!
+//! Usage:
+//! * `rcon exec <address> <command>...` runs every command in order and prints the responses, the password is read from the `RCON_PASSWORD` environment variable.
+//! * `rcon schedule <config file>` runs the jobs in the TOML config file on their schedules, see `Scheduler::from_config()` for the format.
+//! * `rcon schedule <config file> --run <job>` runs one job now and exits.
+//! * `rcon broadcast [--json] [--config <file>] [--concurrency <n>] [--timeout <seconds>] <command> <[name=]address>...` runs the command on every server at the same time and prints a table of the results, or JSON with `--json`.
+//!   The password of every server is read from the `RCON_PASSWORD` environment variable.
+//!   With `--config`, servers can also be given by their name in a scheduler config file, which has their addresses and passwords. Without any servers the command runs on every server in the file.
+
+use std::{net::TcpStream, process::ExitCode, time::Duration};
+
+use ya_rcon::broadcast::{Broadcast, BroadcastResult, Target};
+use ya_rcon::scheduler::{Scheduler, ServerProfile};
+use ya_rcon::{RCONClient, SimpleIDGenerator};
+
+const USAGE: &str = "Usage:
+    rcon exec <address> <command>...
+    rcon schedule <config file> [--run <job>]
+    rcon broadcast [--json] [--config <file>] [--concurrency <n>] [--timeout <seconds>] <command> <name|[name=]address>...";
+
+fn password() -> Result<String, &'static str> {
+    std::env::var("RCON_PASSWORD").map_err(|_| "The RCON_PASSWORD environment variable must be set")
+}
+
+fn exec(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let address = args.next().ok_or(USAGE)?;
+    let password = password()?;
+    let stream = TcpStream::connect(address.as_str())?;
+    let mut client = RCONClient::new(stream, SimpleIDGenerator::new(), password)?;
+    for command in args {
+        println!("{}", client.send_command(command)?);
+    }
+    Ok(())
+}
+
+fn schedule(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args.next().ok_or(USAGE)?;
+    let mut scheduler = Scheduler::from_config(&std::fs::read_to_string(path)?)?;
+    match (args.next().as_deref(), args.next()) {
+        (None, _) => scheduler.run(),
+        (Some("--run"), Some(job)) => {
+            let report = scheduler.run_job(&job)?;
+            if report.is_success() {
+                Ok(())
+            } else {
+                Err(format!("job {job:?} failed").into())
+            }
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn print_table(results: &[BroadcastResult]) {
+    let rows: Vec<[String; 4]> = results
+        .iter()
+        .map(|result| {
+            let (status, output) = match &result.result {
+                Ok(response) => (
+                    "ok",
+                    response.lines().next().unwrap_or_default().to_string(),
+                ),
+                Err(e) => ("error", e.to_string()),
+            };
+            [
+                result.target.clone(),
+                status.to_string(),
+                format!("{}ms", result.elapsed.as_millis()),
+                output,
+            ]
+        })
+        .collect();
+    let header = ["SERVER", "STATUS", "TIME", "RESPONSE"].map(String::from);
+    let mut widths = [0; 3];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for [server, status, time, output] in std::iter::once(&header).chain(&rows) {
+        println!(
+            "{server:<0$}  {status:<1$}  {time:>2$}  {output}",
+            widths[0], widths[1], widths[2]
+        );
+    }
+}
+
+fn print_json(results: &[BroadcastResult]) -> Result<(), serde_json::Error> {
+    let results: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| {
+            let elapsed_ms = result.elapsed.as_secs_f64() * 1000.0;
+            match &result.result {
+                Ok(response) => serde_json::json!({
+                    "server": result.target,
+                    "address": result.address,
+                    "ok": true,
+                    "response": response,
+                    "elapsed_ms": elapsed_ms,
+                }),
+                Err(e) => serde_json::json!({
+                    "server": result.target,
+                    "address": result.address,
+                    "ok": false,
+                    "error": e.to_string(),
+                    "elapsed_ms": elapsed_ms,
+                }),
+            }
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&results)?);
+    Ok(())
+}
+
+fn broadcast(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut json = false;
+    let mut profiles = Vec::new();
+    let mut broadcast = Broadcast::new();
+    let command = loop {
+        match args.next().ok_or(USAGE)?.as_str() {
+            "--json" => json = true,
+            "--config" => {
+                let path = args.next().ok_or(USAGE)?;
+                profiles = ServerProfile::from_config(&std::fs::read_to_string(path)?)?;
+            }
+            "--concurrency" => {
+                broadcast = broadcast.with_concurrency(args.next().ok_or(USAGE)?.parse()?)
+            }
+            "--timeout" => {
+                let seconds: f64 = args.next().ok_or(USAGE)?.parse()?;
+                broadcast = broadcast.with_timeout(Duration::try_from_secs_f64(seconds)?);
+            }
+            command => break command.to_string(),
+        }
+    };
+    let args: Vec<String> = args.collect();
+    let targets: Vec<Target> = if args.is_empty() {
+        profiles
+            .into_iter()
+            .map(|profile| Target::new(profile.name, profile.address, profile.password))
+            .collect()
+    } else {
+        args.into_iter()
+            .map(|arg| {
+                if let Some(profile) = profiles.iter().find(|profile| profile.name == arg) {
+                    return Ok(Target::new(
+                        arg,
+                        profile.address.clone(),
+                        profile.password.clone(),
+                    ));
+                }
+                Ok(match arg.split_once('=') {
+                    Some((name, address)) => {
+                        Target::new(name.to_string(), address.to_string(), password()?)
+                    }
+                    None => Target::new(arg.clone(), arg, password()?),
+                })
+            })
+            .collect::<Result<_, &str>>()?
+    };
+    if targets.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let results = runtime.block_on(broadcast.run(&targets, &command));
+    if json {
+        print_json(&results)?;
+    } else {
+        print_table(&results);
+    }
+    if results.iter().all(|result| result.result.is_ok()) {
+        Ok(())
+    } else {
+        Err("the command failed on some servers".into())
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("exec") => exec(args),
+        Some("schedule") => schedule(args),
+        Some("broadcast") => broadcast(args),
+        _ => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Contains the implementation for [`Broadcast`], which runs one command on many servers at the same time.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() {
+//! use std::time::Duration;
+//! use ya_rcon::broadcast::{Broadcast, Target};
+//!
+//! let targets: Vec<Target> = (1..=50)
+//!     .map(|i| Target::new(format!("game-{i}"), format!("10.0.0.{i}:27015"), "password".to_string()))
+//!     .collect();
+//! let results = Broadcast::new()
+//!     .with_concurrency(10)
+//!     .with_timeout(Duration::from_secs(5))
+//!     .run(&targets, "say Maintenance in 5 minutes")
+//!     .await;
+//! for result in results {
+//!     match result.result {
+//!         Ok(response) => println!("{}: {response}", result.target),
+//!         Err(e) => eprintln!("{}: {e}", result.target),
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::{
+    io::{Error, ErrorKind},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "async-net")]
+#[cfg(not(feature = "tokio"))]
+use async_net::TcpStream;
+use futures::{future::Either, StreamExt};
+#[cfg(feature = "tokio")]
+#[cfg(not(feature = "async-net"))]
+use tokio::net::TcpStream;
+
+use crate::{client_async::AsyncRCONClient, rate_limit::sleep, SimpleIDGenerator};
+
+/// A server to run the command on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    name: String,
+    address: String,
+    password: String,
+}
+
+impl Target {
+    /// Creates a new instance of the `Target`, the name is only used to identify the server in the results.
+    pub fn new(name: String, address: String, password: String) -> Target {
+        Target {
+            name,
+            address,
+            password,
+        }
+    }
+
+    /// Gets the name of the server.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the address of the server.
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    async fn execute(&self, command: String) -> Result<String, Error> {
+        let stream = TcpStream::connect(self.address.as_str()).await?;
+        let mut client =
+            AsyncRCONClient::new(stream, SimpleIDGenerator::new(), self.password.clone()).await?;
+        client.send_command(command).await
+    }
+}
+
+/// The result of the command on one server.
+#[derive(Debug)]
+pub struct BroadcastResult {
+    /// The name of the server.
+    pub target: String,
+    /// The address of the server.
+    pub address: String,
+    /// The response of the server, or why there is none.
+    pub result: Result<String, Error>,
+    /// How long it took to connect, authenticate and run the command.
+    pub elapsed: Duration,
+}
+
+/// Runs a command on many servers with a limit on how many connections are open at once. See the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub struct Broadcast {
+    concurrency: usize,
+    timeout: Duration,
+}
+
+impl Default for Broadcast {
+    fn default() -> Self {
+        Broadcast::new()
+    }
+}
+
+impl Broadcast {
+    /// Creates a broadcast that talks to at most 16 servers at once and gives every server 10 seconds.
+    pub fn new() -> Broadcast {
+        Broadcast {
+            concurrency: 16,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets how many servers are talked to at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Broadcast {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets how long each server gets to connect, authenticate and respond, a server that takes longer fails with [`ErrorKind::TimedOut`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Broadcast {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs the command on every target, the results are in the same order as the targets. Every target gets its own connection.
+    pub async fn run(&self, targets: &[Target], command: &str) -> Vec<BroadcastResult> {
+        let mut results: Vec<(usize, BroadcastResult)> =
+            futures::stream::iter(targets.iter().enumerate())
+                .map(|(index, target)| async move {
+                    let start = Instant::now();
+                    let execute = std::pin::pin!(target.execute(command.to_string()));
+                    let timeout = std::pin::pin!(sleep(self.timeout));
+                    let result = match futures::future::select(execute, timeout).await {
+                        Either::Left((result, _)) => result,
+                        Either::Right(_) => Err(Error::new(
+                            ErrorKind::TimedOut,
+                            "The server did not respond in time",
+                        )),
+                    };
+                    let result = BroadcastResult {
+                        target: target.name.clone(),
+                        address: target.address.clone(),
+                        result,
+                        elapsed: start.elapsed(),
+                    };
+                    (index, result)
+                })
+                .buffer_unordered(self.concurrency)
+                .collect()
+                .await;
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::server::{serve_connection, PasswordHandler};
+
+    fn start_simulator() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let handler =
+                    PasswordHandler::new("secret".to_string(), |cmd: &str| format!("ran {cmd}"));
+                std::thread::spawn(move || serve_connection(stream.unwrap(), handler));
+            }
+        });
+        address
+    }
+
+    #[tokio_macros::test]
+    async fn broadcast_to_fleet() {
+        // Accepts connections but never answers.
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent_address = silent.local_addr().unwrap().to_string();
+        // Nothing listens on a port that was just released.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_address = closed.local_addr().unwrap().to_string();
+        drop(closed);
+
+        let password = "secret".to_string();
+        let targets = [
+            Target::new("a".to_string(), start_simulator(), password.clone()),
+            Target::new("b".to_string(), start_simulator(), password.clone()),
+            Target::new("wrong".to_string(), start_simulator(), "nope".to_string()),
+            Target::new("silent".to_string(), silent_address, password.clone()),
+            Target::new("closed".to_string(), closed_address, password),
+        ];
+        let results = Broadcast::new()
+            .with_concurrency(2)
+            .with_timeout(Duration::from_millis(300))
+            .run(&targets, "reload")
+            .await;
+
+        let names: Vec<&str> = results.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(names, ["a", "b", "wrong", "silent", "closed"]);
+        assert_eq!(results[0].result.as_ref().unwrap(), "ran reload");
+        assert_eq!(results[1].result.as_ref().unwrap(), "ran reload");
+        let kinds: Vec<ErrorKind> = results[2..]
+            .iter()
+            .map(|r| r.result.as_ref().unwrap_err().kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ErrorKind::PermissionDenied,
+                ErrorKind::TimedOut,
+                ErrorKind::ConnectionRefused
+            ]
+        );
+        drop(silent);
+    }
+}
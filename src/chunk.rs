@@ -0,0 +1,335 @@
+//! Contains the implementation for [`Chunker`]
+
+use std::io::{Error, ErrorKind};
+
+use crate::{
+    command::{word, Quoting},
+    games::{lua, Dialect},
+    packet::MAX_PAYLOAD_SIZE,
+    service::{AsyncCommandService, CommandService},
+};
+
+/// Splits work that does not fit in one packet into several commands and joins their responses, for servers that reject or cut off long commands.
+///
+/// A chunk is the prefix followed by as many statements as fit, joined with the separator of the game.
+/// Games without a separator get one statement per command.
+/// The presets take the limits of the game from its [`Dialect`], or use [`Dialect::chunker`] for any game.
+/// Lengths are counted in UTF-8 bytes.
+///
+/// # Example
+/// ```
+/// use ya_rcon::chunk::Chunker;
+/// use ya_rcon::service::service_fn;
+///
+/// let names: Vec<String> = (0..500).map(|i| format!("player{i}")).collect();
+/// let commands = Chunker::source().batch("sm_whitelist_add", &names).unwrap();
+/// assert!(commands.len() > 1 && commands.len() < 20);
+///
+/// let mut server = service_fn(|cmd: String| Ok(format!("ran {} bytes\n", cmd.len())));
+/// let responses = Chunker::source().send(&mut server, commands).unwrap();
+/// assert!(responses.lines().count() > 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunker {
+    max_len: usize,
+    prefix: String,
+    separator: Option<char>,
+    quoting: Quoting,
+    lua: bool,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Chunker::new()
+    }
+}
+
+impl Chunker {
+    /// Creates a chunker for [`MAX_PAYLOAD_SIZE`] bytes with one statement per command.
+    pub fn new() -> Chunker {
+        Chunker {
+            max_len: MAX_PAYLOAD_SIZE,
+            prefix: String::new(),
+            separator: None,
+            quoting: Quoting::Source,
+            lua: false,
+        }
+    }
+
+    /// Source engine games, see [`Dialect::source`].
+    pub fn source() -> Chunker {
+        Dialect::source().chunker()
+    }
+
+    /// Minecraft, see [`Dialect::minecraft`].
+    pub fn minecraft() -> Chunker {
+        Dialect::minecraft().chunker()
+    }
+
+    /// Factorio, see [`Dialect::factorio`], where every chunk is run as Lua with `/silent-command`.
+    /// Every chunk is its own Lua chunk, so `local` variables are not visible in the next one, use `storage` instead.
+    pub fn factorio() -> Chunker {
+        Dialect::factorio()
+            .chunker()
+            .with_prefix("/silent-command ".to_string())
+            .with_lua(true)
+    }
+
+    /// Sets the maximum length of a command in bytes, including the prefix.
+    ///
+    /// # Panics
+    /// If `max_len` is larger than [`MAX_PAYLOAD_SIZE`].
+    pub fn with_max_len(mut self, max_len: usize) -> Chunker {
+        assert!(
+            max_len <= MAX_PAYLOAD_SIZE,
+            "max_len can not be larger than MAX_PAYLOAD_SIZE"
+        );
+        self.max_len = max_len;
+        self
+    }
+
+    /// Sets the text that starts every chunk, such as `/silent-command `.
+    pub fn with_prefix(mut self, prefix: String) -> Chunker {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Sets the character that separates statements in one command, `None` sends one statement per command.
+    pub fn with_separator(mut self, separator: Option<char>) -> Chunker {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets how strings are quoted, so [`Chunker::split`] does not split inside them.
+    /// With [`Quoting::Backslash`] both `"` and `'` start a string and `\` escapes the next character.
+    pub fn with_quoting(mut self, quoting: Quoting) -> Chunker {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Sets whether [`Chunker::split`] reads the script as Lua, where a statement can span several lines.
+    pub fn with_lua(mut self, lua: bool) -> Chunker {
+        self.lua = lua;
+        self
+    }
+
+    /// Splits a script into statements at line breaks and at separators outside of strings, then packs them with [`Chunker::pack`].
+    ///
+    /// Lua scripts, see [`Chunker::with_lua`], are only split between top-level statements: never inside a block such as `function ... end`
+    /// or a table, and not at a line break after an operator or before a line that continues the statement, such as `:method()`.
+    /// Line comments are removed, and long strings and block comments are rejected with [`ErrorKind::InvalidInput`].
+    pub fn split(&self, script: &str) -> Result<Vec<String>, Error> {
+        if self.lua {
+            return self.pack(lua::statements(script)?);
+        }
+        let mut statements = Vec::new();
+        let mut statement = String::new();
+        let mut string = None;
+        let mut escaped = false;
+        for c in script.chars() {
+            if let Some(quote) = string {
+                if c == '\n' {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Unfinished string in the script",
+                    ));
+                }
+                statement.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && self.quoting == Quoting::Backslash {
+                    escaped = true;
+                } else if c == quote {
+                    string = None;
+                }
+                continue;
+            }
+            match c {
+                '"' if self.quoting != Quoting::None => string = Some(c),
+                '\'' if self.quoting == Quoting::Backslash => string = Some(c),
+                '\n' | '\r' => {
+                    statements.push(std::mem::take(&mut statement));
+                    continue;
+                }
+                _ if Some(c) == self.separator => {
+                    statements.push(std::mem::take(&mut statement));
+                    continue;
+                }
+                _ => {}
+            }
+            statement.push(c);
+        }
+        if string.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Unfinished string in the script",
+            ));
+        }
+        statements.push(statement);
+        self.pack(
+            statements
+                .iter()
+                .map(|statement| statement.trim())
+                .filter(|statement| !statement.is_empty()),
+        )
+    }
+
+    /// Packs statements into as few commands as possible, in order.
+    /// Fails with [`ErrorKind::InvalidInput`] if a statement does not fit in a command on its own.
+    pub fn pack<T: AsRef<str>>(
+        &self,
+        statements: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<String>, Error> {
+        let mut commands = Vec::new();
+        let mut command = String::new();
+        for statement in statements {
+            let statement = statement.as_ref();
+            if self.prefix.len() + statement.len() > self.max_len {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "A statement of {} bytes does not fit in a command of {} bytes",
+                        statement.len(),
+                        self.max_len
+                    ),
+                ));
+            }
+            match self.separator {
+                // Separated by the separator and a space.
+                Some(separator) if !command.is_empty() => {
+                    if command.len() + separator.len_utf8() + 1 + statement.len() <= self.max_len {
+                        command.push(separator);
+                        command.push(' ');
+                        command.push_str(statement);
+                        continue;
+                    }
+                    commands.push(std::mem::take(&mut command));
+                }
+                None if !command.is_empty() => commands.push(std::mem::take(&mut command)),
+                _ => {}
+            }
+            command.push_str(&self.prefix);
+            command.push_str(statement);
+        }
+        if !command.is_empty() {
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+
+    /// Runs `command` once for every item, packed with [`Chunker::pack`], for example to add many players to a whitelist.
+    /// Items are checked like [`crate::Command::with_arg`].
+    pub fn batch<T: AsRef<str>>(&self, command: &str, items: &[T]) -> Result<Vec<String>, Error> {
+        let statements = items
+            .iter()
+            .map(|item| Ok(format!("{command} {}", word(item.as_ref(), "item")?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.pack(statements)
+    }
+
+    /// Sends the commands in order and joins the responses that are not empty with line breaks.
+    /// Stops at the first error, the commands before it have already run.
+    pub fn send<S: CommandService>(
+        &self,
+        service: &mut S,
+        commands: Vec<String>,
+    ) -> Result<String, Error> {
+        let mut responses = Vec::new();
+        for command in commands {
+            responses.push(service.call(command)?);
+        }
+        Ok(join(responses))
+    }
+
+    /// The async version of [`Chunker::send`].
+    pub async fn send_async<S: AsyncCommandService>(
+        &self,
+        service: &mut S,
+        commands: Vec<String>,
+    ) -> Result<String, Error> {
+        let mut responses = Vec::new();
+        for command in commands {
+            responses.push(service.call(command).await?);
+        }
+        Ok(join(responses))
+    }
+}
+
+fn join(responses: Vec<String>) -> String {
+    responses
+        .iter()
+        .map(|response| response.trim_end_matches('\n'))
+        .filter(|response| !response.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn split_scripts() {
+        let chunker = Chunker::factorio().with_max_len(60);
+        let script = "storage.a = 'x;y'\nstorage.b = \"q\\\";\"; game.print(storage.a)\n\n";
+        assert_eq!(
+            chunker.split(script).unwrap(),
+            [
+                "/silent-command storage.a = 'x;y'; storage.b = \"q\\\";\"",
+                "/silent-command game.print(storage.a)",
+            ]
+        );
+        assert_eq!(
+            Chunker::source()
+                .split("say \"don't; go\"; status")
+                .unwrap(),
+            ["say \"don't; go\"; status"]
+        );
+        assert_eq!(
+            chunker.split("game.print('a)").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let script = "-- don't split; here\nstorage.t = {\n  1, -- it's one\n  2,\n}\n\
+            function storage.f()\n  game.print('f')\nend\n";
+        assert_eq!(
+            Chunker::factorio().with_max_len(60).split(script).unwrap(),
+            [
+                "/silent-command storage.t = { 1, 2, }",
+                "/silent-command function storage.f() game.print('f') end",
+            ]
+        );
+        let long = format!("game.print('{}')", "a".repeat(60));
+        assert_eq!(
+            chunker.split(&long).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn batch_and_send() {
+        let names: Vec<String> = (0..200).map(|i| format!("player{i:03}")).collect();
+        let commands = Chunker::minecraft().batch("whitelist add", &names).unwrap();
+        assert_eq!(commands.len(), 200);
+        let commands = Chunker::source()
+            .with_max_len(100)
+            .batch("sm_whitelist_add", &names)
+            .unwrap();
+        assert!(commands.iter().all(|command| command.len() <= 100));
+        assert_eq!(commands[0].matches("sm_whitelist_add").count(), 3);
+        assert!(Chunker::source().batch("kick", &["a; quit"]).is_err());
+
+        let mut sent = 0;
+        let mut server = service_fn(|cmd: String| {
+            sent += 1;
+            Ok(if cmd.ends_with("199") {
+                String::new()
+            } else {
+                format!("{} added\n", cmd.matches("add").count())
+            })
+        });
+        let response = Chunker::source().send(&mut server, commands).unwrap();
+        assert_eq!(response.lines().next(), Some("3 added"));
+        assert_eq!(sent, 67);
+    }
+}
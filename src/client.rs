@@ -2,13 +2,25 @@
 
 use std::io::{Error, ErrorKind, Read, Write};
 
-use crate::packet::{packet_id::ID, Packet, PacketError, PacketType, MAX_PACKET_SIZE};
+use crate::correlation::Correlator;
+use crate::encoding::TextEncoding;
+use crate::packet::{
+    packet_id::ID,
+    packet_kind::{PacketKind, ServerPacket, SessionState},
+    Direction, Packet, PacketError, PacketType, MAX_PACKET_SIZE,
+};
+use crate::trace;
 
 /// The base RCON client. See the [`RCONClient::new()`] function for info about the fields.
 #[derive(Debug)]
 pub struct RCONClient<T: Read + Write, I: Iterator<Item = ID>> {
     socket: T,
     incremental_id: I,
+    encoding: TextEncoding,
+    lenient: bool,
+    correlator: Correlator,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl<T: Read + Write, I: Iterator<Item = ID>> RCONClient<T, I> {
@@ -19,14 +31,44 @@ impl<T: Read + Write, I: Iterator<Item = ID>> RCONClient<T, I> {
     /// * `id_generator` - Some iterator that yields [`ID`], this is to fill the "ID" field of the packet. I reccomend simply using `0_u32..`
     /// * `password` - The password used to authenticate with the server.
     pub fn new(socket: T, id_generator: I, password: String) -> Result<RCONClient<T, I>, Error> {
+        RCONClient::with_encoding(socket, id_generator, password, TextEncoding::Utf8)
+    }
+
+    /// Same as [`RCONClient::new()`], but uses the given [`TextEncoding`] for the password, commands and responses instead of UTF-8.
+    pub fn with_encoding(
+        socket: T,
+        id_generator: I,
+        password: String,
+        encoding: TextEncoding,
+    ) -> Result<RCONClient<T, I>, Error> {
         let mut client = RCONClient {
             socket,
             incremental_id: id_generator,
+            encoding,
+            lenient: false,
+            correlator: Correlator::new(),
+            #[cfg(feature = "tracing")]
+            span: trace::connection_span(encoding),
         };
         client.authenticate(password)?;
         Ok(client)
     }
 
+    /// Sets the [`TextEncoding`] used for commands and responses from now on.
+    pub fn set_encoding(&mut self, encoding: TextEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Gets the [`TextEncoding`] used for commands and responses.
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Sets whether responses may leave out the null bytes that end the body, see [`Packet::try_from_lenient()`]. Off by default.
+    pub fn set_lenient_parsing(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
     fn next_id(&mut self) -> ID {
         self.incremental_id
             .next()
@@ -35,42 +77,76 @@ impl<T: Read + Write, I: Iterator<Item = ID>> RCONClient<T, I> {
 
     fn send_packet(&mut self, pkt_type: PacketType, body: String) -> Result<ID, Error> {
         let id = self.next_id();
-        let pkt = Vec::from(Packet::new(pkt_type, body, id)?);
-        self.socket.write_all(&pkt)?;
+        let packet = Packet::new_encoded(pkt_type, &body, id, self.encoding)?;
+        trace::packet_sent(&packet);
+        self.socket.write_all(&Vec::from(packet))?;
         Ok(id)
     }
 
+    /// Reads the next whole packet, several packets can arrive in one read and a packet can be split across reads.
     fn recv_packet_unchecked(&mut self) -> Result<Packet, Error> {
-        let mut buf = [0u8; MAX_PACKET_SIZE];
-        let packet_len = self.socket.read(&mut buf)?;
-        // Question about buf[..]
-        let packet = Packet::try_from(&buf[..packet_len])?;
-        Ok(packet)
+        loop {
+            if let Some(packet) = self.correlator.next_packet(self.encoding, self.lenient)? {
+                trace::packet_received(&packet);
+                return Ok(packet);
+            }
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let read = self.socket.read(&mut buf)?;
+            if read == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "The RCON server closed the connection",
+                ));
+            }
+            self.correlator.extend(&buf[..read]);
+        }
     }
 
     fn recv_packet(&mut self, expected_type: PacketType, expected_id: ID) -> Result<String, Error> {
-        let packet = self.recv_packet_unchecked()?;
-        if packet.get_id() != expected_id {
-            Err(PacketError::UnexpectedID)?;
-        }
+        let packet = loop {
+            if let Some(packet) = self.correlator.take(expected_id) {
+                break packet;
+            }
+            let packet = self.recv_packet_unchecked()?;
+            if let Some(packet) = self.correlator.route(packet, expected_id) {
+                break packet;
+            }
+        };
         if packet.get_type() != expected_type {
+            trace::unexpected_packet(expected_type, expected_id.into(), &packet);
             Err(PacketError::UnexpectedType)?;
         }
-        Ok(packet.get_body())
+        Ok(packet.decode_body(self.encoding)?)
     }
 
     /// When [`RCONClient::new()`] is called this method will also be called, but it is exposed separatly in case it is desired. Not sure why it would be.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "rcon_auth", parent = &self.span, skip_all, err)
+    )]
     pub fn authenticate(&mut self, password: String) -> Result<(), Error> {
+        let _timer = trace::Timer::start();
         let used_id = self.send_packet(PacketType::Auth, password)?;
         self.wait_authentication(used_id)
     }
 
     fn wait_authentication(&mut self, expected_id: ID) -> Result<(), Error> {
-        let packet = self.recv_packet_unchecked()?;
-
-        if packet.get_type() != PacketType::AuthResponse {
-            return Err(PacketError::UnexpectedType.into());
-        }
+        let packet = loop {
+            let packet = self.recv_packet_unchecked()?;
+            match packet.get_kind(Direction::ServerToClient, SessionState::AuthPending) {
+                PacketKind::Server(ServerPacket::AuthResponse) => break packet,
+                // Source servers send an empty SERVERDATA_RESPONSE_VALUE right before the SERVERDATA_AUTH_RESPONSE.
+                PacketKind::Server(ServerPacket::ResponseValue)
+                    if packet.get_id() == expected_id => {}
+                PacketKind::Server(ServerPacket::ResponseValue) => {
+                    self.correlator.route(packet, expected_id);
+                }
+                _ => {
+                    trace::unexpected_packet(PacketType::AuthResponse, expected_id.into(), &packet);
+                    return Err(PacketError::UnexpectedType.into());
+                }
+            }
+        };
 
         let packet_id = packet.get_id();
         if packet_id == (-1).into() {
@@ -81,25 +157,127 @@ impl<T: Read + Write, I: Iterator<Item = ID>> RCONClient<T, I> {
         } else if expected_id == packet_id {
             Ok(())
         } else {
+            trace::unexpected_packet(PacketType::AuthResponse, expected_id.into(), &packet);
             Err(PacketError::UnexpectedID.into())
         }
     }
 
     /// Send the given command to the server and returns the response. This does not handle multipacket responses.
+    /// Use a [`crate::Command`] to build commands from untrusted values such as player names.
+    ///
+    /// If it fails, for example because the socket has a read timeout, a late response to the command is discarded when it arrives instead of being taken as the response to the next command.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "rcon_command",
+            parent = &self.span,
+            skip_all,
+            fields(command = %trace::recorded_command(&cmd)),
+            err
+        )
+    )]
     pub fn send_command(&mut self, cmd: String) -> Result<String, Error> {
+        let _timer = trace::Timer::start();
+        let used_id = self.send_request(cmd)?;
+        self.receive_response(used_id)
+            .inspect_err(|_| self.correlator.abandon(used_id))
+    }
+
+    /// Sends a command without waiting for the response, which is read with [`RCONClient::receive_response()`].
+    /// Several commands can be in flight at once, their responses are matched by ID whatever order they arrive in.
+    pub fn send_request(&mut self, cmd: String) -> Result<ID, Error> {
         let used_id = self.send_packet(PacketType::ExecCommand, cmd)?;
-        self.recv_packet(PacketType::ResponseValue, used_id)
+        self.correlator.register(used_id);
+        Ok(used_id)
+    }
+
+    /// Waits for the response to a command sent with [`RCONClient::send_request()`].
+    /// Responses to other commands that arrive first are kept for them.
+    /// If this fails with a timeout it can be called again, fails with [`ErrorKind::NotFound`] if the command is not outstanding.
+    pub fn receive_response(&mut self, id: ID) -> Result<String, Error> {
+        if !self.correlator.is_pending(id) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "No outstanding command with this ID",
+            ));
+        }
+        self.recv_packet(PacketType::ResponseValue, id)
+    }
+
+    /// Stops waiting for the response to a command sent with [`RCONClient::send_request()`], it is discarded when it arrives.
+    pub fn abandon_request(&mut self, id: ID) {
+        self.correlator.abandon(id);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{io::Error, net::TcpStream};
+    use std::{
+        io::{Cursor, Error},
+        net::TcpStream,
+    };
 
     use crate::SimpleIDGenerator;
 
     use super::*;
 
+    /// Replays recorded bytes from a server and ignores what the client writes.
+    struct Replay(Cursor<Vec<u8>>);
+
+    impl Read for Replay {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for Replay {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packet(pkt_type: PacketType, id: i32, body: &str) -> Vec<u8> {
+        Packet::new(pkt_type, body.to_string(), ID::from(id))
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn out_of_order_responses() -> Result<(), Error> {
+        let server = [
+            // Source servers answer the auth request with an empty response first.
+            packet(PacketType::ResponseValue, 0, ""),
+            packet(PacketType::AuthResponse, 0, ""),
+            // A late response to a command from before, then the two commands answered out of order.
+            packet(PacketType::ResponseValue, 7, "stale"),
+            packet(PacketType::ResponseValue, 2, "two"),
+            packet(PacketType::ResponseValue, 1, "one"),
+        ]
+        .concat();
+        let mut client = RCONClient::new(
+            Replay(Cursor::new(server)),
+            SimpleIDGenerator::new(),
+            "password".to_string(),
+        )?;
+        let first = client.send_request("first".to_string())?;
+        assert_eq!(client.send_command("second".to_string())?, "two");
+        assert_eq!(client.receive_response(first)?, "one");
+        assert_eq!(
+            client.receive_response(first).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        // Nothing left to read.
+        assert_eq!(
+            client.send_command("third".to_string()).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        Ok(())
+    }
+
     #[test]
     #[ignore = "Requires RCON Server"]
     fn basic_rcon_client_test() -> Result<(), Error> {
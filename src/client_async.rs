@@ -7,13 +7,27 @@ use std::io::{Error, ErrorKind};
 #[cfg(not(feature = "async-net"))]
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::packet::{packet_id::ID, Packet, PacketError, PacketType, MAX_PACKET_SIZE};
+use crate::correlation::Correlator;
+use crate::encoding::TextEncoding;
+use crate::packet::{
+    packet_id::ID,
+    packet_kind::{PacketKind, ServerPacket, SessionState},
+    Direction, Packet, PacketError, PacketType, MAX_PACKET_SIZE,
+};
+use crate::trace;
 
 /// The base AsyncRCON client. See the [`AsyncRCONClient::new()`] function for info about the fields.
 #[derive(Debug)]
 pub struct AsyncRCONClient<T: AsyncReadExt + AsyncWriteExt, I: Iterator<Item = ID>> {
     socket: T,
     incremental_id: I,
+    encoding: TextEncoding,
+    lenient: bool,
+    correlator: Correlator,
+    /// The command of the last [`AsyncRCONClient::send_command()`] until it finishes, still set at the next call if its future was dropped.
+    command_in_flight: Option<ID>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl<T: AsyncReadExt + AsyncWriteExt + Unpin, I: Iterator<Item = ID>> AsyncRCONClient<T, I> {
@@ -27,15 +41,46 @@ impl<T: AsyncReadExt + AsyncWriteExt + Unpin, I: Iterator<Item = ID>> AsyncRCONC
         socket: T,
         id_generator: I,
         password: String,
+    ) -> Result<AsyncRCONClient<T, I>, Error> {
+        AsyncRCONClient::with_encoding(socket, id_generator, password, TextEncoding::Utf8).await
+    }
+
+    /// Same as [`AsyncRCONClient::new()`], but uses the given [`TextEncoding`] for the password, commands and responses instead of UTF-8.
+    pub async fn with_encoding(
+        socket: T,
+        id_generator: I,
+        password: String,
+        encoding: TextEncoding,
     ) -> Result<AsyncRCONClient<T, I>, Error> {
         let mut client = AsyncRCONClient {
             socket,
             incremental_id: id_generator,
+            encoding,
+            lenient: false,
+            correlator: Correlator::new(),
+            command_in_flight: None,
+            #[cfg(feature = "tracing")]
+            span: trace::connection_span(encoding),
         };
         client.authenticate(password).await?;
         Ok(client)
     }
 
+    /// Sets the [`TextEncoding`] used for commands and responses from now on.
+    pub fn set_encoding(&mut self, encoding: TextEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Gets the [`TextEncoding`] used for commands and responses.
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Sets whether responses may leave out the null bytes that end the body, see [`Packet::try_from_lenient()`]. Off by default.
+    pub fn set_lenient_parsing(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
     fn next_id(&mut self) -> ID {
         self.incremental_id
             .next()
@@ -44,17 +89,29 @@ impl<T: AsyncReadExt + AsyncWriteExt + Unpin, I: Iterator<Item = ID>> AsyncRCONC
 
     async fn send_packet(&mut self, pkt_type: PacketType, body: String) -> Result<ID, Error> {
         let id = self.next_id();
-        let pkt = Vec::from(Packet::new(pkt_type, body, id)?);
-        self.socket.write_all(&pkt).await?;
+        let packet = Packet::new_encoded(pkt_type, &body, id, self.encoding)?;
+        trace::packet_sent(&packet);
+        self.socket.write_all(&Vec::from(packet)).await?;
         Ok(id)
     }
 
+    /// Reads the next whole packet, several packets can arrive in one read and a packet can be split across reads.
     async fn recv_packet_unchecked(&mut self) -> Result<Packet, Error> {
-        let mut buf = [0u8; MAX_PACKET_SIZE];
-        let packet_len = self.socket.read(&mut buf).await?;
-        // Question about buf[..]
-        let packet = Packet::try_from(&buf[..packet_len])?;
-        Ok(packet)
+        loop {
+            if let Some(packet) = self.correlator.next_packet(self.encoding, self.lenient)? {
+                trace::packet_received(&packet);
+                return Ok(packet);
+            }
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let read = self.socket.read(&mut buf).await?;
+            if read == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "The RCON server closed the connection",
+                ));
+            }
+            self.correlator.extend(&buf[..read]);
+        }
     }
 
     async fn recv_packet(
@@ -62,28 +119,50 @@ impl<T: AsyncReadExt + AsyncWriteExt + Unpin, I: Iterator<Item = ID>> AsyncRCONC
         expected_type: PacketType,
         expected_id: ID,
     ) -> Result<String, Error> {
-        let packet = self.recv_packet_unchecked().await?;
-        if packet.get_id() != expected_id {
-            Err(PacketError::UnexpectedID)?;
-        }
+        let packet = loop {
+            if let Some(packet) = self.correlator.take(expected_id) {
+                break packet;
+            }
+            let packet = self.recv_packet_unchecked().await?;
+            if let Some(packet) = self.correlator.route(packet, expected_id) {
+                break packet;
+            }
+        };
         if packet.get_type() != expected_type {
+            trace::unexpected_packet(expected_type, expected_id.into(), &packet);
             Err(PacketError::UnexpectedType)?;
         }
-        Ok(packet.get_body())
+        Ok(packet.decode_body(self.encoding)?)
     }
 
     /// When [`AsyncRCONClient::new()`] is called this method will also be called, but it is exposed separatly in case it is desired. Not sure why it would be.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "rcon_auth", parent = &self.span, skip_all, err)
+    )]
     pub async fn authenticate(&mut self, password: String) -> Result<(), Error> {
+        let _timer = trace::Timer::start();
         let used_id = self.send_packet(PacketType::Auth, password).await?;
         self.wait_authentication(used_id).await
     }
 
     async fn wait_authentication(&mut self, expected_id: ID) -> Result<(), Error> {
-        let packet = self.recv_packet_unchecked().await?;
-
-        if packet.get_type() != PacketType::AuthResponse {
-            return Err(PacketError::UnexpectedType.into());
-        }
+        let packet = loop {
+            let packet = self.recv_packet_unchecked().await?;
+            match packet.get_kind(Direction::ServerToClient, SessionState::AuthPending) {
+                PacketKind::Server(ServerPacket::AuthResponse) => break packet,
+                // Source servers send an empty SERVERDATA_RESPONSE_VALUE right before the SERVERDATA_AUTH_RESPONSE.
+                PacketKind::Server(ServerPacket::ResponseValue)
+                    if packet.get_id() == expected_id => {}
+                PacketKind::Server(ServerPacket::ResponseValue) => {
+                    self.correlator.route(packet, expected_id);
+                }
+                _ => {
+                    trace::unexpected_packet(PacketType::AuthResponse, expected_id.into(), &packet);
+                    return Err(PacketError::UnexpectedType.into());
+                }
+            }
+        };
 
         let packet_id = packet.get_id();
         if packet_id == (-1).into() {
@@ -94,14 +173,61 @@ impl<T: AsyncReadExt + AsyncWriteExt + Unpin, I: Iterator<Item = ID>> AsyncRCONC
         } else if expected_id == packet_id {
             Ok(())
         } else {
+            trace::unexpected_packet(PacketType::AuthResponse, expected_id.into(), &packet);
             Err(PacketError::UnexpectedID.into())
         }
     }
 
     /// Send the given command to the server and returns the response. This does not handle multipacket responses.
+    ///
+    /// If the future is dropped before it finishes, for example by a timeout, the late response to the command is discarded when it arrives instead of being taken as the response to the next command.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "rcon_command",
+            parent = &self.span,
+            skip_all,
+            fields(command = %trace::recorded_command(&cmd)),
+            err
+        )
+    )]
     pub async fn send_command(&mut self, cmd: String) -> Result<String, Error> {
+        let _timer = trace::Timer::start();
+        // A command still in flight here was cancelled, for example by a timeout, its late response is discarded.
+        if let Some(cancelled) = self.command_in_flight.take() {
+            self.correlator.abandon(cancelled);
+        }
+        let used_id = self.send_request(cmd).await?;
+        self.command_in_flight = Some(used_id);
+        let response = self.recv_packet(PacketType::ResponseValue, used_id).await;
+        self.command_in_flight = None;
+        response.inspect_err(|_| self.correlator.abandon(used_id))
+    }
+
+    /// Sends a command without waiting for the response, which is read with [`AsyncRCONClient::receive_response()`].
+    /// Several commands can be in flight at once, their responses are matched by ID whatever order they arrive in.
+    pub async fn send_request(&mut self, cmd: String) -> Result<ID, Error> {
         let used_id = self.send_packet(PacketType::ExecCommand, cmd).await?;
-        self.recv_packet(PacketType::ResponseValue, used_id).await
+        self.correlator.register(used_id);
+        Ok(used_id)
+    }
+
+    /// Waits for the response to a command sent with [`AsyncRCONClient::send_request()`].
+    /// Responses to other commands that arrive first are kept for them.
+    /// If the future is dropped, for example by a timeout, this can be called again. Fails with [`ErrorKind::NotFound`] if the command is not outstanding.
+    pub async fn receive_response(&mut self, id: ID) -> Result<String, Error> {
+        if !self.correlator.is_pending(id) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "No outstanding command with this ID",
+            ));
+        }
+        self.recv_packet(PacketType::ResponseValue, id).await
+    }
+
+    /// Stops waiting for the response to a command sent with [`AsyncRCONClient::send_request()`], it is discarded when it arrives.
+    pub fn abandon_request(&mut self, id: ID) {
+        self.correlator.abandon(id);
     }
 }
 
@@ -117,6 +243,130 @@ mod tests {
     #[cfg(not(feature = "async-net"))]
     use tokio::net::TcpStream;
 
+    /// Replays recorded bytes from a server and ignores what the client writes.
+    struct Replay(std::io::Cursor<Vec<u8>>);
+
+    #[cfg(feature = "tokio")]
+    #[cfg(not(feature = "async-net"))]
+    impl tokio::io::AsyncRead for Replay {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            let read = std::io::Read::read(&mut self.0, buf.initialize_unfilled())?;
+            buf.advance(read);
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[cfg(not(feature = "async-net"))]
+    impl tokio::io::AsyncWrite for Replay {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "async-net")]
+    #[cfg(not(feature = "tokio"))]
+    impl futures::AsyncRead for Replay {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(std::io::Read::read(&mut self.0, buf))
+        }
+    }
+
+    #[cfg(feature = "async-net")]
+    #[cfg(not(feature = "tokio"))]
+    impl futures::AsyncWrite for Replay {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    fn packet(pkt_type: PacketType, id: i32, body: &str) -> Vec<u8> {
+        Packet::new(pkt_type, body.to_string(), ID::from(id))
+            .unwrap()
+            .into()
+    }
+
+    #[tokio_macros::test]
+    async fn out_of_order_responses() -> Result<(), Error> {
+        let server = [
+            // Source servers answer the auth request with an empty response first.
+            packet(PacketType::ResponseValue, 0, ""),
+            packet(PacketType::AuthResponse, 0, ""),
+            // A late response to a command from before, then the two commands answered out of order.
+            packet(PacketType::ResponseValue, 7, "stale"),
+            packet(PacketType::ResponseValue, 2, "two"),
+            packet(PacketType::ResponseValue, 1, "one"),
+        ]
+        .concat();
+        let mut client = AsyncRCONClient::new(
+            Replay(std::io::Cursor::new(server)),
+            SimpleIDGenerator::new(),
+            "password".to_string(),
+        )
+        .await?;
+        let first = client.send_request("first".to_string()).await?;
+        assert_eq!(client.send_command("second".to_string()).await?, "two");
+        assert_eq!(client.receive_response(first).await?, "one");
+        assert_eq!(
+            client.receive_response(first).await.unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        // Nothing left to read.
+        assert_eq!(
+            client
+                .send_command("third".to_string())
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::UnexpectedEof
+        );
+        Ok(())
+    }
+
     #[tokio_macros::test]
     #[ignore = "Requires RCON Server"]
     async fn basic_rcon_client_test() {
@@ -0,0 +1,148 @@
+//! Contains the implementation for [`RconCodec`]
+
+use std::io::Error;
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::encoding::TextEncoding;
+use crate::packet::{Packet, PacketError, MAX_PACKET_SIZE, MIN_PACKET_SIZE};
+
+/// The size in bytes of the size field at the start of every packet.
+const SIZE_FIELD_LEN: usize = 4;
+
+/// A [`Decoder`] and [`Encoder`] for RCON [`Packet`]s, for use with [`tokio_util::codec::Framed`] and friends.
+///
+/// # Example
+/// ```no_run
+/// use tokio_util::codec::Framed;
+/// use ya_rcon::codec::RconCodec;
+/// # async fn connect() -> std::io::Result<()> {
+/// let stream = tokio::net::TcpStream::connect("127.0.0.1:27015").await?;
+/// let framed = Framed::new(stream, RconCodec::new());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RconCodec {
+    encoding: TextEncoding,
+}
+
+impl RconCodec {
+    /// Creates a new instance of the codec that expects UTF-8 packet bodies. Same as calling [`RconCodec::default()`]
+    pub fn new() -> RconCodec {
+        RconCodec::default()
+    }
+
+    /// Creates a new instance of the codec that checks the packet bodies are valid for the given [`TextEncoding`].
+    pub fn with_encoding(encoding: TextEncoding) -> RconCodec {
+        RconCodec { encoding }
+    }
+
+    /// Gets the [`TextEncoding`] the codec checks the packet bodies against.
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+}
+
+impl Decoder for RconCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < SIZE_FIELD_LEN {
+            return Ok(None);
+        }
+        let size = i32::from_le_bytes(
+            src[..SIZE_FIELD_LEN]
+                .try_into()
+                .expect("slice with incorrect length"),
+        );
+        let frame_len = match usize::try_from(size) {
+            Ok(size) if (MIN_PACKET_SIZE..=MAX_PACKET_SIZE - SIZE_FIELD_LEN).contains(&size) => {
+                size + SIZE_FIELD_LEN
+            }
+            _ => return Err(PacketError::InvalidPayloadLength.into()),
+        };
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        let packet = Packet::try_from_encoded(&src[..frame_len], self.encoding);
+        src.advance(frame_len);
+        Ok(Some(packet?))
+    }
+}
+
+impl Encoder<Packet> for RconCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = item.encoded_len();
+        if len > MAX_PACKET_SIZE {
+            return Err(PacketError::InvalidPayloadLength.into());
+        }
+        dst.reserve(len);
+        item.put_into(dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{packet_id::ID, PacketType};
+
+    #[test]
+    fn decode_partial_frames() {
+        let mut codec = RconCodec::new();
+        let mut src = BytesMut::new();
+        codec
+            .encode(
+                Packet::new(PacketType::ResponseValue, "hello".to_string(), ID::from(4)).unwrap(),
+                &mut src,
+            )
+            .unwrap();
+        codec
+            .encode(
+                Packet::new(PacketType::ResponseValue, String::new(), ID::from(5)).unwrap(),
+                &mut src,
+            )
+            .unwrap();
+        let full = src.split();
+
+        src.extend_from_slice(&full[..3]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&full[3..10]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&full[10..]);
+
+        let first = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(first.get_id(), ID::from(4));
+        assert_eq!(first.get_body(), "hello");
+        let second = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(second.get_id(), ID::from(5));
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_rejects_invalid_sizes() {
+        let mut codec = RconCodec::new();
+        for size in [-1_i32, 9, 4097] {
+            let mut src = BytesMut::from(&size.to_le_bytes()[..]);
+            assert!(codec.decode(&mut src).is_err());
+        }
+    }
+
+    #[test]
+    fn encode_rejects_oversized_packets() {
+        let body = "a".repeat(MAX_PACKET_SIZE);
+        let size = i32::try_from(body.len() + MIN_PACKET_SIZE).unwrap();
+        let packet = Packet::new_raw(PacketType::ExecCommand, body, size, ID::from(0));
+        assert!(RconCodec::new()
+            .encode(packet, &mut BytesMut::new())
+            .is_err());
+    }
+}
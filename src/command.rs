@@ -0,0 +1,256 @@
+//! Contains the implementation for [`Command`]
+
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind},
+};
+
+use crate::packet::MAX_PAYLOAD_SIZE;
+
+/// How a game splits a command into arguments, which decides how [`Command::with_quoted`] quotes a value.
+/// The quoting of each game is set in its [`crate::games::Dialect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quoting {
+    /// Source engine games and most games based on them: double quotes without escape sequences, and `;` chains commands.
+    /// Values with `"`, `;` or control characters are rejected.
+    #[default]
+    Source,
+    /// Double quotes where `\"` and `\\` are escaped, such as the command parser of Minecraft.
+    /// `;` has no special meaning, values with control characters are rejected.
+    Backslash,
+    /// Games without quoting such as Palworld and ARK, where a value with spaces can only be sent as trailing text.
+    /// Quoted values are checked like [`Command::with_arg`].
+    None,
+}
+
+fn invalid(what: &str, value: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("Invalid {what} {value:?}"))
+}
+
+/// Checks that a value can be sent as a single argument without quotes, it can not end the argument or the command early.
+pub(crate) fn word<'a>(value: &'a str, what: &str) -> Result<&'a str, Error> {
+    if value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == ';' || c == '"')
+    {
+        return Err(invalid(what, value));
+    }
+    Ok(value)
+}
+
+impl Quoting {
+    /// Quotes a value as a single argument.
+    pub(crate) fn quote(self, value: &str, what: &str) -> Result<String, Error> {
+        match self {
+            Quoting::Source => {
+                if value
+                    .chars()
+                    .any(|c| c.is_control() || c == ';' || c == '"')
+                {
+                    return Err(invalid(what, value));
+                }
+                Ok(format!("\"{value}\""))
+            }
+            Quoting::Backslash => {
+                if value.chars().any(char::is_control) {
+                    return Err(invalid(what, value));
+                }
+                Ok(format!(
+                    "\"{}\"",
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                ))
+            }
+            Quoting::None => word(value, what).map(str::to_string),
+        }
+    }
+
+    /// Checks free text at the end of a command.
+    fn text(self, value: &str, what: &str) -> Result<(), Error> {
+        if value
+            .chars()
+            .any(|c| c.is_control() || (self == Quoting::Source && c == ';'))
+        {
+            return Err(invalid(what, value));
+        }
+        Ok(())
+    }
+}
+
+/// Builds a command from arguments, so values such as player names can not change what the command does.
+///
+/// Arguments are checked as they are added and the first error is returned by [`Command::build`], which also checks that the command fits in a packet.
+/// Every [`Quoting`] rejects control characters, including null bytes and line breaks.
+///
+/// # Example
+/// ```
+/// use ya_rcon::command::{Command, Quoting};
+///
+/// let command = Command::new("kickid").with_arg(12).with_quoted("Spamming; sorry").build();
+/// assert!(command.is_err());
+///
+/// let command = Command::new("kickid").with_arg(12).with_quoted("Spamming").build().unwrap();
+/// assert_eq!(command, "kickid 12 \"Spamming\"");
+///
+/// let command = Command::new("tellraw")
+///     .with_quoting(Quoting::Backslash)
+///     .with_arg("@a")
+///     .with_quoted(r#"Bob "the builder""#)
+///     .build()
+///     .unwrap();
+/// assert_eq!(command, r#"tellraw @a "Bob \"the builder\"""#);
+/// ```
+#[derive(Debug)]
+pub struct Command {
+    quoting: Quoting,
+    command: String,
+    has_text: bool,
+    error: Option<Error>,
+}
+
+impl Command {
+    /// Starts a command with the given name, which is checked like [`Command::with_arg`].
+    pub fn new(name: &str) -> Command {
+        let mut command = Command {
+            quoting: Quoting::default(),
+            command: String::new(),
+            has_text: false,
+            error: None,
+        };
+        match word(name, "command name") {
+            Ok(name) => command.command.push_str(name),
+            Err(e) => command.error = Some(e),
+        }
+        command
+    }
+
+    /// Sets how quoted arguments are quoted, [`Quoting::Source`] by default.
+    pub fn with_quoting(mut self, quoting: Quoting) -> Command {
+        self.quoting = quoting;
+        self
+    }
+
+    fn push(mut self, argument: Result<String, Error>) -> Command {
+        if self.error.is_some() {
+            return self;
+        }
+        if self.has_text {
+            self.error = Some(Error::new(
+                ErrorKind::InvalidInput,
+                "Arguments can not follow trailing text",
+            ));
+            return self;
+        }
+        match argument {
+            Ok(argument) => {
+                self.command.push(' ');
+                self.command.push_str(&argument);
+            }
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    /// Adds an unquoted argument such as a number or an ID, it can not contain whitespace, `"`, `;` or control characters.
+    pub fn with_arg(self, value: impl Display) -> Command {
+        let value = value.to_string();
+        let argument = word(&value, "argument").map(str::to_string);
+        self.push(argument)
+    }
+
+    /// Adds a quoted argument such as a player name or a reason, see [`Quoting`] for what is allowed.
+    pub fn with_quoted(self, value: &str) -> Command {
+        let argument = self.quoting.quote(value, "argument");
+        self.push(argument)
+    }
+
+    /// Adds unquoted free text such as a chat message, which the game reads up to the end of the command.
+    /// No arguments can be added after it, with [`Quoting::Source`] it can not contain `;`.
+    pub fn with_text(self, value: &str) -> Command {
+        let argument = self.quoting.text(value, "text").map(|()| value.to_string());
+        let mut command = self.push(argument);
+        command.has_text = true;
+        command
+    }
+
+    /// Returns the command, or the first invalid argument as an [`ErrorKind::InvalidInput`] error.
+    /// Also fails if the command is longer than [`MAX_PAYLOAD_SIZE`] bytes in UTF-8.
+    pub fn build(self) -> Result<String, Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.command.len() > MAX_PAYLOAD_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The command is {} bytes, the limit is {MAX_PAYLOAD_SIZE}",
+                    self.command.len()
+                ),
+            ));
+        }
+        Ok(self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_injection() {
+        for name in ["Bob\"; quit", "Bob\nquit", "Bob\0"] {
+            assert_eq!(
+                Command::new("kick")
+                    .with_quoted(name)
+                    .build()
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::InvalidInput,
+                "{name:?}"
+            );
+        }
+        assert!(Command::new("say").with_text("a; quit").build().is_err());
+        assert!(Command::new("kick bob").build().is_err());
+        assert!(Command::new("kick").with_arg("bob quit").build().is_err());
+        assert!(Command::new("say")
+            .with_text("hi")
+            .with_arg(1)
+            .build()
+            .is_err());
+        assert!(Command::new("Broadcast")
+            .with_quoting(Quoting::None)
+            .with_quoted("two words")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn builds_commands() {
+        assert_eq!(
+            Command::new("say")
+                .with_text("Restart in 5 minutes")
+                .build()
+                .unwrap(),
+            "say Restart in 5 minutes"
+        );
+        assert_eq!(
+            Command::new("msg")
+                .with_quoting(Quoting::Backslash)
+                .with_quoted(r"C:\ 1;2")
+                .build()
+                .unwrap(),
+            r#"msg "C:\\ 1;2""#
+        );
+        assert_eq!(
+            Command::new("Broadcast")
+                .with_quoting(Quoting::None)
+                .with_text("Hello; world")
+                .build()
+                .unwrap(),
+            "Broadcast Hello; world"
+        );
+        let long = "a".repeat(MAX_PAYLOAD_SIZE);
+        assert!(Command::new("say").with_text(&long).build().is_err());
+        assert!(Command::new("say").with_text(&long[4..]).build().is_ok());
+    }
+}
@@ -0,0 +1,256 @@
+//! Contains the implementation for [`CommandQueue`]
+//!
+//! Some servers (ARK, Palworld) drop or mangle responses when commands arrive too fast. A [`CommandQueue`] puts a bounded queue and an optional [`TokenBucket`] in front of a single connection:
+//! the [`QueueWorker`] owns the client and runs the queued commands one at a time, while any number of [`CommandQueue`] handles submit commands and wait for their responses.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! # #[cfg(feature = "tokio")]
+//! use tokio::net::TcpStream;
+//! # #[cfg(not(feature = "tokio"))]
+//! # use async_net::TcpStream;
+//! use ya_rcon::client_async::AsyncRCONClient;
+//! use ya_rcon::command_queue::{CommandQueue, QueueConfig};
+//! use ya_rcon::rate_limit::TokenBucket;
+//! use ya_rcon::SimpleIDGenerator;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").await?;
+//! let client = AsyncRCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).await?;
+//! let config = QueueConfig::new(32).with_rate_limit(TokenBucket::per_second(2));
+//! let (mut queue, worker) = CommandQueue::new(client, config);
+//! // Run `worker.run()` on its own task, it finishes once every handle is dropped.
+//! # let _ = worker;
+//! let players = queue.send_command("ListPlayers".to_string()).await?;
+//! queue.send_urgent("KickPlayer 1234".to_string()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{Error, ErrorKind};
+
+use futures::{
+    channel::{mpsc, oneshot},
+    SinkExt, StreamExt,
+};
+
+use crate::{rate_limit::TokenBucket, service::AsyncCommandService};
+
+/// The lane a command is queued in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    /// Commands are run in the order they were queued.
+    #[default]
+    Normal,
+    /// Commands skip ahead of every [`Priority::Normal`] command that is still waiting, for example `kick` or `ban`.
+    Urgent,
+}
+
+/// The configuration of a [`CommandQueue`].
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    capacity: usize,
+    urgent_capacity: usize,
+    rate_limit: Option<TokenBucket>,
+}
+
+impl QueueConfig {
+    /// Creates a configuration where up to `capacity` normal commands can wait in the queue, without a rate limit.
+    /// When the queue is full, [`CommandQueue::send_command()`] waits until there is room again.
+    pub fn new(capacity: usize) -> QueueConfig {
+        QueueConfig {
+            capacity,
+            urgent_capacity: capacity,
+            rate_limit: None,
+        }
+    }
+
+    /// Sets how many urgent commands can wait in the queue, by default the same as the normal capacity.
+    pub fn with_urgent_capacity(mut self, urgent_capacity: usize) -> QueueConfig {
+        self.urgent_capacity = urgent_capacity;
+        self
+    }
+
+    /// Runs at most as many commands as the [`TokenBucket`] allows. The limit applies to the connection, so it is shared by every handle of the queue.
+    /// Urgent commands skip the queue but still wait for a token.
+    pub fn with_rate_limit(mut self, rate_limit: TokenBucket) -> QueueConfig {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Gets how many normal commands can wait in the queue.
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    command: String,
+    reply: oneshot::Sender<Result<String, Error>>,
+}
+
+/// A handle to submit commands to a [`QueueWorker`]. Handles can be cloned and shared between tasks.
+///
+/// Every handle can always queue one command of each priority on top of the configured capacity.
+#[derive(Debug, Clone)]
+pub struct CommandQueue {
+    normal: mpsc::Sender<Job>,
+    urgent: mpsc::Sender<Job>,
+}
+
+impl CommandQueue {
+    /// Creates a new queue in front of `service`, usually an [`crate::client_async::AsyncRCONClient`] or a stack of [`crate::service`] layers around one.
+    /// The commands only run while [`QueueWorker::run()`] is polled.
+    pub fn new<S: AsyncCommandService>(
+        service: S,
+        config: QueueConfig,
+    ) -> (CommandQueue, QueueWorker<S>) {
+        let (normal, normal_rx) = mpsc::channel(config.capacity);
+        let (urgent, urgent_rx) = mpsc::channel(config.urgent_capacity);
+        let worker = QueueWorker {
+            service,
+            normal: normal_rx,
+            urgent: urgent_rx,
+            rate_limit: config.rate_limit,
+        };
+        (CommandQueue { normal, urgent }, worker)
+    }
+
+    /// Queues the command with [`Priority::Normal`] and waits for the response.
+    pub async fn send_command(&mut self, cmd: String) -> Result<String, Error> {
+        self.send_with_priority(cmd, Priority::Normal).await
+    }
+
+    /// Queues the command with [`Priority::Urgent`] and waits for the response.
+    pub async fn send_urgent(&mut self, cmd: String) -> Result<String, Error> {
+        self.send_with_priority(cmd, Priority::Urgent).await
+    }
+
+    /// Queues the command and waits for the response. If the lane is full this first waits until there is room.
+    pub async fn send_with_priority(
+        &mut self,
+        cmd: String,
+        priority: Priority,
+    ) -> Result<String, Error> {
+        let (reply, response) = oneshot::channel();
+        let job = Job {
+            command: cmd,
+            reply,
+        };
+        let lane = match priority {
+            Priority::Normal => &mut self.normal,
+            Priority::Urgent => &mut self.urgent,
+        };
+        lane.send(job).await.map_err(|_| closed())?;
+        response.await.map_err(|_| closed())?
+    }
+}
+
+impl AsyncCommandService for CommandQueue {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        self.send_command(command).await
+    }
+}
+
+/// Runs the commands submitted through a [`CommandQueue`], see [`CommandQueue::new()`].
+#[derive(Debug)]
+pub struct QueueWorker<S> {
+    service: S,
+    normal: mpsc::Receiver<Job>,
+    urgent: mpsc::Receiver<Job>,
+    rate_limit: Option<TokenBucket>,
+}
+
+impl<S: AsyncCommandService> QueueWorker<S> {
+    /// Runs queued commands until every [`CommandQueue`] handle has been dropped, then returns the service.
+    /// Errors are passed to the caller of the command and do not stop the worker.
+    pub async fn run(mut self) -> S {
+        loop {
+            let job = futures::select_biased! {
+                job = self.urgent.next() => job,
+                job = self.normal.next() => job,
+                complete => return self.service,
+            };
+            let Some(job) = job else {
+                continue;
+            };
+            if let Some(bucket) = &mut self.rate_limit {
+                bucket.acquire().await;
+            }
+            let result = self.service.call(job.command).await;
+            // The caller may have stopped waiting for the response.
+            let _ = job.reply.send(result);
+        }
+    }
+}
+
+fn closed() -> Error {
+    Error::new(ErrorKind::BrokenPipe, "RCON command queue was closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::service::service_fn;
+
+    fn recording_service(log: Arc<Mutex<Vec<String>>>) -> impl AsyncCommandService {
+        service_fn(move |cmd: String| {
+            log.lock().unwrap().push(cmd.clone());
+            std::future::ready(Ok(cmd))
+        })
+    }
+
+    #[tokio_macros::test]
+    async fn urgent_commands_skip_the_queue() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (queue, worker) =
+            CommandQueue::new(recording_service(log.clone()), QueueConfig::new(8));
+
+        let callers = async move {
+            let (mut a, mut b, mut kick) = (queue.clone(), queue.clone(), queue);
+            let responses = futures::join!(
+                a.send_command("status".to_string()),
+                b.send_command("players".to_string()),
+                kick.send_urgent("kick 7".to_string()),
+            );
+            assert_eq!(responses.0.unwrap(), "status");
+            assert_eq!(responses.1.unwrap(), "players");
+            assert_eq!(responses.2.unwrap(), "kick 7");
+        };
+        futures::join!(worker.run(), callers);
+        assert_eq!(*log.lock().unwrap(), ["kick 7", "status", "players"]);
+    }
+
+    #[tokio_macros::test]
+    async fn commands_are_rate_limited() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let config =
+            QueueConfig::new(8).with_rate_limit(TokenBucket::new(1, Duration::from_millis(30)));
+        let (mut queue, worker) = CommandQueue::new(recording_service(log.clone()), config);
+
+        let start = Instant::now();
+        let callers = async move {
+            for _ in 0..3 {
+                queue.send_command("status".to_string()).await.unwrap();
+            }
+        };
+        futures::join!(worker.run(), callers);
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+
+    #[tokio_macros::test]
+    async fn closed_queue_is_an_error() {
+        let (mut queue, worker) =
+            CommandQueue::new(recording_service(Arc::default()), QueueConfig::new(1));
+        drop(worker);
+        let error = queue.send_command("status".to_string()).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,160 @@
+//! Contains the [`Correlator`] the clients use to match responses to the requests they answer.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::encoding::TextEncoding;
+use crate::packet::{packet_id::ID, Packet, PacketError, MAX_PACKET_SIZE, MIN_PACKET_SIZE};
+use crate::trace;
+
+/// Splits the bytes read from the server into packets and keeps track of the requests that have not been answered yet.
+///
+/// Responses to other outstanding requests are kept until they are asked for, responses nobody waits for any more are discarded.
+/// That way a late response, for example to a command that timed out, does not get mistaken for the response to the next command.
+#[derive(Debug, Default)]
+pub(crate) struct Correlator {
+    pending: HashSet<ID>,
+    responses: HashMap<ID, Packet>,
+    buf: Vec<u8>,
+}
+
+impl Correlator {
+    pub(crate) fn new() -> Correlator {
+        Correlator::default()
+    }
+
+    /// Starts waiting for the response to a request.
+    pub(crate) fn register(&mut self, id: ID) {
+        self.pending.insert(id);
+    }
+
+    /// Stops waiting for the response to a request, it is discarded when it arrives.
+    pub(crate) fn abandon(&mut self, id: ID) {
+        self.pending.remove(&id);
+        self.responses.remove(&id);
+    }
+
+    /// Whether the request has not been answered or its response has not been taken yet.
+    pub(crate) fn is_pending(&self, id: ID) -> bool {
+        self.pending.contains(&id) || self.responses.contains_key(&id)
+    }
+
+    /// Adds bytes read from the server.
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Takes the next whole packet out of the bytes read so far, `None` if more bytes are needed.
+    /// A packet with an invalid size leaves no way to find the next one, so everything read so far is dropped.
+    /// With `lenient` the null bytes that end the body may be missing, see [`Packet::try_from_lenient`].
+    pub(crate) fn next_packet(
+        &mut self,
+        encoding: TextEncoding,
+        lenient: bool,
+    ) -> Result<Option<Packet>, PacketError> {
+        let Some(size) = self.buf.get(..4) else {
+            return Ok(None);
+        };
+        let size = i32::from_le_bytes(size.try_into().expect("slice with incorrect length"));
+        let frame_len = match usize::try_from(size) {
+            Ok(size) if (MIN_PACKET_SIZE..=MAX_PACKET_SIZE - 4).contains(&size) => size + 4,
+            _ => {
+                self.buf.clear();
+                return Err(PacketError::InvalidPayloadLength);
+            }
+        };
+        if self.buf.len() < frame_len {
+            return Ok(None);
+        }
+        let frame: Vec<u8> = self.buf.drain(..frame_len).collect();
+        if lenient {
+            Packet::try_from_lenient(&frame, encoding).map(Some)
+        } else {
+            Packet::try_from_encoded(&frame, encoding).map(Some)
+        }
+    }
+
+    /// Takes the response to `id` if it arrived while waiting for another request.
+    pub(crate) fn take(&mut self, id: ID) -> Option<Packet> {
+        self.responses.remove(&id)
+    }
+
+    /// Returns the packet if it answers `expected`. Otherwise it is kept for [`Correlator::take`] if it answers another outstanding request, or discarded.
+    pub(crate) fn route(&mut self, packet: Packet, expected: ID) -> Option<Packet> {
+        let id = packet.get_id();
+        if id == expected {
+            self.pending.remove(&id);
+            return Some(packet);
+        }
+        if self.pending.remove(&id) {
+            trace::buffered_packet(expected.into(), &packet);
+            self.responses.insert(id, packet);
+        } else {
+            trace::stale_packet(expected.into(), &packet);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PacketType;
+
+    fn response(id: i32, body: &str) -> Vec<u8> {
+        Packet::new(PacketType::ResponseValue, body.to_string(), ID::from(id))
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn resynchronizes_late_responses() {
+        let mut correlator = Correlator::new();
+        // Request 1 timed out, 2 was sent without waiting, 3 is being waited for.
+        correlator.register(ID::from(1));
+        correlator.abandon(ID::from(1));
+        correlator.register(ID::from(2));
+        correlator.register(ID::from(3));
+
+        let mut bytes = [
+            response(1, "late"),
+            response(2, "two"),
+            response(3, "three"),
+        ]
+        .concat();
+        let rest = bytes.split_off(bytes.len() - 5);
+        correlator.extend(&bytes);
+
+        let mut answer = None;
+        while let Some(packet) = correlator.next_packet(TextEncoding::Utf8, false).unwrap() {
+            answer = answer.or(correlator.route(packet, ID::from(3)));
+        }
+        assert!(answer.is_none());
+        correlator.extend(&rest);
+        let packet = correlator
+            .next_packet(TextEncoding::Utf8, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            correlator.route(packet, ID::from(3)).unwrap().get_body(),
+            "three"
+        );
+
+        assert!(correlator.is_pending(ID::from(2)));
+        assert_eq!(correlator.take(ID::from(2)).unwrap().get_body(), "two");
+        assert!(!correlator.is_pending(ID::from(1)));
+        assert!(correlator.take(ID::from(1)).is_none());
+    }
+
+    #[test]
+    fn invalid_size_drops_buffer() {
+        let mut correlator = Correlator::new();
+        correlator.extend(&[0xff, 0xff, 0xff, 0x7f, 1, 2, 3]);
+        assert!(correlator.next_packet(TextEncoding::Utf8, false).is_err());
+        correlator.extend(&response(4, "ok"));
+        let packet = correlator
+            .next_packet(TextEncoding::Utf8, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.get_body(), "ok");
+    }
+}
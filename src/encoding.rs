@@ -0,0 +1,185 @@
+//! Contains the implementation for [`TextEncoding`]
+
+use std::borrow::Cow;
+
+use crate::packet::PacketError;
+
+/// The text encoding used for the body of RCON packets.
+///
+/// The protocol itself only deals in bytes, but most servers use UTF-8. Some older Source mods use Windows-1252 and some community servers use codepages such as GBK or Shift-JIS (those require the `encoding` feature).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// UTF-8, the default.
+    #[default]
+    Utf8,
+    /// UTF-8, but invalid sequences in responses are replaced with `U+FFFD` instead of failing.
+    /// For servers such as Palworld that cut responses off in the middle of a character.
+    Utf8Lossy,
+    /// [Windows-1252](https://en.wikipedia.org/wiki/Windows-1252), used by older Source engine mods.
+    Windows1252,
+    /// GBK, used by some Chinese community servers.
+    #[cfg(feature = "encoding")]
+    Gbk,
+    /// Shift-JIS, used by some Japanese community servers.
+    #[cfg(feature = "encoding")]
+    ShiftJis,
+    /// Any other encoding supported by [`encoding_rs`].
+    #[cfg(feature = "encoding")]
+    Other(&'static encoding_rs::Encoding),
+}
+
+/// The characters for the bytes `0x80..=0x9F` in Windows-1252. The rest of the codepage is the same as Latin-1.
+/// The undefined bytes are mapped to the matching C1 control character like the [WHATWG](https://encoding.spec.whatwg.org/index-windows-1252.txt) index does.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl TextEncoding {
+    /// Looks up an encoding by its label, for example `"utf-8"`, `"windows-1252"` or `"shift_jis"`.
+    /// Without the `encoding` feature only UTF-8 and Windows-1252 are recognised.
+    pub fn from_label(label: &str) -> Option<TextEncoding> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => return Some(TextEncoding::Utf8),
+            "utf-8-lossy" | "utf8-lossy" => return Some(TextEncoding::Utf8Lossy),
+            "windows-1252" | "cp1252" => return Some(TextEncoding::Windows1252),
+            #[cfg(feature = "encoding")]
+            "gbk" => return Some(TextEncoding::Gbk),
+            #[cfg(feature = "encoding")]
+            "shift_jis" | "shift-jis" | "sjis" => return Some(TextEncoding::ShiftJis),
+            _ => {}
+        }
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = encoding_rs::Encoding::for_label(label.trim().as_bytes()) {
+            return Some(TextEncoding::Other(encoding));
+        }
+        None
+    }
+
+    /// Encodes the given string into bytes.
+    /// Returns [`PacketError::InvalidPacketBody`] if a character can not be represented in this encoding.
+    pub fn encode<'a>(&self, text: &'a str) -> Result<Cow<'a, [u8]>, PacketError> {
+        match self {
+            TextEncoding::Utf8 | TextEncoding::Utf8Lossy => Ok(Cow::Borrowed(text.as_bytes())),
+            TextEncoding::Windows1252 => text
+                .chars()
+                .map(encode_windows_1252)
+                .collect::<Option<Vec<u8>>>()
+                .map(Cow::Owned)
+                .ok_or(PacketError::InvalidPacketBody),
+            #[cfg(feature = "encoding")]
+            TextEncoding::Gbk => encode_with(encoding_rs::GBK, text),
+            #[cfg(feature = "encoding")]
+            TextEncoding::ShiftJis => encode_with(encoding_rs::SHIFT_JIS, text),
+            #[cfg(feature = "encoding")]
+            TextEncoding::Other(encoding) => encode_with(encoding, text),
+        }
+    }
+
+    /// Decodes the given bytes into a string.
+    /// Returns [`PacketError::InvalidPacketBody`] if the bytes are not valid for this encoding.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, str>, PacketError> {
+        match self {
+            TextEncoding::Utf8 => std::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|_| PacketError::InvalidPacketBody),
+            TextEncoding::Utf8Lossy => Ok(String::from_utf8_lossy(bytes)),
+            TextEncoding::Windows1252 => Ok(Cow::Owned(
+                bytes.iter().copied().map(decode_windows_1252).collect(),
+            )),
+            #[cfg(feature = "encoding")]
+            TextEncoding::Gbk => decode_with(encoding_rs::GBK, bytes),
+            #[cfg(feature = "encoding")]
+            TextEncoding::ShiftJis => decode_with(encoding_rs::SHIFT_JIS, bytes),
+            #[cfg(feature = "encoding")]
+            TextEncoding::Other(encoding) => decode_with(encoding, bytes),
+        }
+    }
+}
+
+fn decode_windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+        x => char::from(x),
+    }
+}
+
+fn encode_windows_1252(c: char) -> Option<u8> {
+    match u32::from(c) {
+        x @ (0x00..=0x7F | 0xA0..=0xFF) => Some(x as u8),
+        _ => WINDOWS_1252_HIGH
+            .iter()
+            .position(|&high| high == c)
+            .map(|index| 0x80 + index as u8),
+    }
+}
+
+#[cfg(feature = "encoding")]
+fn encode_with<'a>(
+    encoding: &'static encoding_rs::Encoding,
+    text: &'a str,
+) -> Result<Cow<'a, [u8]>, PacketError> {
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        Err(PacketError::InvalidPacketBody)
+    } else {
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "encoding")]
+fn decode_with<'a>(
+    encoding: &'static encoding_rs::Encoding,
+    bytes: &'a [u8],
+) -> Result<Cow<'a, str>, PacketError> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .ok_or(PacketError::InvalidPacketBody)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_1252_round_trip() {
+        let text = "Caf\u{E9} \u{20AC}5 \u{201C}quoted\u{201D}";
+        let bytes = TextEncoding::Windows1252.encode(text).unwrap();
+        assert_eq!(&bytes[..], b"Caf\xE9 \x805 \x93quoted\x94".as_slice());
+        assert_eq!(TextEncoding::Windows1252.decode(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn unencodable_text_is_rejected() {
+        assert!(TextEncoding::Windows1252
+            .encode("\u{4F60}\u{597D}")
+            .is_err());
+        assert!(TextEncoding::Utf8.decode(b"\xE9t\xE9").is_err());
+    }
+
+    #[test]
+    fn lossy_utf8_only_replaces_invalid_sequences() {
+        // `Zoë` cut off after the first byte of `ë`.
+        assert_eq!(
+            TextEncoding::Utf8Lossy.decode(b"Zo\xC3").unwrap(),
+            "Zo\u{FFFD}"
+        );
+        assert_eq!(
+            TextEncoding::Utf8Lossy.decode("Zoë".as_bytes()).unwrap(),
+            "Zoë"
+        );
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn gbk_and_shift_jis_round_trip() {
+        for encoding in [TextEncoding::Gbk, TextEncoding::ShiftJis] {
+            let text = "\u{65E5}\u{672C}";
+            let bytes = encoding.encode(text).unwrap();
+            assert_ne!(&bytes[..], text.as_bytes());
+            assert_eq!(encoding.decode(&bytes).unwrap(), text);
+        }
+    }
+}
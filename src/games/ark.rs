@@ -0,0 +1,176 @@
+//! Contains typed wrappers for the commands of ARK: Survival Evolved servers.
+//!
+//! Every command goes through [`Dialect::ark()`], so commands without output return an empty string instead of `Server received, But no response!!`.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::ark::Ark;
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27020").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Ark::new(client);
+//! for player in server.list_players().unwrap() {
+//!     println!("{} {}", player.name, player.steam_id);
+//! }
+//! server.broadcast("Saving the world").unwrap();
+//! server.save_world().unwrap();
+//! ```
+
+use std::io::{Error, ErrorKind};
+
+use super::{single_argument, Dialect, DialectService};
+use crate::service::{CommandService, Layer};
+
+/// A player in the response to `ListPlayers`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    /// The name of the player.
+    pub name: String,
+    /// The SteamID64 of the player, used by `KickPlayer` and `BanPlayer`.
+    pub steam_id: String,
+}
+
+/// Parses the response to `ListPlayers`, a line per player such as `0. Alice, 76561198000000001`, or `No Players Connected`.
+pub fn parse_players(response: &str) -> Result<Vec<Player>, Error> {
+    if response.trim() == "No Players Connected" || response.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    response
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_once(". ")
+                .and_then(|(_, rest)| rest.rsplit_once(','))
+                .map(|(name, steam_id)| Player {
+                    name: name.trim().to_string(),
+                    steam_id: steam_id.trim().to_string(),
+                })
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Invalid player line {line:?}"),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Typed wrappers for the commands of an ARK server, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Ark<S> {
+    service: DialectService<S>,
+}
+
+impl<S: CommandService> Ark<S> {
+    /// Creates a new instance of the `Ark` wrapper.
+    pub fn new(service: S) -> Ark<S> {
+        Ark {
+            service: Dialect::ark().layer(service),
+        }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service.into_inner()
+    }
+
+    /// Runs a command that should not have any output, other output is most likely an error.
+    fn call_silent(&mut self, command: String) -> Result<(), Error> {
+        let response = self.service.call(command)?;
+        if !response.trim().is_empty() {
+            return Err(Error::other(response.trim().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Runs `ListPlayers`.
+    pub fn list_players(&mut self) -> Result<Vec<Player>, Error> {
+        parse_players(&self.service.call("ListPlayers".to_string())?)
+    }
+
+    /// Shows a message in the middle of the screen of every player with `Broadcast`.
+    pub fn broadcast(&mut self, message: &str) -> Result<(), Error> {
+        if message.chars().any(|c| c.is_control()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid message {message:?}"),
+            ));
+        }
+        self.call_silent(format!("Broadcast {message}"))
+    }
+
+    /// Kicks the player with the given SteamID64, see [`Player::steam_id`].
+    pub fn kick_player(&mut self, steam_id: &str) -> Result<(), Error> {
+        let steam_id = single_argument(steam_id, "SteamID")?;
+        let response = self.service.call(format!("KickPlayer {steam_id}"))?;
+        // `Kicked 76561198000000001`, older versions have no output.
+        if !(response.trim().is_empty() || response.starts_with("Kicked")) {
+            return Err(Error::new(ErrorKind::NotFound, response.trim().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Bans the player with the given SteamID64, see [`Player::steam_id`].
+    pub fn ban_player(&mut self, steam_id: &str) -> Result<(), Error> {
+        let steam_id = single_argument(steam_id, "SteamID")?;
+        self.service
+            .call(format!("BanPlayer {steam_id}"))
+            .map(|_| ())
+    }
+
+    /// Saves the world with `SaveWorld`.
+    pub fn save_world(&mut self) -> Result<(), Error> {
+        let response = self.service.call("SaveWorld".to_string())?;
+        // `World Saved`, older versions have no output.
+        if !(response.trim().is_empty() || response.trim() == "World Saved") {
+            return Err(Error::other(response.trim().to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn typed_commands() {
+        const NO_RESPONSE: &str = "Server received, But no response!! \n";
+        let mut server = Ark::new(service_fn(|cmd: String| {
+            Ok(match cmd.as_str() {
+                "ListPlayers" => include_str!("samples/ark_listplayers.txt"),
+                "KickPlayer 1" => "Can't find player from the given steam id\n",
+                "SaveWorld" => "World Saved\n",
+                _ => NO_RESPONSE,
+            }
+            .to_string())
+        }));
+        let players = server.list_players().unwrap();
+        assert_eq!(
+            players[1],
+            Player {
+                name: "Bob the builder".to_string(),
+                steam_id: "76561198000000002".to_string()
+            }
+        );
+        server.broadcast("Restart in 5 minutes").unwrap();
+        server.kick_player("76561198000000001").unwrap();
+        assert_eq!(
+            server.kick_player("1").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        server.save_world().unwrap();
+
+        let mut service = server.into_inner();
+        assert_eq!(
+            parse_players(&service.call("ListPlayers".to_string()).unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(parse_players("No Players Connected\n").unwrap(), []);
+    }
+}
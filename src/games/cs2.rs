@@ -0,0 +1,408 @@
+//! Contains helpers to run Counter-Strike 2 and Counter-Strike: Global Offensive match servers, on top of the [`Source`] wrapper.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::cs2::{Cs2, Phase, Team};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Cs2::new(client);
+//! server.changelevel("de_inferno").unwrap();
+//! server.rename_team(Team::CounterTerrorist, "Team Liquid").unwrap();
+//! server.exec("league.cfg").unwrap();
+//! server.mp_set("mp_maxrounds", 24).unwrap();
+//! // The cvars the knife round overrides are set back to the league values when the match goes live.
+//! server.set_phase(Phase::Knife).unwrap();
+//! // Once a team has picked a side.
+//! server.set_phase(Phase::Live).unwrap();
+//! let status = server.status().unwrap();
+//! println!("{} players on {}", status.players.len(), status.map);
+//! ```
+
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use super::{
+    quoted, single_argument,
+    source::{CvarValue, Player, Source, Status},
+};
+use crate::service::CommandService;
+
+/// A side, the names are swapped with the teams at half time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Team {
+    /// The team that starts as counter-terrorists, `mp_teamname_1`.
+    CounterTerrorist,
+    /// The team that starts as terrorists, `mp_teamname_2`.
+    Terrorist,
+}
+
+/// A phase of a match for [`Cs2::set_phase`].
+///
+/// The cvars a phase overrides are read before the phase starts and set back to those values when the next phase starts,
+/// so the values of a league config are kept as long as it is run before the first phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Endless warmup until the match is started with `mp_warmup_start`.
+    /// Overrides `mp_warmup_pausetimer` and `mp_warmuptime`.
+    Warmup,
+    /// A knife round to pick sides: no pistols, no money, no buying, no bomb and free armor, then `mp_warmup_end` and `mp_restartgame 1`.
+    /// Overrides `mp_ct_default_secondary`, `mp_t_default_secondary`, `mp_startmoney`, `mp_buytime`, `mp_give_player_c4` and `mp_free_armor`.
+    Knife,
+    /// The match restarts with `mp_warmup_end` and `mp_restartgame 1`, with the cvars overridden by the phase before it set back.
+    /// Overrides no cvars.
+    Live,
+}
+
+impl Phase {
+    /// The cvars the phase sets and the values it sets them to.
+    fn overrides(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Phase::Warmup => &[("mp_warmup_pausetimer", "1"), ("mp_warmuptime", "9999")],
+            Phase::Knife => &[
+                ("mp_ct_default_secondary", ""),
+                ("mp_t_default_secondary", ""),
+                ("mp_startmoney", "0"),
+                ("mp_buytime", "0"),
+                ("mp_give_player_c4", "0"),
+                ("mp_free_armor", "1"),
+            ],
+            Phase::Live => &[],
+        }
+    }
+
+    /// The commands that start the phase after the cvars are set.
+    fn commands(self) -> &'static [&'static str] {
+        match self {
+            Phase::Warmup => &["mp_warmup_start"],
+            Phase::Knife | Phase::Live => &["mp_warmup_end", "mp_restartgame 1"],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StatusJson {
+    #[serde(default)]
+    server: ServerJson,
+    #[serde(default)]
+    players: Vec<PlayerJson>,
+}
+
+#[derive(Deserialize, Default)]
+struct ServerJson {
+    #[serde(default)]
+    hostname: String,
+    version: Option<String>,
+    #[serde(default)]
+    map: String,
+    #[serde(default)]
+    players: PlayerCountJson,
+}
+
+#[derive(Deserialize, Default)]
+struct PlayerCountJson {
+    max: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct PlayerJson {
+    userid: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    steamid: String,
+    /// Seconds connected.
+    time: Option<u64>,
+    ping: Option<u32>,
+    loss: Option<u32>,
+    #[serde(default)]
+    state: String,
+    #[serde(alias = "adr")]
+    address: Option<String>,
+}
+
+/// Parses the response to `status_json` into the same [`Status`] as the `status` command.
+///
+/// Only tested against a hand-written sample of the output, no CS2 server was available to capture it from, see the [`crate::games`] module.
+pub fn parse_status_json(response: &str) -> Result<Status, Error> {
+    // The server may print lines before the JSON.
+    let json = response
+        .find('{')
+        .zip(response.rfind('}'))
+        .map(|(start, end)| &response[start..=end])
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Unexpected response {response:?}"),
+            )
+        })?;
+    let status: StatusJson = serde_json::from_str(json)?;
+    Ok(Status {
+        hostname: status.server.hostname,
+        version: status.server.version,
+        map: status.server.map,
+        max_players: status.server.players.max,
+        players: status
+            .players
+            .into_iter()
+            .map(|player| Player {
+                userid: player.userid,
+                name: player.name,
+                unique_id: player.steamid,
+                connected: player.time.map(Duration::from_secs),
+                ping: player.ping,
+                loss: player.loss,
+                state: player.state,
+                address: player.address,
+            })
+            .collect(),
+    })
+}
+
+/// Helpers for a Counter-Strike match server, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Cs2<S> {
+    source: Source<S>,
+    /// The values of the cvars overridden by the current phase, from before it started.
+    saved: Vec<(&'static str, String)>,
+}
+
+impl<S: CommandService> Cs2<S> {
+    /// Creates a new instance of the `Cs2` wrapper.
+    pub fn new(service: S) -> Cs2<S> {
+        Cs2 {
+            source: Source::new(service),
+            saved: Vec::new(),
+        }
+    }
+
+    /// Gets the [`Source`] wrapper for the commands shared by every Source engine game, such as `kickid`.
+    pub fn source(&mut self) -> &mut Source<S> {
+        &mut self.source
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.source.into_inner()
+    }
+
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.source.get_mut().call(command)
+    }
+
+    /// Runs `status_json`, fails with [`ErrorKind::Unsupported`] if the server does not have it.
+    pub fn status_json(&mut self) -> Result<Status, Error> {
+        let response = self.call("status_json".to_string())?;
+        if response.trim_start().starts_with("Unknown command") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "The server does not support status_json",
+            ));
+        }
+        parse_status_json(&response)
+    }
+
+    /// Runs `status_json`, or `status` if the server does not have it.
+    pub fn status(&mut self) -> Result<Status, Error> {
+        match self.status_json() {
+            Err(e) if e.kind() == ErrorKind::Unsupported => self.source.status(),
+            result => result,
+        }
+    }
+
+    fn check_mp(name: &str) -> Result<(), Error> {
+        if !name.starts_with("mp_") {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{name:?} is not an mp_ cvar"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads an `mp_` cvar, see [`Source::cvar_get`].
+    pub fn mp_get<T: CvarValue>(&mut self, name: &str) -> Result<T, Error> {
+        Self::check_mp(name)?;
+        self.source.cvar_get(name)
+    }
+
+    /// Sets an `mp_` cvar, see [`Source::cvar_set`].
+    pub fn mp_set<T: CvarValue>(&mut self, name: &str, value: T) -> Result<String, Error> {
+        Self::check_mp(name)?;
+        self.source.cvar_set(name, value)
+    }
+
+    /// Changes to a map that is installed on the server.
+    pub fn changelevel(&mut self, map: &str) -> Result<String, Error> {
+        self.source.changelevel(map)
+    }
+
+    /// Changes to a map from the Steam Workshop by its ID, downloading it if needed.
+    pub fn workshop_map(&mut self, id: u64) -> Result<String, Error> {
+        self.call(format!("host_workshop_map {id}"))
+    }
+
+    /// Hosts a Steam Workshop collection by its ID, the maps of the collection become the map group.
+    pub fn workshop_collection(&mut self, id: u64) -> Result<String, Error> {
+        self.call(format!("host_workshop_collection {id}"))
+    }
+
+    /// Starts a phase of the match, sent as one command separated by `;`.
+    /// The cvars overridden by the previous phase are set back first, and the ones the new phase overrides are read so they can be set back later, see [`Phase`].
+    pub fn set_phase(&mut self, phase: Phase) -> Result<String, Error> {
+        let mut saved = Vec::new();
+        for (name, _) in phase.overrides() {
+            // A cvar overridden by the current phase is saved with the value from before that phase.
+            let value = match self.saved.iter().find(|(saved, _)| saved == name) {
+                Some((_, value)) => value.clone(),
+                None => self.source.cvar_get::<String>(name)?,
+            };
+            saved.push((*name, value));
+        }
+        let mut commands = Vec::new();
+        for (name, value) in &self.saved {
+            commands.push(format!("{name} {}", quoted(value, "cvar value")?));
+        }
+        for (name, value) in phase.overrides() {
+            commands.push(format!("{name} {}", quoted(value, "cvar value")?));
+        }
+        commands.extend(phase.commands().iter().map(|command| command.to_string()));
+        let response = self.call(commands.join("; "))?;
+        self.saved = saved;
+        Ok(response)
+    }
+
+    /// Sets the name shown for a team.
+    pub fn rename_team(&mut self, team: Team, name: &str) -> Result<String, Error> {
+        let cvar = match team {
+            Team::CounterTerrorist => "mp_teamname_1",
+            Team::Terrorist => "mp_teamname_2",
+        };
+        let name = quoted(name, "team name")?;
+        self.call(format!("{cvar} {name}"))
+    }
+
+    /// Pauses the match at the start of the next freeze time with `mp_pause_match`.
+    pub fn pause(&mut self) -> Result<String, Error> {
+        self.call("mp_pause_match".to_string())
+    }
+
+    /// Unpauses the match with `mp_unpause_match`.
+    pub fn unpause(&mut self) -> Result<String, Error> {
+        self.call("mp_unpause_match".to_string())
+    }
+
+    /// Runs a config file from the `cfg` folder of the server, such as a league config.
+    pub fn exec(&mut self, config: &str) -> Result<String, Error> {
+        let config = single_argument(config, "config name")?;
+        self.call(format!("exec {config}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn parse_sample_status_json() {
+        let status = parse_status_json(include_str!("samples/cs2_status_json.txt")).unwrap();
+        assert_eq!(status.hostname, "Match server #1");
+        assert_eq!(status.map, "de_inferno");
+        assert_eq!(status.max_players, Some(12));
+        assert_eq!(status.players.len(), 3);
+        assert_eq!(status.players[1].name, "Bob \"b0b\"");
+        assert_eq!(status.players[1].connected, Some(Duration::from_secs(3753)));
+        assert_eq!(
+            status.players[0].address.as_deref(),
+            Some("198.51.100.2:27005")
+        );
+        assert!(status.players[2].is_bot());
+        assert_eq!(status.players[2].ping, None);
+    }
+
+    #[test]
+    fn phases_set_back_overridden_cvars() {
+        let mut sent = Vec::new();
+        let mut server = Cs2::new(service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            // The values of a league config.
+            Ok(match cmd.as_str() {
+                "mp_ct_default_secondary" => "mp_ct_default_secondary = weapon_usp_silencer",
+                "mp_t_default_secondary" => "mp_t_default_secondary = weapon_glock",
+                "mp_startmoney" => "mp_startmoney = 800",
+                "mp_buytime" => "mp_buytime = 20",
+                "mp_give_player_c4" => "mp_give_player_c4 = 1",
+                "mp_free_armor" => "mp_free_armor = 0",
+                _ => "",
+            }
+            .to_string())
+        }));
+        server.set_phase(Phase::Knife).unwrap();
+        // A second knife round keeps the values from before the first one.
+        server.set_phase(Phase::Knife).unwrap();
+        server.set_phase(Phase::Live).unwrap();
+        server.into_inner();
+        assert_eq!(sent.len(), 9);
+        assert_eq!(
+            sent[6],
+            "mp_ct_default_secondary \"\"; mp_t_default_secondary \"\"; mp_startmoney \"0\"; mp_buytime \"0\"; mp_give_player_c4 \"0\"; mp_free_armor \"1\"; mp_warmup_end; mp_restartgame 1"
+        );
+        assert_eq!(
+            sent[8],
+            "mp_ct_default_secondary \"weapon_usp_silencer\"; mp_t_default_secondary \"weapon_glock\"; mp_startmoney \"800\"; mp_buytime \"20\"; mp_give_player_c4 \"1\"; mp_free_armor \"0\"; mp_warmup_end; mp_restartgame 1"
+        );
+    }
+
+    #[test]
+    fn match_commands() {
+        let mut sent = Vec::new();
+        let mut server = Cs2::new(service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            Ok(match cmd.as_str() {
+                // CS:GO before status_json existed.
+                "status_json" => "Unknown command \"status_json\"".to_string(),
+                "status" => include_str!("samples/csgo_status.txt").to_string(),
+                "mp_maxrounds" => "mp_maxrounds = 24".to_string(),
+                _ => String::new(),
+            })
+        }));
+        assert_eq!(server.status().unwrap().map, "de_dust2");
+        assert_eq!(server.mp_get::<u32>("mp_maxrounds").unwrap(), 24);
+        assert_eq!(
+            server.mp_set("sv_cheats", true).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        server
+            .rename_team(Team::Terrorist, "Natus Vincere")
+            .unwrap();
+        server.set_phase(Phase::Live).unwrap();
+        server.workshop_map(3_070_284_539).unwrap();
+        server.pause().unwrap();
+        server.source().kick(2, None).unwrap();
+        assert_eq!(
+            server
+                .rename_team(Team::CounterTerrorist, "x\"; quit")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        server.into_inner();
+        assert_eq!(
+            sent[3..],
+            [
+                "mp_teamname_2 \"Natus Vincere\"",
+                "mp_warmup_end; mp_restartgame 1",
+                "host_workshop_map 3070284539",
+                "mp_pause_match",
+                "kickid 2",
+            ]
+        );
+    }
+}
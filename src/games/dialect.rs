@@ -0,0 +1,300 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::{
+    chunk::Chunker,
+    command::{Command, Quoting},
+    encoding::TextEncoding,
+    packet::{packet_id::ID, MAX_PAYLOAD_SIZE},
+    service::{AsyncCommandService, CommandService, Layer},
+    RCONClient,
+};
+
+/// The quirks of a game's RCON implementation that can be smoothed over between the client and the caller.
+/// A `Dialect` is a [`Layer`], so it can be used with a [`crate::service::ServiceBuilder`] or wrap a client directly.
+///
+/// # Example
+/// ```
+/// use ya_rcon::games::Dialect;
+/// use ya_rcon::service::{service_fn, CommandService, Layer};
+///
+/// let server = service_fn(|_cmd: String| Ok("Server received, But no response!! \n".to_string()));
+/// let mut service = Dialect::ark().layer(server);
+/// assert_eq!(service.call("SaveWorld".to_string()).unwrap(), "");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dialect {
+    quoting: Quoting,
+    max_command_len: Option<usize>,
+    separator: Option<char>,
+    ascii_only: bool,
+    encoding: TextEncoding,
+    lenient_packets: bool,
+    empty_responses: Vec<String>,
+}
+
+impl Dialect {
+    /// Creates a dialect without any quirks.
+    pub fn new() -> Dialect {
+        Dialect::default()
+    }
+
+    /// Source engine games, which quote arguments with [`Quoting::Source`] and run several commands separated by `;` from one packet.
+    pub fn source() -> Dialect {
+        Dialect::new()
+            .with_quoting(Quoting::Source)
+            .with_separator(Some(';'))
+    }
+
+    /// Minecraft, whose command parser quotes arguments with [`Quoting::Backslash`].
+    /// It drops packets from the client with more than 1446 bytes and has no separator.
+    pub fn minecraft() -> Dialect {
+        Dialect::new()
+            .with_quoting(Quoting::Backslash)
+            .with_max_command_len(1446)
+    }
+
+    /// Factorio, where commands are mostly Lua, strings are quoted with [`Quoting::Backslash`] and statements are separated by `;`.
+    pub fn factorio() -> Dialect {
+        Dialect::new()
+            .with_quoting(Quoting::Backslash)
+            .with_separator(Some(';'))
+    }
+
+    /// Palworld can not handle commands with non-ASCII characters, cuts responses off in the middle of UTF-8 characters
+    /// and leaves out the null bytes that should end a packet. Arguments can not be quoted.
+    pub fn palworld() -> Dialect {
+        Dialect::new()
+            .with_quoting(Quoting::None)
+            .with_ascii_only(true)
+            .with_encoding(TextEncoding::Utf8Lossy)
+            .with_lenient_packets(true)
+    }
+
+    /// ARK: Survival Evolved answers `Server received, But no response!!` instead of an empty response. Arguments can not be quoted.
+    pub fn ark() -> Dialect {
+        Dialect::new()
+            .with_quoting(Quoting::None)
+            .with_empty_response("Server received, But no response!!".to_string())
+    }
+
+    /// Sets how the game splits a command into arguments, [`Quoting::Source`] by default.
+    pub fn with_quoting(mut self, quoting: Quoting) -> Dialect {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Gets how the game splits a command into arguments, see [`Dialect::with_quoting`].
+    pub fn get_quoting(&self) -> Quoting {
+        self.quoting
+    }
+
+    /// Sets the maximum length of a command in bytes, longer commands are rejected with [`ErrorKind::InvalidInput`] instead of being sent.
+    /// [`MAX_PAYLOAD_SIZE`] by default.
+    ///
+    /// # Panics
+    /// If `max_command_len` is larger than [`MAX_PAYLOAD_SIZE`].
+    pub fn with_max_command_len(mut self, max_command_len: usize) -> Dialect {
+        assert!(
+            max_command_len <= MAX_PAYLOAD_SIZE,
+            "max_command_len can not be larger than MAX_PAYLOAD_SIZE"
+        );
+        self.max_command_len = Some(max_command_len);
+        self
+    }
+
+    /// Gets the maximum length of a command in bytes, see [`Dialect::with_max_command_len`].
+    pub fn get_max_command_len(&self) -> usize {
+        self.max_command_len.unwrap_or(MAX_PAYLOAD_SIZE)
+    }
+
+    /// Sets the character that separates several commands or statements sent in one packet, `None` if the game has none.
+    pub fn with_separator(mut self, separator: Option<char>) -> Dialect {
+        self.separator = separator;
+        self
+    }
+
+    /// Gets the character that separates several commands or statements, see [`Dialect::with_separator`].
+    pub fn get_separator(&self) -> Option<char> {
+        self.separator
+    }
+
+    /// Creates a [`Chunker`] with the maximum command length, separator and quoting of the game.
+    pub fn chunker(&self) -> Chunker {
+        Chunker::new()
+            .with_max_len(self.get_max_command_len())
+            .with_separator(self.separator)
+            .with_quoting(self.quoting)
+    }
+
+    /// Starts a [`Command`] that quotes arguments the way the game expects.
+    pub fn build_command(&self, name: &str) -> Command {
+        Command::new(name).with_quoting(self.quoting)
+    }
+
+    /// Sets whether commands with non-ASCII characters are rejected with [`ErrorKind::InvalidInput`] instead of being sent.
+    pub fn with_ascii_only(mut self, ascii_only: bool) -> Dialect {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Sets the encoding the client has to use for the game, [`TextEncoding::Utf8`] by default.
+    /// Strings can not be decoded again once a client turned the bytes into text, so the dialect does not touch the text itself.
+    pub fn with_encoding(mut self, encoding: TextEncoding) -> Dialect {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Gets the encoding the client has to use for the game, see [`Dialect::with_encoding`].
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Sets whether the client has to accept packets without the null bytes that end the body, see [`crate::Packet::try_from_lenient`].
+    pub fn with_lenient_packets(mut self, lenient_packets: bool) -> Dialect {
+        self.lenient_packets = lenient_packets;
+        self
+    }
+
+    /// Sets up a client for the game, the encoding and how packets are parsed can only be handled by the client.
+    pub fn apply_to<T: Read + Write, I: Iterator<Item = ID>>(&self, client: &mut RCONClient<T, I>) {
+        client.set_encoding(self.encoding);
+        client.set_lenient_parsing(self.lenient_packets);
+    }
+
+    /// Adds a response the server sends instead of an empty response, it is compared without surrounding whitespace.
+    pub fn with_empty_response(mut self, response: String) -> Dialect {
+        self.empty_responses.push(response);
+        self
+    }
+
+    /// Checks a command before it is sent.
+    pub fn command(&self, command: String) -> Result<String, Error> {
+        if command.len() > self.get_max_command_len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The command is {} bytes, the server accepts at most {}",
+                    command.len(),
+                    self.get_max_command_len()
+                ),
+            ));
+        }
+        if self.ascii_only && !command.is_ascii() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("The server only supports ASCII commands, got {command:?}"),
+            ));
+        }
+        Ok(command)
+    }
+
+    /// Normalizes a response from the server.
+    pub fn response(&self, response: String) -> String {
+        // Left over terminators when the size field of the packet is too large.
+        let response = response.trim_end_matches('\0');
+        if self
+            .empty_responses
+            .iter()
+            .any(|empty| response.trim() == empty)
+        {
+            return String::new();
+        }
+        response.to_string()
+    }
+}
+
+impl<S> Layer<S> for Dialect {
+    type Service = DialectService<S>;
+    fn layer(&self, inner: S) -> DialectService<S> {
+        DialectService {
+            inner,
+            dialect: self.clone(),
+        }
+    }
+}
+
+/// Applies a [`Dialect`] to the commands and responses of a service.
+#[derive(Debug, Clone)]
+pub struct DialectService<S> {
+    inner: S,
+    dialect: Dialect,
+}
+
+impl<S> DialectService<S> {
+    /// Gets the wrapped service back.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: CommandService> CommandService for DialectService<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        let command = self.dialect.command(command)?;
+        let response = self.inner.call(command)?;
+        Ok(self.dialect.response(response))
+    }
+}
+
+impl<S: AsyncCommandService> AsyncCommandService for DialectService<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        let command = self.dialect.command(command)?;
+        let response = self.inner.call(command).await?;
+        Ok(self.dialect.response(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palworld_responses() {
+        let dialect = Dialect::palworld();
+        assert_eq!(
+            dialect
+                .command("Broadcast Grüße".to_string())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(dialect.get_encoding(), TextEncoding::Utf8Lossy);
+        // Text that was decoded correctly is left alone.
+        assert_eq!(dialect.response("Zoë\0".to_string()), "Zoë");
+        assert_eq!(dialect.response("名前".to_string()), "名前");
+    }
+
+    #[test]
+    fn long_commands_are_rejected() {
+        let dialect = Dialect::minecraft();
+        let command = format!("say {}", "a".repeat(1442));
+        assert_eq!(dialect.command(command.clone()).unwrap(), command);
+        assert_eq!(
+            dialect.command(command + "a").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(dialect.chunker(), crate::chunk::Chunker::minecraft());
+    }
+
+    #[test]
+    fn commands_are_quoted_for_the_game() {
+        let name = r#"Bob "the builder""#;
+        assert_eq!(
+            Dialect::minecraft()
+                .build_command("kick")
+                .with_quoted(name)
+                .build()
+                .unwrap(),
+            r#"kick "Bob \"the builder\"""#
+        );
+        assert!(Dialect::source()
+            .build_command("kickid")
+            .with_quoted(name)
+            .build()
+            .is_err());
+        assert!(Dialect::palworld()
+            .build_command("KickPlayer")
+            .with_quoted("two words")
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//! Contains typed wrappers for the commands of Factorio servers, mostly Lua run with `/silent-command`.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::factorio::{lua_string, Factorio};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Factorio::new(client);
+//! let tick: u64 = server.eval("game.tick").unwrap();
+//! let evolution: f64 = server.eval("game.forces.enemy.evolution_factor").unwrap();
+//! server
+//!     .silent_command(&format!("game.print({})", lua_string("Evolution is \"high\"")))
+//!     .unwrap();
+//! for player in server.players_online().unwrap() {
+//!     println!("{}", player.name);
+//! }
+//! ```
+
+use std::{
+    fmt::{self, Display, Write as _},
+    io::{Error, ErrorKind},
+    str::FromStr,
+    time::Duration,
+};
+
+use super::lua::single_line;
+use crate::{packet::MAX_PAYLOAD_SIZE, service::CommandService};
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn unexpected(response: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Unexpected response {response:?}"),
+    )
+}
+
+/// Quotes a value as a Lua string literal, to put values into Lua code.
+///
+/// # Example
+/// ```
+/// use ya_rcon::games::factorio::lua_string;
+/// assert_eq!(lua_string("say \"hi\"\n"), r#""say \"hi\"\n""#);
+/// ```
+pub fn lua_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // Decimal escapes are three digits at most, so the next character can not be taken as part of it.
+            c if c.is_ascii_control() => {
+                let _ = write!(quoted, "\\{:03}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A value that can be read back from the output of `rcon.print(tostring(...))`.
+pub trait LuaValue: Sized {
+    /// Parses the output, returns `None` if it is not a `Self`.
+    fn from_lua(output: &str) -> Option<Self>;
+}
+
+impl LuaValue for bool {
+    fn from_lua(output: &str) -> Option<Self> {
+        output.parse().ok()
+    }
+}
+
+impl LuaValue for String {
+    fn from_lua(output: &str) -> Option<Self> {
+        Some(output.to_string())
+    }
+}
+
+impl<T: LuaValue> LuaValue for Option<T> {
+    fn from_lua(output: &str) -> Option<Self> {
+        match output {
+            "nil" => Some(None),
+            _ => T::from_lua(output).map(Some),
+        }
+    }
+}
+
+macro_rules! lua_value_from_str {
+    ($($t:ty),*) => {
+        $(impl LuaValue for $t {
+            fn from_lua(output: &str) -> Option<Self> {
+                output.parse().ok()
+            }
+        })*
+    };
+}
+
+lua_value_from_str!(i32, i64, u32, u64, f32, f64);
+
+/// A player in the response to `/players`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    /// The name of the player.
+    pub name: String,
+    /// Whether the player is online.
+    pub online: bool,
+}
+
+/// Parses the response to `/players` or `/players online`, `Online players (2):` followed by one player per line such as `  Alice (online)`.
+pub fn parse_players(response: &str) -> Result<Vec<Player>, Error> {
+    let mut lines = response.lines();
+    let header = lines.next().unwrap_or_default();
+    if !(header.starts_with("Players") || header.starts_with("Online players")) {
+        return Err(unexpected(response));
+    }
+    Ok(lines
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.strip_suffix(" (online)") {
+            Some(name) => Player {
+                name: name.to_string(),
+                online: true,
+            },
+            None => Player {
+                name: line.to_string(),
+                online: false,
+            },
+        })
+        .collect())
+}
+
+/// The response to `/version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    /// The major version.
+    pub major: u32,
+    /// The minor version.
+    pub minor: u32,
+    /// The patch version.
+    pub patch: u32,
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('.').map(|part| part.parse().ok());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => Ok(Version {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(unexpected(s)),
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Parses the response to `/time`, such as `1 day, 4 hours, 12 minutes and 5 seconds`.
+pub fn parse_time(response: &str) -> Result<Duration, Error> {
+    let words: Vec<&str> = response
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty() && *word != "and")
+        .collect();
+    if words.is_empty() || !words.len().is_multiple_of(2) {
+        return Err(unexpected(response));
+    }
+    let mut seconds = 0u64;
+    for pair in words.chunks(2) {
+        let count: u64 = pair[0].parse().map_err(|_| unexpected(response))?;
+        let unit = match pair[1].trim_end_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 86_400,
+            _ => return Err(unexpected(response)),
+        };
+        seconds += count * unit;
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Typed wrappers for the commands of a Factorio server, see the [module documentation](self).
+///
+/// Lua errors, reported as `Cannot execute command. Error: ...`, become [`ErrorKind::InvalidInput`] errors.
+#[derive(Debug)]
+pub struct Factorio<S> {
+    service: S,
+}
+
+impl<S: CommandService> Factorio<S> {
+    /// Creates a new instance of the `Factorio` wrapper.
+    pub fn new(service: S) -> Factorio<S> {
+        Factorio { service }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        if command.len() > MAX_PAYLOAD_SIZE {
+            return Err(invalid_input(format!(
+                "The command is {} bytes long, at most {MAX_PAYLOAD_SIZE} bytes fit in a packet",
+                command.len()
+            )));
+        }
+        let response = self.service.call(command)?;
+        if let Some(error) = response.trim().strip_prefix("Cannot execute command. ") {
+            return Err(invalid_input(error.to_string()));
+        }
+        Ok(response)
+    }
+
+    /// Runs Lua code with `/silent-command`, which does not announce the command in the chat or disable achievements.
+    /// The code is put on a single line, see [`lua_string`] to put values into it. Returns what the code printed with `rcon.print`.
+    pub fn silent_command(&mut self, code: &str) -> Result<String, Error> {
+        let code = single_line(code)?;
+        self.call(format!("/silent-command {code}"))
+    }
+
+    /// Evaluates a Lua expression and parses its value. Fails with [`ErrorKind::InvalidData`] if the value is not a `T`, use `Option<T>` for values that can be `nil`.
+    pub fn eval<T: LuaValue>(&mut self, expression: &str) -> Result<T, Error> {
+        let expression = single_line(expression)?;
+        let output = self.silent_command(&format!("rcon.print(tostring({expression}))"))?;
+        // Factorio ends every printed line with a newline.
+        let output = output.strip_suffix('\n').unwrap_or(&output);
+        T::from_lua(output).ok_or_else(|| unexpected(output))
+    }
+
+    /// Runs `/players online`.
+    pub fn players_online(&mut self) -> Result<Vec<Player>, Error> {
+        parse_players(&self.call("/players online".to_string())?)
+    }
+
+    /// Runs `/players`, which includes players that are offline.
+    pub fn players(&mut self) -> Result<Vec<Player>, Error> {
+        parse_players(&self.call("/players".to_string())?)
+    }
+
+    /// Runs `/version`.
+    pub fn version(&mut self) -> Result<Version, Error> {
+        self.call("/version".to_string())?.parse()
+    }
+
+    /// Runs `/time`, how long the map has been running.
+    pub fn time(&mut self) -> Result<Duration, Error> {
+        parse_time(&self.call("/time".to_string())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn parse_responses() {
+        let players = parse_players(include_str!("samples/factorio_players.txt")).unwrap();
+        assert_eq!(
+            players,
+            [
+                Player {
+                    name: "Alice".to_string(),
+                    online: true
+                },
+                Player {
+                    name: "bob_the_builder".to_string(),
+                    online: false
+                },
+                Player {
+                    name: "Carol".to_string(),
+                    online: true
+                },
+            ]
+        );
+        assert_eq!(
+            parse_players("Online players (0):\n").unwrap(),
+            Vec::<Player>::new()
+        );
+
+        let version: Version = "1.1.110\n".parse().unwrap();
+        assert_eq!(version.to_string(), "1.1.110");
+        assert!(version < "2.0.28".parse().unwrap());
+
+        assert_eq!(
+            parse_time("1 day, 4 hours, 1 minute and 5 seconds\n").unwrap(),
+            Duration::from_secs(86_400 + 4 * 3600 + 60 + 5)
+        );
+        assert_eq!(parse_time("12 seconds").unwrap(), Duration::from_secs(12));
+        assert!(parse_time("a while").is_err());
+    }
+
+    #[test]
+    fn lua_strings() {
+        assert_eq!(lua_string("a\u{7}1"), "\"a\\0071\"");
+    }
+
+    #[test]
+    fn eval_round_trip() {
+        let mut sent = Vec::new();
+        let mut server = Factorio::new(service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            let expression = cmd
+                .strip_prefix("/silent-command rcon.print(tostring(")
+                .and_then(|rest| rest.strip_suffix("))"));
+            Ok(match expression {
+                Some("game.tick") => "123456\n",
+                Some("game.forces.enemy.evolution_factor") => "0.25\n",
+                Some("game.players[\"nobody\"]") => "nil\n",
+                Some("game.surfaces[1].name") => "nauvis\n",
+                _ => "Cannot execute command. Error: [string \"...\"]:1: unexpected symbol\n",
+            }
+            .to_string())
+        }));
+        assert_eq!(server.eval::<u64>("game.tick").unwrap(), 123_456);
+        assert_eq!(
+            server
+                .eval::<f64>("game.forces.enemy.evolution_factor")
+                .unwrap(),
+            0.25
+        );
+        assert_eq!(
+            server
+                .eval::<Option<String>>("game.players[\"nobody\"]")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            server
+                .eval::<bool>("game.surfaces[1].name")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            server.eval::<u32>("1 +").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let long = format!("game.print({})", lua_string(&"x".repeat(MAX_PAYLOAD_SIZE)));
+        assert_eq!(
+            server.silent_command(&long).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        server.into_inner();
+        assert_eq!(sent.len(), 5);
+    }
+}
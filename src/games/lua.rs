@@ -0,0 +1,239 @@
+//! Contains the handling of Lua code shared by [`crate::chunk::Chunker`] and the Factorio wrapper, which both have to put Lua on single lines.
+
+use std::io::{Error, ErrorKind};
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Removes the line comment from a line of Lua code, strings are kept as they are.
+/// Long strings and block comments (`[[ ]]`, `--[[ ]]`) can not be put on one line safely and are rejected.
+fn strip_comment(source_line: &str) -> Result<String, Error> {
+    let mut line = String::with_capacity(source_line.len());
+    let mut quote = None;
+    let mut chars = source_line.trim_start().chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                line.push(c);
+                if let Some(escaped) = chars.next() {
+                    line.push(escaped);
+                }
+                continue;
+            }
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '-') if chars.peek() == Some(&'-') => {
+                chars.next();
+                if chars.peek() == Some(&'[') {
+                    return Err(invalid_input(
+                        "Block comments are not supported in Lua commands".to_string(),
+                    ));
+                }
+                break;
+            }
+            (None, '[') if matches!(chars.peek(), Some('[' | '=')) => {
+                return Err(invalid_input(
+                    "Long strings are not supported in Lua commands".to_string(),
+                ));
+            }
+            (None, _) => {}
+        }
+        line.push(c);
+    }
+    if quote.is_some() {
+        return Err(invalid_input(format!(
+            "Unfinished string in Lua line {source_line:?}"
+        )));
+    }
+    let trimmed_end = line.trim_end().len();
+    line.truncate(trimmed_end);
+    Ok(line)
+}
+
+/// Puts Lua code on a single line, since a console command ends at the first newline.
+/// Line comments are removed because they would comment out the rest of the code, see [`strip_comment`].
+#[cfg_attr(not(feature = "factorio"), allow(dead_code))]
+pub(crate) fn single_line(code: &str) -> Result<String, Error> {
+    let mut lines = Vec::new();
+    for source_line in code.lines() {
+        let line = strip_comment(source_line)?;
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    // Keep the code on separate lines apart, `a = 1\nb = 2` becomes `a = 1 b = 2`.
+    Ok(lines.join(" "))
+}
+
+/// How a line of Lua code changes the nesting of blocks and brackets.
+#[derive(Debug, Default)]
+struct Nesting {
+    depth: i32,
+    /// Byte offsets in the line of the `;` outside of blocks and brackets, where a statement ends.
+    separators: Vec<usize>,
+}
+
+/// Follows the nesting through a line without comments, starting at `depth`.
+/// `function`, `do`, `if` and `repeat` open a block that `end` or `until` close, `while` and `for` are closed through their `do`.
+fn nesting(line: &str, mut depth: i32) -> Result<Nesting, Error> {
+    let mut separators = Vec::new();
+    let mut quote = None;
+    let mut word = String::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some(open) = quote {
+            if c == '\\' {
+                chars.next();
+            } else if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            if chars
+                .peek()
+                .is_some_and(|(_, next)| next.is_alphanumeric() || *next == '_')
+            {
+                continue;
+            }
+            match word.as_str() {
+                "function" | "do" | "if" | "repeat" => depth += 1,
+                "end" | "until" => depth -= 1,
+                _ => {}
+            }
+            word.clear();
+        } else {
+            match c {
+                '"' | '\'' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ';' if depth == 0 => separators.push(i),
+                _ => {}
+            }
+        }
+        if depth < 0 {
+            return Err(invalid_input(format!(
+                "Unbalanced brackets or blocks in Lua line {line:?}"
+            )));
+        }
+    }
+    Ok(Nesting { depth, separators })
+}
+
+/// Whether the statement continues on the next line, because the line ends with an operator.
+fn ends_in_operator(line: &str) -> bool {
+    let last_word = line
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default();
+    line.ends_with(|c: char| "+-*/%^#<>=~,.".contains(c))
+        || matches!(last_word, "and" | "or" | "not" | "local" | "return")
+}
+
+/// Whether a line continues the statement before it. A statement starts with a name or a keyword,
+/// anything else such as an operator, `.`, `:` or `(` is taken as part of the statement before, like Lua does.
+fn continues_statement(line: &str) -> bool {
+    let first_word = line
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default();
+    matches!(first_word, "and" | "or")
+        || (!line.starts_with("::") && !line.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+}
+
+/// Splits Lua code into its top-level statements, each on a single line without comments, see [`single_line`].
+/// Statements end at a `;` or a line break outside of blocks and brackets, unless the line ends in an operator or the next line starts with one.
+pub(crate) fn statements(code: &str) -> Result<Vec<String>, Error> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut depth = 0;
+    for source_line in code.lines() {
+        let line = strip_comment(source_line)?;
+        if line.is_empty() {
+            continue;
+        }
+        if depth == 0
+            && !statement.is_empty()
+            && !ends_in_operator(&statement)
+            && !continues_statement(&line)
+        {
+            statements.push(std::mem::take(&mut statement));
+        }
+        let nesting = nesting(&line, depth)?;
+        depth = nesting.depth;
+        let mut start = 0;
+        for separator in nesting.separators {
+            push_part(&mut statement, &line[start..separator]);
+            statements.push(std::mem::take(&mut statement));
+            start = separator + 1;
+        }
+        push_part(&mut statement, &line[start..]);
+    }
+    if depth != 0 {
+        return Err(invalid_input(
+            "Unfinished block or bracket in the Lua code".to_string(),
+        ));
+    }
+    statements.push(statement);
+    statements.retain(|statement| !statement.is_empty());
+    Ok(statements)
+}
+
+/// Adds a part of a line to a statement, separated by a space from the part before it.
+fn push_part(statement: &mut String, part: &str) {
+    let part = part.trim();
+    if part.is_empty() {
+        return;
+    }
+    if !statement.is_empty() {
+        statement.push(' ');
+    }
+    statement.push_str(part);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lua_on_one_line() {
+        assert_eq!(
+            single_line("local a = \"--not a comment\" -- a comment\n\n  game.print(a)\n").unwrap(),
+            "local a = \"--not a comment\" game.print(a)"
+        );
+        assert_eq!(
+            single_line("game.print('it\\'s')").unwrap(),
+            "game.print('it\\'s')"
+        );
+        assert!(single_line("local s = [[\nlong]]").is_err());
+        assert!(single_line("--[[ block ]] x()").is_err());
+        assert!(single_line("game.print(\"unfinished)").is_err());
+    }
+
+    #[test]
+    fn top_level_statements() {
+        let script = "-- don't split here; or here\n\
+            storage.items = {\n  'a;b', -- it's a table\n  \"c\",\n}\n\
+            for _, p in pairs(game.players) do\n  if p.valid then p.print('hi') end\nend\n\
+            local total = 1 +\n  2\n\
+            game.print(total)\n  :gsub('x', 'y'); storage.done = true\n\
+            function storage.f(x)\n  return x ~= 'end'\nend";
+        assert_eq!(
+            statements(script).unwrap(),
+            [
+                "storage.items = { 'a;b', \"c\", }",
+                "for _, p in pairs(game.players) do if p.valid then p.print('hi') end end",
+                "local total = 1 + 2",
+                "game.print(total) :gsub('x', 'y')",
+                "storage.done = true",
+                "function storage.f(x) return x ~= 'end' end",
+            ]
+        );
+        assert!(statements("if x then\n  y()\n").is_err());
+        assert!(statements("x())").is_err());
+        assert!(statements("game.print('a)").is_err());
+    }
+}
@@ -0,0 +1,619 @@
+//! Contains typed wrappers for the commands of Minecraft: Java Edition servers, including Spigot and Paper.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::minecraft::{ClickEvent, Minecraft, Text, Weather};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:25575").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Minecraft::new(client);
+//! let list = server.list().unwrap();
+//! println!("{}/{} online: {:?}", list.online, list.max, list.players);
+//! if !server.whitelist_add("Alice").unwrap() {
+//!     println!("Alice was already whitelisted");
+//! }
+//! server.weather(Weather::Clear).unwrap();
+//! let message = Text::new("Restarting in 5 minutes, ")
+//!     .with_color("gold")
+//!     .with_extra(
+//!         Text::new("click to vote")
+//!             .with_underlined(true)
+//!             .with_click(ClickEvent::RunCommand("/vote restart".to_string())),
+//!     );
+//! server.tellraw("@a", &message).unwrap();
+//! ```
+
+use std::{
+    fmt::{self, Display, Write as _},
+    io::{Error, ErrorKind},
+};
+
+use super::{single_argument, Dialect, DialectService};
+use crate::service::{CommandService, Layer};
+
+/// The response to `list`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlayerList {
+    /// How many players are online.
+    pub online: u32,
+    /// The maximum number of players.
+    pub max: u32,
+    /// The names of the online players.
+    pub players: Vec<String>,
+}
+
+/// Removes the `§` formatting codes Spigot and Paper add to responses.
+fn strip_formatting(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+fn unexpected(response: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Unexpected response {response:?}"),
+    )
+}
+
+/// Turns the error responses shared by every command into errors, returns the response without formatting codes otherwise.
+fn check(response: &str) -> Result<String, Error> {
+    let plain = strip_formatting(response);
+    let trimmed = plain.trim();
+    let kind = if trimmed.starts_with("Unknown or incomplete command")
+        || trimmed.starts_with("Unknown command")
+        || trimmed.starts_with("Incorrect argument")
+        || trimmed.contains("<--[HERE]")
+    {
+        ErrorKind::InvalidInput
+    } else if trimmed.starts_with("That player does not exist")
+        || trimmed.starts_with("No player was found")
+        || trimmed.starts_with("No entity was found")
+        || trimmed.starts_with("Player not found")
+        || trimmed.starts_with("Can't find player")
+    {
+        ErrorKind::NotFound
+    } else if trimmed.starts_with("I'm sorry, but you do not have permission") {
+        ErrorKind::PermissionDenied
+    } else {
+        return Ok(plain);
+    };
+    Err(Error::new(kind, trimmed.to_string()))
+}
+
+/// Whether a command that may have nothing to do changed anything, by the start of the response.
+fn changed(response: &str, changed: &[&str], unchanged: &[&str]) -> Result<bool, Error> {
+    let plain = check(response)?;
+    let trimmed = plain.trim();
+    if changed.iter().any(|prefix| trimmed.starts_with(prefix)) {
+        Ok(true)
+    } else if unchanged.iter().any(|prefix| trimmed.starts_with(prefix)) {
+        Ok(false)
+    } else {
+        Err(unexpected(response))
+    }
+}
+
+/// Splits `Alice, Bob and Carol` into names.
+fn names(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(',')
+        .flat_map(|part| part.split(" and "))
+        .map(|name| {
+            let name = name.trim();
+            // Essentials marks players that are away with `[AFK]`.
+            match name.strip_prefix('[').and_then(|tag| tag.split_once(']')) {
+                Some((_, name)) => name.trim(),
+                None => name,
+            }
+        })
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+impl std::str::FromStr for PlayerList {
+    type Err = Error;
+
+    /// Parses the response to `list`, which is one of
+    /// - `There are 3 of a max of 20 players online: Alice, Bob, Carol` on vanilla 1.13 and later,
+    /// - `There are 3/20 players online:` followed by the names on a new line on older versions and Spigot,
+    /// - `There are 3 out of maximum 20 players online.` followed by a line per group such as `default: Alice, Bob` with Essentials on Spigot and Paper.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let plain = check(s)?;
+        let (_, rest) = plain
+            .split_once("There are ")
+            .ok_or_else(|| unexpected(s))?;
+        let (header, others) = rest.split_once('\n').unwrap_or((rest, ""));
+        let mut counts = header
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|number| number.parse().ok());
+        let (Some(online), Some(max)) = (counts.next(), counts.next()) else {
+            return Err(unexpected(s));
+        };
+        let mut players: Vec<String> = match header.split_once(':') {
+            Some((_, names_on_header)) => names(names_on_header).collect(),
+            None => Vec::new(),
+        };
+        for line in others.lines() {
+            let line = line.split_once(':').map_or(line, |(_, names)| names);
+            players.extend(names(line));
+        }
+        Ok(PlayerList {
+            online,
+            max,
+            players,
+        })
+    }
+}
+
+/// Where to teleport to with [`Minecraft::tp`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// A player name or a selector that matches a single entity.
+    Entity(String),
+    /// Absolute coordinates.
+    Position(f64, f64, f64),
+}
+
+/// A time of day for [`Minecraft::time_set`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Time {
+    /// 1000 ticks.
+    Day,
+    /// 6000 ticks.
+    Noon,
+    /// 13000 ticks.
+    Night,
+    /// 18000 ticks.
+    Midnight,
+    /// A number of ticks.
+    Ticks(u32),
+}
+
+/// The weather for [`Minecraft::weather`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weather {
+    /// Clear skies.
+    Clear,
+    /// Rain or snow.
+    Rain,
+    /// A thunderstorm.
+    Thunder,
+}
+
+/// What happens when a player clicks on a [`Text`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClickEvent {
+    /// Runs a command as the player, including the `/`.
+    RunCommand(String),
+    /// Puts a command in the chat box of the player.
+    SuggestCommand(String),
+    /// Opens a URL.
+    OpenUrl(String),
+    /// Copies the text to the clipboard.
+    CopyToClipboard(String),
+}
+
+/// A JSON text component for `tellraw`, in the format used up to Minecraft 1.21.4.
+/// Formats as JSON with [`Display`].
+///
+/// # Example
+/// ```
+/// use ya_rcon::games::minecraft::Text;
+/// let text = Text::new("Hello \"world\"").with_color("red").with_bold(true);
+/// assert_eq!(text.to_string(), r#"{"text":"Hello \"world\"","color":"red","bold":true}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Text {
+    text: String,
+    color: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underlined: Option<bool>,
+    strikethrough: Option<bool>,
+    obfuscated: Option<bool>,
+    click: Option<ClickEvent>,
+    hover: Option<Box<Text>>,
+    extra: Vec<Text>,
+}
+
+impl Text {
+    /// Creates a new instance of the `Text` with the given plain text.
+    pub fn new(text: impl Into<String>) -> Text {
+        Text {
+            text: text.into(),
+            ..Text::default()
+        }
+    }
+
+    /// Sets the color, a name such as `gold` or a hex color such as `#ff8800`.
+    pub fn with_color(mut self, color: impl Into<String>) -> Text {
+        self.color = Some(color.into());
+        self
+    }
+
+    /// Sets whether the text is bold.
+    pub fn with_bold(mut self, bold: bool) -> Text {
+        self.bold = Some(bold);
+        self
+    }
+
+    /// Sets whether the text is italic.
+    pub fn with_italic(mut self, italic: bool) -> Text {
+        self.italic = Some(italic);
+        self
+    }
+
+    /// Sets whether the text is underlined.
+    pub fn with_underlined(mut self, underlined: bool) -> Text {
+        self.underlined = Some(underlined);
+        self
+    }
+
+    /// Sets whether the text is struck through.
+    pub fn with_strikethrough(mut self, strikethrough: bool) -> Text {
+        self.strikethrough = Some(strikethrough);
+        self
+    }
+
+    /// Sets whether the text is obfuscated.
+    pub fn with_obfuscated(mut self, obfuscated: bool) -> Text {
+        self.obfuscated = Some(obfuscated);
+        self
+    }
+
+    /// Sets what happens when the text is clicked.
+    pub fn with_click(mut self, click: ClickEvent) -> Text {
+        self.click = Some(click);
+        self
+    }
+
+    /// Sets the text shown when hovering over the text.
+    pub fn with_hover_text(mut self, hover: Text) -> Text {
+        self.hover = Some(Box::new(hover));
+        self
+    }
+
+    /// Adds a component after this one, which inherits the formatting of this one.
+    pub fn with_extra(mut self, extra: Text) -> Text {
+        self.extra.push(extra);
+        self
+    }
+}
+
+/// Writes a JSON string.
+fn write_json_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{\"text\":")?;
+        write_json_string(f, &self.text)?;
+        if let Some(color) = &self.color {
+            f.write_str(",\"color\":")?;
+            write_json_string(f, color)?;
+        }
+        for (name, value) in [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough),
+            ("obfuscated", self.obfuscated),
+        ] {
+            if let Some(value) = value {
+                write!(f, ",\"{name}\":{value}")?;
+            }
+        }
+        if let Some(click) = &self.click {
+            let (action, value) = match click {
+                ClickEvent::RunCommand(value) => ("run_command", value),
+                ClickEvent::SuggestCommand(value) => ("suggest_command", value),
+                ClickEvent::OpenUrl(value) => ("open_url", value),
+                ClickEvent::CopyToClipboard(value) => ("copy_to_clipboard", value),
+            };
+            write!(f, ",\"clickEvent\":{{\"action\":\"{action}\",\"value\":")?;
+            write_json_string(f, value)?;
+            f.write_char('}')?;
+        }
+        if let Some(hover) = &self.hover {
+            write!(
+                f,
+                ",\"hoverEvent\":{{\"action\":\"show_text\",\"contents\":{hover}}}"
+            )?;
+        }
+        if !self.extra.is_empty() {
+            f.write_str(",\"extra\":[")?;
+            for (i, extra) in self.extra.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                write!(f, "{extra}")?;
+            }
+            f.write_char(']')?;
+        }
+        f.write_char('}')
+    }
+}
+
+/// Typed wrappers for the commands of a Minecraft server, see the [module documentation](self).
+///
+/// Responses that report a failure become errors: [`ErrorKind::NotFound`] for unknown players or entities, [`ErrorKind::InvalidInput`] for unknown commands and invalid arguments, and [`ErrorKind::InvalidData`] for responses that are not understood.
+#[derive(Debug)]
+pub struct Minecraft<S> {
+    service: DialectService<S>,
+}
+
+impl<S: CommandService> Minecraft<S> {
+    /// Creates a new instance of the `Minecraft` wrapper, every command goes through [`Dialect::minecraft()`].
+    pub fn new(service: S) -> Minecraft<S> {
+        Minecraft {
+            service: Dialect::minecraft().layer(service),
+        }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service.into_inner()
+    }
+
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.service.call(command)
+    }
+
+    /// Runs `list`.
+    pub fn list(&mut self) -> Result<PlayerList, Error> {
+        self.call("list".to_string())?.parse()
+    }
+
+    /// Adds a player to the whitelist, returns `false` if they already were on it.
+    pub fn whitelist_add(&mut self, player: &str) -> Result<bool, Error> {
+        let player = single_argument(player, "player name")?;
+        let response = self.call(format!("whitelist add {player}"))?;
+        changed(
+            &response,
+            &["Added "],
+            &["Player is already whitelisted", "Nothing changed"],
+        )
+    }
+
+    /// Removes a player from the whitelist, returns `false` if they were not on it.
+    pub fn whitelist_remove(&mut self, player: &str) -> Result<bool, Error> {
+        let player = single_argument(player, "player name")?;
+        let response = self.call(format!("whitelist remove {player}"))?;
+        changed(
+            &response,
+            &["Removed "],
+            &["Player is not whitelisted", "Nothing changed"],
+        )
+    }
+
+    /// Gets the names of the players on the whitelist.
+    pub fn whitelist_list(&mut self) -> Result<Vec<String>, Error> {
+        let response = self.call("whitelist list".to_string())?;
+        let plain = check(&response)?;
+        if plain.contains("no whitelisted players") {
+            return Ok(Vec::new());
+        }
+        // `There are 2 whitelisted players: Alice, Bob` or `There are 2 (out of 3 seen) whitelisted players:` followed by the names on a new line.
+        let (_, players) = plain.split_once(':').ok_or_else(|| unexpected(&response))?;
+        Ok(names(players).collect())
+    }
+
+    /// Turns the whitelist on or off, returns `false` if it already was.
+    pub fn whitelist_set_enabled(&mut self, enabled: bool) -> Result<bool, Error> {
+        let state = if enabled { "on" } else { "off" };
+        let response = self.call(format!("whitelist {state}"))?;
+        changed(
+            &response,
+            &[
+                &format!("Whitelist is now turned {state}"),
+                &format!("Turned {state} the whitelist"),
+            ],
+            &[&format!("Whitelist is already turned {state}")],
+        )
+    }
+
+    /// Makes a player an operator, returns `false` if they already were.
+    pub fn op(&mut self, player: &str) -> Result<bool, Error> {
+        let player = single_argument(player, "player name")?;
+        let response = self.call(format!("op {player}"))?;
+        changed(&response, &["Made ", "Opped "], &["Nothing changed"])
+    }
+
+    /// Makes a player no longer an operator, returns `false` if they were not one.
+    pub fn deop(&mut self, player: &str) -> Result<bool, Error> {
+        let player = single_argument(player, "player name")?;
+        let response = self.call(format!("deop {player}"))?;
+        changed(&response, &["Made ", "De-opped "], &["Nothing changed"])
+    }
+
+    /// Teleports the players or entities matched by `target` to the destination.
+    pub fn tp(&mut self, target: &str, destination: &Destination) -> Result<(), Error> {
+        let target = single_argument(target, "target")?;
+        let command = match destination {
+            Destination::Entity(entity) => {
+                format!("tp {target} {}", single_argument(entity, "destination")?)
+            }
+            Destination::Position(x, y, z) => format!("tp {target} {x} {y} {z}"),
+        };
+        let response = self.call(command)?;
+        changed(&response, &["Teleported "], &[]).map(|_| ())
+    }
+
+    /// Sets the time of day, returns the time in ticks reported by the server.
+    pub fn time_set(&mut self, time: Time) -> Result<u32, Error> {
+        let command = match time {
+            Time::Day => "time set day".to_string(),
+            Time::Noon => "time set noon".to_string(),
+            Time::Night => "time set night".to_string(),
+            Time::Midnight => "time set midnight".to_string(),
+            Time::Ticks(ticks) => format!("time set {ticks}"),
+        };
+        let response = self.call(command)?;
+        check(&response)?
+            .trim()
+            .strip_prefix("Set the time to ")
+            .and_then(|ticks| ticks.parse().ok())
+            .ok_or_else(|| unexpected(&response))
+    }
+
+    /// Sets the weather.
+    pub fn weather(&mut self, weather: Weather) -> Result<(), Error> {
+        let weather = match weather {
+            Weather::Clear => "clear",
+            Weather::Rain => "rain",
+            Weather::Thunder => "thunder",
+        };
+        let response = self.call(format!("weather {weather}"))?;
+        // `Changing to ...` before 1.13.
+        changed(&response, &["Set the weather to ", "Changing to "], &[]).map(|_| ())
+    }
+
+    /// Saves the worlds with `save-all`, with `flush` the call returns after everything is written to disk.
+    pub fn save_all(&mut self, flush: bool) -> Result<(), Error> {
+        let command = if flush { "save-all flush" } else { "save-all" };
+        let response = self.call(command.to_string())?;
+        // Paper first sends `Saving the game (this may take a moment!)`, Spigot before 1.13 sends `Saved the world`.
+        changed(
+            &response,
+            &["Saving the game", "Saved the game", "Saved the world"],
+            &[],
+        )
+        .map(|_| ())
+    }
+
+    /// Sends a message to the players matched by `target` with `tellraw`.
+    pub fn tellraw(&mut self, target: &str, text: &Text) -> Result<(), Error> {
+        let target = single_argument(target, "target")?;
+        let response = self.call(format!("tellraw {target} {text}"))?;
+        let plain = check(&response)?;
+        if !plain.trim().is_empty() {
+            return Err(unexpected(&response));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn parse_list_variants() {
+        let vanilla: PlayerList = include_str!("samples/minecraft_list_vanilla.txt")
+            .parse()
+            .unwrap();
+        let paper: PlayerList = include_str!("samples/minecraft_list_paper.txt")
+            .parse()
+            .unwrap();
+        let expected = PlayerList {
+            online: 3,
+            max: 20,
+            players: vec![
+                "Alice".to_string(),
+                "Bob".to_string(),
+                "Carol_99".to_string(),
+            ],
+        };
+        assert_eq!(vanilla, expected);
+        assert_eq!(paper, expected);
+
+        let spigot: PlayerList = include_str!("samples/minecraft_list_spigot.txt")
+            .parse()
+            .unwrap();
+        assert_eq!((spigot.online, spigot.max), (0, 20));
+        assert!(spigot.players.is_empty());
+    }
+
+    #[test]
+    fn typed_commands() {
+        let mut server = Minecraft::new(service_fn(|cmd: String| {
+            Ok(match cmd.as_str() {
+                "whitelist add Alice" => "Added Alice to the whitelist",
+                "whitelist add Bob" => "Player is already whitelisted",
+                "whitelist add Nobody" => "That player does not exist",
+                "whitelist list" => {
+                    "There are 2 (out of 3 seen) whitelisted players:\nAlice and Bob"
+                }
+                "op Alice" => "§eOpped Alice",
+                "deop Alice" => "Nothing changed. The player is not an operator",
+                "time set day" => "Set the time to 1000",
+                "weather thunder" => "Changing to rain and thunder",
+                "save-all flush" => "Saving the game (this may take a moment!)\nSaved the game",
+                _ if cmd.starts_with("tellraw @a ") => "",
+                _ => "Unknown or incomplete command, see below for error",
+            }
+            .to_string())
+        }));
+        assert!(server.whitelist_add("Alice").unwrap());
+        assert!(!server.whitelist_add("Bob").unwrap());
+        assert_eq!(
+            server.whitelist_add("Nobody").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(server.whitelist_list().unwrap(), ["Alice", "Bob"]);
+        assert!(server.op("Alice").unwrap());
+        assert!(!server.deop("Alice").unwrap());
+        assert_eq!(server.time_set(Time::Day).unwrap(), 1000);
+        server.weather(Weather::Thunder).unwrap();
+        server.save_all(true).unwrap();
+        server.tellraw("@a", &Text::new("hi")).unwrap();
+        // Minecraft drops packets with more than 1446 bytes, so they are not sent.
+        assert_eq!(
+            server
+                .tellraw("@a", &Text::new("a".repeat(1500)))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            server
+                .tp("Alice", &Destination::Position(0.0, 64.0, 0.0))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            server.op("Alice Bob").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn text_component_json() {
+        let text = Text::new("Vote ").with_color("gold").with_extra(
+            Text::new("here\n")
+                .with_underlined(true)
+                .with_click(ClickEvent::RunCommand("/vote".to_string()))
+                .with_hover_text(Text::new("Click")),
+        );
+        assert_eq!(
+            text.to_string(),
+            concat!(
+                r#"{"text":"Vote ","color":"gold","extra":[{"text":"here\n","underlined":true,"#,
+                r#""clickEvent":{"action":"run_command","value":"/vote"},"#,
+                r#""hoverEvent":{"action":"show_text","contents":{"text":"Click"}}}]}"#
+            )
+        );
+    }
+}
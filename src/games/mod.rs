@@ -0,0 +1,53 @@
+//! Contains typed wrappers for the commands of specific games, each gated with a feature named after the game.
+//!
+//! Every wrapper takes any [`crate::service::CommandService`], usually an [`crate::RCONClient`], and parses the responses into typed values.
+//! The parsers are tested against the samples in `src/games/samples`. These are not output captured from live servers, none were available when the parsers were written:
+//! they are written by hand from the documented output of each game and the formats the community tools parse, with made up names and IDs.
+//! A format that differs between versions of a game is only covered as far as those sources describe it, replacing a sample with anonymized captured output is welcome.
+//! Quirks in how a game implements the protocol are smoothed over with a [`Dialect`].
+
+#[cfg(feature = "ark")]
+pub mod ark;
+#[cfg(feature = "cs2")]
+pub mod cs2;
+mod dialect;
+#[cfg(feature = "factorio")]
+pub mod factorio;
+pub(crate) mod lua;
+#[cfg(feature = "minecraft")]
+pub mod minecraft;
+#[cfg(feature = "palworld")]
+pub mod palworld;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "source")]
+pub mod source;
+#[cfg(feature = "squad")]
+pub mod squad;
+
+use std::io::Error;
+
+use crate::command;
+
+pub use dialect::{Dialect, DialectService};
+
+/// Checks that a value sent as a single command argument can not end the argument or the command early.
+#[cfg_attr(
+    not(any(
+        feature = "ark",
+        feature = "minecraft",
+        feature = "palworld",
+        feature = "schema",
+        feature = "source"
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn single_argument<'a>(value: &'a str, what: &str) -> Result<&'a str, Error> {
+    command::word(value, what)
+}
+
+/// Quotes free text such as a kick reason, the characters that would end the quote or the command are not allowed.
+#[cfg_attr(not(any(feature = "source", feature = "squad")), allow(dead_code))]
+pub(crate) fn quoted(value: &str, what: &str) -> Result<String, Error> {
+    Dialect::source().get_quoting().quote(value, what)
+}
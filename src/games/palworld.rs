@@ -0,0 +1,221 @@
+//! Contains typed wrappers for the commands of Palworld dedicated servers.
+//!
+//! Every command goes through [`Dialect::palworld()`]. [`Palworld::new`] sets the client to [`TextEncoding::Utf8Lossy`] so responses cut off in the middle of a character can still be read, and accepts packets without the null bytes that should end them.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::palworld::Palworld;
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:25575").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Palworld::new(client);
+//! for player in server.show_players().unwrap() {
+//!     println!("{} {}", player.name, player.steam_id);
+//! }
+//! server.broadcast("Saving the world").unwrap();
+//! server.save().unwrap();
+//! ```
+
+use std::io::{Error, ErrorKind, Read, Write};
+
+use super::{single_argument, Dialect, DialectService};
+#[cfg(doc)]
+use crate::encoding::TextEncoding;
+use crate::{
+    packet::packet_id::ID,
+    service::{CommandService, Layer},
+    RCONClient,
+};
+
+/// A player in the response to `ShowPlayers`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    /// The name of the player, non-ASCII characters may be replaced by `U+FFFD`.
+    pub name: String,
+    /// The ID of the player on this server.
+    pub player_uid: String,
+    /// The SteamID64 of the player, used by `KickPlayer` and `BanPlayer`.
+    pub steam_id: String,
+}
+
+/// Parses the CSV response to `ShowPlayers`, a `name,playeruid,steamid` header followed by a line per player.
+/// Names are not quoted and may contain commas, so the other columns are taken from the end of the line.
+pub fn parse_players(response: &str) -> Result<Vec<Player>, Error> {
+    let mut lines = response.lines();
+    if lines.next().map(str::trim) != Some("name,playeruid,steamid") {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Unexpected response {response:?}"),
+        ));
+    }
+    lines
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut columns = line.rsplitn(3, ',');
+            match (columns.next(), columns.next(), columns.next()) {
+                (Some(steam_id), Some(player_uid), Some(name)) => Ok(Player {
+                    name: name.to_string(),
+                    player_uid: player_uid.trim().to_string(),
+                    steam_id: steam_id.trim().to_string(),
+                }),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid player line {line:?}"),
+                )),
+            }
+        })
+        .collect()
+}
+
+/// The response to `Info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Info {
+    /// The version of the server, such as `v0.1.5.1`.
+    pub version: String,
+    /// The name of the server.
+    pub name: String,
+}
+
+/// Typed wrappers for the commands of a Palworld server, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Palworld<S> {
+    service: DialectService<S>,
+}
+
+impl<T: Read + Write, I: Iterator<Item = ID>> Palworld<RCONClient<T, I>> {
+    /// Creates a new instance of the `Palworld` wrapper, the client is set up with [`Dialect::apply_to`].
+    pub fn new(mut client: RCONClient<T, I>) -> Palworld<RCONClient<T, I>> {
+        Dialect::palworld().apply_to(&mut client);
+        Palworld::from_service(client)
+    }
+}
+
+impl<S: CommandService> Palworld<S> {
+    /// Same as [`Palworld::new`] for any service, such as a client wrapped in layers.
+    /// The client underneath has to decode responses with [`TextEncoding::Utf8Lossy`] and accept packets without terminators.
+    pub fn from_service(service: S) -> Palworld<S> {
+        Palworld {
+            service: Dialect::palworld().layer(service),
+        }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service.into_inner()
+    }
+
+    /// Runs a command, responses starting with `Failed` become errors.
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        let response = self.service.call(command)?;
+        if response.starts_with("Failed") {
+            let kind = if response.to_ascii_lowercase().contains("not found") {
+                ErrorKind::NotFound
+            } else {
+                ErrorKind::Other
+            };
+            return Err(Error::new(kind, response.trim().to_string()));
+        }
+        Ok(response)
+    }
+
+    /// Runs `ShowPlayers`.
+    pub fn show_players(&mut self) -> Result<Vec<Player>, Error> {
+        parse_players(&self.call("ShowPlayers".to_string())?)
+    }
+
+    /// Runs `Info`.
+    pub fn info(&mut self) -> Result<Info, Error> {
+        let response = self.call("Info".to_string())?;
+        // `Welcome to Pal Server[v0.1.5.1] Default Palworld Server`
+        response
+            .trim()
+            .strip_prefix("Welcome to Pal Server[")
+            .and_then(|rest| rest.split_once(']'))
+            .map(|(version, name)| Info {
+                version: version.to_string(),
+                name: name.trim().to_string(),
+            })
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unexpected response {response:?}"),
+                )
+            })
+    }
+
+    /// Shows a message to every player with `Broadcast`.
+    /// Palworld only shows the first word of the message, so spaces are replaced by underscores.
+    pub fn broadcast(&mut self, message: &str) -> Result<(), Error> {
+        let message = message.trim().replace(' ', "_");
+        let message = single_argument(&message, "message")?;
+        self.call(format!("Broadcast {message}")).map(|_| ())
+    }
+
+    /// Kicks the player with the given SteamID64, see [`Player::steam_id`].
+    pub fn kick_player(&mut self, steam_id: &str) -> Result<(), Error> {
+        let steam_id = single_argument(steam_id, "SteamID")?;
+        self.call(format!("KickPlayer {steam_id}")).map(|_| ())
+    }
+
+    /// Bans the player with the given SteamID64, see [`Player::steam_id`].
+    pub fn ban_player(&mut self, steam_id: &str) -> Result<(), Error> {
+        let steam_id = single_argument(steam_id, "SteamID")?;
+        self.call(format!("BanPlayer {steam_id}")).map(|_| ())
+    }
+
+    /// Saves the world with `Save`.
+    pub fn save(&mut self) -> Result<(), Error> {
+        self.call("Save".to_string()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn parse_show_players() {
+        let raw = include_bytes!("samples/palworld_showplayers.txt");
+        // What a client set to the encoding of the dialect returns.
+        let decoded = Dialect::palworld()
+            .get_encoding()
+            .decode(raw)
+            .unwrap()
+            .into_owned();
+        let mut sent = Vec::new();
+        let mut server = Palworld::from_service(service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            Ok(match cmd.as_str() {
+                "ShowPlayers" => decoded.clone(),
+                "Info" => "Welcome to Pal Server[v0.1.5.1] Default Palworld Server\n".to_string(),
+                "KickPlayer 0" => "Failed to kick: Not Found Player\n".to_string(),
+                _ => format!("{cmd}\n"),
+            })
+        }));
+        let players = server.show_players().unwrap();
+        let names: Vec<&str> = players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Alice", "Bob, the builder", "Zo\u{FFFD}"]);
+        assert_eq!(players[0].player_uid, "1234567890");
+        assert_eq!(players[0].steam_id, "76561198000000001");
+
+        let info = server.info().unwrap();
+        assert_eq!(info.version, "v0.1.5.1");
+        assert_eq!(info.name, "Default Palworld Server");
+
+        server.broadcast("Restart in 5 minutes").unwrap();
+        assert_eq!(
+            server.kick_player("0").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            server.broadcast("Grüße").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        server.into_inner();
+        assert_eq!(sent[2], "Broadcast Restart_in_5_minutes");
+        assert_eq!(sent.len(), 4);
+    }
+}
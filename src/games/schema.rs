@@ -0,0 +1,580 @@
+//! Contains [`Schema`], which describes the commands of a game in a TOML file so games without a module here can be supported without writing Rust.
+//!
+//! # File format
+//! ```toml
+//! game = "Valheim"
+//! # Responses matching any of these regexes are errors, for every command.
+//! errors = ['^Unknown command']
+//!
+//! [commands.players]
+//! help = "The connected players"
+//! template = "players"
+//! # One record per line that matches the pattern, lines that do not match are skipped.
+//! parser = "lines"
+//! pattern = '^(?P<name>.+?) \((?P<steam_id>\d{17})\)$'
+//!
+//! [commands.kick]
+//! # `{name}` is a single argument, `{name:text}` is free text that may contain spaces.
+//! template = "kick {name}"
+//! parser = "none"
+//! ```
+//!
+//! A command has a `template` and a `parser`, which is one of
+//! - `raw` (the default): the whole response as the field `response`,
+//! - `none`: the response is ignored,
+//! - `regex`: the named groups of the first match of `pattern`, fails if nothing matches,
+//! - `lines`: a record per line matching `pattern`, after skipping the first `skip` lines,
+//! - `key-value`: one record from `Key: Value` lines, with the keys in snake case and `separator` defaulting to `:`.
+//!
+//! Fields are strings unless `types` says otherwise, for example `types = { ping = "integer" }` with `string`, `integer`, `float` or `bool`.
+//! `errors` can also be set per command. Examples for a few games are in `src/games/schemas`.
+//! They are written from the documented commands of those games and tested against hand-written responses, not output captured from live servers, see the [`crate::games`] module.
+//!
+//! With the schema-yaml feature the same structure can also be written in YAML and loaded with `Schema::from_yaml()`.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::schema::Schema;
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let schema: Schema = std::fs::read_to_string("valheim.toml").unwrap().parse().unwrap();
+//! let stream = TcpStream::connect("127.0.0.1:2458").unwrap();
+//! let mut client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let players = schema.run(&mut client, "players", &[]).unwrap();
+//! schema.run(&mut client, "kick", &[("name", "Alice")]).unwrap();
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::single_argument;
+use crate::service::CommandService;
+
+/// A parsed field.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    /// A `string` field.
+    String(String),
+    /// An `integer` field.
+    Integer(i64),
+    /// A `float` field.
+    Float(f64),
+    /// A `bool` field, `true`/`false`, `yes`/`no`, `on`/`off` or `1`/`0` in any case.
+    Bool(bool),
+}
+
+/// The named fields parsed from a response or one of its lines.
+pub type Record = BTreeMap<String, Value>;
+
+/// The result of running a command, serializes to `null`, an object or an array of objects.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Output {
+    /// From the `none` parser.
+    None,
+    /// From the `raw`, `regex` and `key-value` parsers.
+    Record(Record),
+    /// From the `lines` parser.
+    Records(Vec<Record>),
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum ParserKind {
+    #[default]
+    Raw,
+    None,
+    Regex,
+    Lines,
+    KeyValue,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    #[default]
+    String,
+    Integer,
+    Float,
+    Bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaFile {
+    game: String,
+    #[serde(default)]
+    errors: Vec<String>,
+    #[serde(default)]
+    commands: BTreeMap<String, CommandFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommandFile {
+    template: String,
+    help: Option<String>,
+    #[serde(default)]
+    parser: ParserKind,
+    pattern: Option<String>,
+    #[serde(default)]
+    skip: usize,
+    separator: Option<String>,
+    #[serde(default)]
+    types: BTreeMap<String, FieldType>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Argument { name: String, text: bool },
+}
+
+/// A command described by a [`Schema`].
+#[derive(Debug, Clone)]
+pub struct CommandSchema {
+    help: Option<String>,
+    template: Vec<Segment>,
+    parser: ParserKind,
+    pattern: Option<Regex>,
+    skip: usize,
+    separator: String,
+    types: BTreeMap<String, FieldType>,
+    errors: Vec<Regex>,
+}
+
+fn invalid_schema(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn compile(pattern: &str) -> Result<Regex, Error> {
+    Regex::new(pattern).map_err(|e| invalid_schema(format!("Invalid pattern {pattern:?}: {e}")))
+}
+
+/// Splits `kick {name} {reason:text}` into literals and arguments.
+fn parse_template(template: &str) -> Result<Vec<Segment>, Error> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Literal(rest[..start].to_string()));
+        }
+        let (argument, after) = rest[start + 1..]
+            .split_once('}')
+            .ok_or_else(|| invalid_schema(format!("Unclosed `{{` in template {template:?}")))?;
+        let (name, text) = match argument.split_once(':') {
+            Some((name, "text")) => (name, true),
+            None => (argument, false),
+            Some((_, kind)) => {
+                return Err(invalid_schema(format!(
+                    "Unknown argument kind {kind:?} in template {template:?}"
+                )))
+            }
+        };
+        if name.is_empty() {
+            return Err(invalid_schema(format!(
+                "Unnamed argument in template {template:?}"
+            )));
+        }
+        segments.push(Segment::Argument {
+            name: name.to_string(),
+            text,
+        });
+        rest = after;
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+impl CommandSchema {
+    fn from_file(name: &str, file: CommandFile, errors: &[Regex]) -> Result<CommandSchema, Error> {
+        let pattern = file.pattern.as_deref().map(compile).transpose()?;
+        if matches!(file.parser, ParserKind::Regex | ParserKind::Lines) && pattern.is_none() {
+            return Err(invalid_schema(format!(
+                "Command {name:?} needs a pattern for its parser"
+            )));
+        }
+        let mut command_errors = errors.to_vec();
+        for error in &file.errors {
+            command_errors.push(compile(error)?);
+        }
+        Ok(CommandSchema {
+            help: file.help,
+            template: parse_template(&file.template)?,
+            parser: file.parser,
+            pattern,
+            skip: file.skip,
+            separator: file.separator.unwrap_or_else(|| ":".to_string()),
+            types: file.types,
+            errors: command_errors,
+        })
+    }
+
+    /// Gets the description of the command.
+    pub fn get_help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// The names of the arguments of the command, in the order they appear in the template.
+    pub fn arguments(&self) -> impl Iterator<Item = &str> {
+        self.template.iter().filter_map(|segment| match segment {
+            Segment::Argument { name, .. } => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Fills in the template, every argument must be given exactly once.
+    pub fn render(&self, args: &[(&str, &str)]) -> Result<String, Error> {
+        for (name, _) in args {
+            if !self.arguments().any(|argument| argument == *name) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unknown argument {name:?}"),
+                ));
+            }
+        }
+        let mut command = String::new();
+        for segment in &self.template {
+            match segment {
+                Segment::Literal(literal) => command.push_str(literal),
+                Segment::Argument { name, text } => {
+                    let value = args
+                        .iter()
+                        .find(|(arg, _)| arg == name)
+                        .map(|(_, value)| *value)
+                        .ok_or_else(|| {
+                            Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("Missing argument {name:?}"),
+                            )
+                        })?;
+                    if *text {
+                        if value.chars().any(|c| c.is_control() || c == ';') {
+                            return Err(Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("Invalid {name} {value:?}"),
+                            ));
+                        }
+                        command.push_str(value);
+                    } else {
+                        command.push_str(single_argument(value, name)?);
+                    }
+                }
+            }
+        }
+        Ok(command)
+    }
+
+    fn convert(&self, name: &str, value: &str) -> Result<Value, Error> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid value {value:?} for field {name:?}"),
+            )
+        };
+        let value = value.trim();
+        Ok(match self.types.get(name).copied().unwrap_or_default() {
+            FieldType::String => Value::String(value.to_string()),
+            FieldType::Integer => Value::Integer(value.parse().map_err(|_| invalid())?),
+            FieldType::Float => Value::Float(value.parse().map_err(|_| invalid())?),
+            FieldType::Bool => Value::Bool(match value.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => true,
+                "false" | "no" | "off" | "0" => false,
+                _ => return Err(invalid()),
+            }),
+        })
+    }
+
+    fn captures(&self, regex: &Regex, text: &str) -> Result<Option<Record>, Error> {
+        let Some(captures) = regex.captures(text) else {
+            return Ok(None);
+        };
+        let mut record = Record::new();
+        for name in regex.capture_names().flatten() {
+            if let Some(value) = captures.name(name) {
+                record.insert(name.to_string(), self.convert(name, value.as_str())?);
+            }
+        }
+        Ok(Some(record))
+    }
+
+    /// Parses a response to the command.
+    pub fn parse(&self, response: &str) -> Result<Output, Error> {
+        if self.errors.iter().any(|error| error.is_match(response)) {
+            return Err(Error::other(response.trim().to_string()));
+        }
+        match self.parser {
+            ParserKind::None => Ok(Output::None),
+            ParserKind::Raw => Ok(Output::Record(Record::from([(
+                "response".to_string(),
+                self.convert("response", response)?,
+            )]))),
+            ParserKind::Regex => {
+                let regex = self.pattern.as_ref().expect("checked when loading");
+                self.captures(regex, response)?
+                    .map(Output::Record)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Unexpected response {response:?}"),
+                        )
+                    })
+            }
+            ParserKind::Lines => {
+                let regex = self.pattern.as_ref().expect("checked when loading");
+                let mut records = Vec::new();
+                for line in response.lines().skip(self.skip) {
+                    records.extend(self.captures(regex, line.trim_end())?);
+                }
+                Ok(Output::Records(records))
+            }
+            ParserKind::KeyValue => {
+                let mut record = Record::new();
+                for line in response.lines().skip(self.skip) {
+                    if let Some((key, value)) = line.split_once(self.separator.as_str()) {
+                        let key = key.trim().to_lowercase().replace([' ', '-'], "_");
+                        let value = self.convert(&key, value)?;
+                        record.insert(key, value);
+                    }
+                }
+                Ok(Output::Record(record))
+            }
+        }
+    }
+}
+
+/// The commands of a game, loaded from a TOML file, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Schema {
+    game: String,
+    commands: BTreeMap<String, CommandSchema>,
+}
+
+impl FromStr for Schema {
+    type Err = Error;
+
+    /// Loads a schema from TOML, fails with [`ErrorKind::InvalidInput`] if the file or a pattern in it is invalid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let file: SchemaFile = toml::from_str(s).map_err(|e| invalid_schema(e.to_string()))?;
+        Schema::from_file(file)
+    }
+}
+
+impl Schema {
+    /// Loads a schema from YAML, with the same structure as the TOML files. Only available with the schema-yaml feature.
+    /// Fails with [`ErrorKind::InvalidInput`] if the file or a pattern in it is invalid.
+    #[cfg(feature = "schema-yaml")]
+    pub fn from_yaml(s: &str) -> Result<Schema, Error> {
+        let file: SchemaFile =
+            serde_yaml::from_str(s).map_err(|e| invalid_schema(e.to_string()))?;
+        Schema::from_file(file)
+    }
+
+    fn from_file(file: SchemaFile) -> Result<Schema, Error> {
+        let errors = file
+            .errors
+            .iter()
+            .map(|error| compile(error))
+            .collect::<Result<Vec<_>, _>>()?;
+        let commands = file
+            .commands
+            .into_iter()
+            .map(|(name, command)| {
+                let command = CommandSchema::from_file(&name, command, &errors)?;
+                Ok((name, command))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Schema {
+            game: file.game,
+            commands,
+        })
+    }
+
+    /// Gets the name of the game.
+    pub fn get_game(&self) -> &str {
+        &self.game
+    }
+
+    /// Gets a command by name.
+    pub fn get_command(&self, name: &str) -> Option<&CommandSchema> {
+        self.commands.get(name)
+    }
+
+    /// The names of the commands, sorted.
+    pub fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+
+    /// Renders the command with the arguments, runs it and parses the response.
+    /// Fails with [`ErrorKind::NotFound`] if the schema does not have the command.
+    pub fn run<S: CommandService>(
+        &self,
+        service: &mut S,
+        command: &str,
+        args: &[(&str, &str)],
+    ) -> Result<Output, Error> {
+        let schema = self.get_command(command).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{} has no command {command:?}", self.game),
+            )
+        })?;
+        let response = service.call(schema.render(args)?)?;
+        schema.parse(&response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn bundled_schemas_load() {
+        for schema in [
+            include_str!("schemas/conan_exiles.toml"),
+            include_str!("schemas/space_engineers.toml"),
+            include_str!("schemas/valheim.toml"),
+        ] {
+            let schema: Schema = schema.parse().unwrap();
+            assert!(schema.command_names().count() >= 3, "{}", schema.get_game());
+        }
+    }
+
+    #[test]
+    fn run_against_sample_responses() {
+        let schema: Schema = include_str!("schemas/conan_exiles.toml").parse().unwrap();
+        let mut sent = Vec::new();
+        let mut server = service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            Ok(match cmd.as_str() {
+                "ListPlayers" => include_str!("samples/conan_listplayers.txt").to_string(),
+                "GetTime" => "The time is 13:05".to_string(),
+                "Broadcast Restart soon" => String::new(),
+                _ => "Couldn't find the command: KickPlayer".to_string(),
+            })
+        });
+        let Output::Records(players) = schema.run(&mut server, "players", &[]).unwrap() else {
+            panic!("expected records");
+        };
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[1]["index"], Value::Integer(1));
+        assert_eq!(players[1]["name"], Value::String("bob".to_string()));
+        assert_eq!(
+            players[0]["platform_id"],
+            Value::String("76561198000000001".to_string())
+        );
+
+        let Output::Record(time) = schema.run(&mut server, "time", &[]).unwrap() else {
+            panic!("expected a record");
+        };
+        assert_eq!(time["hour"], Value::Integer(13));
+
+        assert_eq!(
+            schema
+                .run(&mut server, "broadcast", &[("message", "Restart soon")])
+                .unwrap(),
+            Output::None
+        );
+        assert_eq!(
+            schema
+                .run(&mut server, "kick", &[("name", "bob")])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Other
+        );
+        for args in [&[][..], &[("name", "bob; quit")], &[("nam", "bob")]] {
+            assert_eq!(
+                schema.run(&mut server, "kick", args).unwrap_err().kind(),
+                ErrorKind::InvalidInput
+            );
+        }
+        assert_eq!(
+            schema.run(&mut server, "nope", &[]).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(sent[2..], ["Broadcast Restart soon", "KickPlayer bob"]);
+    }
+
+    #[cfg(feature = "schema-yaml")]
+    #[test]
+    fn yaml_schema() {
+        let yaml = r#"
+game: Valheim
+errors: ['^Unknown command']
+commands:
+  players:
+    template: players
+    parser: lines
+    pattern: '^(?P<name>.+?) \((?P<steam_id>\d{17})\)$'
+  kick:
+    template: kick {name}
+    parser: none
+"#;
+        let schema = Schema::from_yaml(yaml).unwrap();
+        assert_eq!(schema.get_game(), "Valheim");
+        assert_eq!(
+            schema.command_names().collect::<Vec<_>>(),
+            ["kick", "players"]
+        );
+        let players = schema
+            .get_command("players")
+            .unwrap()
+            .parse("Alice (76561198000000001)\n")
+            .unwrap();
+        let Output::Records(players) = players else {
+            panic!("expected records");
+        };
+        assert_eq!(players[0]["name"], Value::String("Alice".to_string()));
+        assert_eq!(
+            Schema::from_yaml("game: x\ncommands:\n  a:\n    templat: a\n")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn key_value_and_invalid_schemas() {
+        let schema: Schema = include_str!("schemas/valheim.toml").parse().unwrap();
+        let info = schema
+            .get_command("info")
+            .unwrap()
+            .parse(include_str!("samples/valheim_serverstats.txt"))
+            .unwrap();
+        let Output::Record(info) = info else {
+            panic!("expected a record");
+        };
+        assert_eq!(info["version"], Value::String("0.218.21".to_string()));
+        assert_eq!(info["players"], Value::Integer(2));
+        assert_eq!(
+            serde_json::to_string(&Output::Record(info)).unwrap(),
+            r#"{"name":"Midgard","players":2,"uptime":3600,"version":"0.218.21","world":"Dedicated"}"#
+        );
+
+        for invalid in [
+            "game = \"x\"\n[commands.a]\ntemplate = \"a\"\nparser = \"regex\"",
+            "game = \"x\"\n[commands.a]\ntemplate = \"a {b\"",
+            "game = \"x\"\n[commands.a]\ntemplate = \"a\"\nparser = \"lines\"\npattern = \"(\"",
+            "game = \"x\"\n[commands.a]\ntemplat = \"a\"",
+        ] {
+            assert_eq!(
+                invalid.parse::<Schema>().unwrap_err().kind(),
+                ErrorKind::InvalidInput,
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Contains typed wrappers for the commands of Source engine dedicated servers (srcds), such as Counter-Strike: Global Offensive, Team Fortress 2 and Garry's Mod.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::source::Source;
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut server = Source::new(client);
+//! let status = server.status().unwrap();
+//! for player in status.players.iter().filter(|player| player.ping.unwrap_or(0) > 200) {
+//!     server.kick(player.userid, Some("Ping too high")).unwrap();
+//! }
+//! let gravity: f32 = server.cvar_get("sv_gravity").unwrap();
+//! server.cvar_set("sv_cheats", false).unwrap();
+//! ```
+
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+use super::{quoted, single_argument};
+use crate::service::CommandService;
+
+/// A player in the response to `status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    /// The ID of the player for this session, used by `kickid` and `banid`.
+    pub userid: u32,
+    /// The name of the player.
+    pub name: String,
+    /// The SteamID, `STEAM_1:0:12345` or `[U:1:123456789]` depending on the game, or `BOT`.
+    pub unique_id: String,
+    /// How long the player has been connected, not shown for bots.
+    pub connected: Option<Duration>,
+    /// The ping in milliseconds, not shown for bots on some games.
+    pub ping: Option<u32>,
+    /// The packet loss in percent, not shown for bots.
+    pub loss: Option<u32>,
+    /// The connection state, usually `active` or `spawning`.
+    pub state: String,
+    /// The IP address and port of the player, not shown for bots.
+    pub address: Option<String>,
+}
+
+impl Player {
+    /// Whether the player is a bot.
+    pub fn is_bot(&self) -> bool {
+        self.unique_id == "BOT"
+    }
+}
+
+/// The response to `status`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Status {
+    /// The name of the server.
+    pub hostname: String,
+    /// The version line.
+    pub version: Option<String>,
+    /// The current map.
+    pub map: String,
+    /// The maximum number of players.
+    pub max_players: Option<u32>,
+    /// The connected players, including bots.
+    pub players: Vec<Player>,
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Parses `05:12` or `1:02:33`.
+fn parse_connected(value: &str) -> Option<Duration> {
+    value
+        .split(':')
+        .try_fold(0u64, |total, part| {
+            Some(total * 60 + part.parse::<u64>().ok()?)
+        })
+        .map(Duration::from_secs)
+}
+
+/// Parses a player line such as `#  2 1 "Alice" STEAM_1:0:12345 05:12 45 0 active 196608 198.51.100.2:27005`, where the slot after the userid is only shown by some games.
+fn parse_player(line: &str) -> Option<Player> {
+    let rest = line.strip_prefix('#')?.trim_start();
+    let (userid, rest) = rest.split_once(char::is_whitespace)?;
+    let userid = userid.parse().ok()?;
+    // The name is quoted but may contain quotes itself, it ends at the last quote.
+    let name_start = rest.find('"')?;
+    let name_end = rest.rfind('"')?;
+    if name_end <= name_start {
+        return None;
+    }
+    let name = rest[name_start + 1..name_end].to_string();
+    let fields: Vec<&str> = rest[name_end + 1..].split_whitespace().collect();
+    let (&unique_id, fields) = fields.split_first()?;
+
+    let mut player = Player {
+        userid,
+        name,
+        unique_id: unique_id.to_string(),
+        connected: None,
+        ping: None,
+        loss: None,
+        state: String::new(),
+        address: None,
+    };
+    if player.is_bot() {
+        // `BOT active 64` or `BOT active`
+        player.state = fields.first()?.to_string();
+        player.ping = fields.get(1).and_then(|ping| ping.parse().ok());
+    } else {
+        // `05:12 45 0 active [rate] 198.51.100.2:27005`
+        player.connected = Some(parse_connected(fields.first()?)?);
+        player.ping = Some(fields.get(1)?.parse().ok()?);
+        player.loss = Some(fields.get(2)?.parse().ok()?);
+        player.state = fields.get(3)?.to_string();
+        player.address = fields
+            .last()
+            .filter(|_| fields.len() > 4)
+            .map(|a| a.to_string());
+    }
+    Some(player)
+}
+
+impl std::str::FromStr for Status {
+    type Err = Error;
+
+    /// Parses the response to `status`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut status = Status::default();
+        let mut has_map = false;
+        for line in s.lines() {
+            let line = line.trim_end();
+            if line.starts_with("# userid") || line == "#end" {
+                continue;
+            }
+            if line.starts_with('#') {
+                let player = parse_player(line)
+                    .ok_or_else(|| invalid_data(format!("Invalid player line {line:?}")))?;
+                status.players.push(player);
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "hostname" => status.hostname = value.to_string(),
+                "version" => status.version = Some(value.to_string()),
+                "map" => {
+                    // TF2 adds the position of the camera after the map name.
+                    status.map = value
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string();
+                    has_map = true;
+                }
+                "players" => {
+                    // `2 humans, 1 bots (16/0 max)` or `1 humans, 0 bots (32 max)`
+                    status.max_players = value
+                        .split_once('(')
+                        .and_then(|(_, max)| max.split(|c: char| !c.is_ascii_digit()).next())
+                        .and_then(|max| max.parse().ok());
+                }
+                _ => {}
+            }
+        }
+        if !has_map {
+            return Err(invalid_data(format!("Invalid status response {s:?}")));
+        }
+        Ok(status)
+    }
+}
+
+/// A type that can be read from and written to a console variable.
+pub trait CvarValue: Sized {
+    /// Parses the value shown by the server.
+    fn from_cvar(value: &str) -> Option<Self>;
+    /// Formats the value to set it.
+    fn to_cvar(&self) -> String;
+}
+
+impl CvarValue for bool {
+    fn from_cvar(value: &str) -> Option<Self> {
+        match value {
+            "1" | "true" => Some(true),
+            "0" | "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn to_cvar(&self) -> String {
+        u8::from(*self).to_string()
+    }
+}
+
+impl CvarValue for String {
+    fn from_cvar(value: &str) -> Option<Self> {
+        Some(value.to_string())
+    }
+
+    fn to_cvar(&self) -> String {
+        self.clone()
+    }
+}
+
+macro_rules! cvar_value_from_str {
+    ($($t:ty),*) => {
+        $(impl CvarValue for $t {
+            fn from_cvar(value: &str) -> Option<Self> {
+                value.parse().ok()
+            }
+
+            fn to_cvar(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+cvar_value_from_str!(i32, i64, u32, u64, f32, f64);
+
+/// Takes the value from the response to reading a cvar, `"sv_gravity" = "800" ( def. "800" ) notify replicated` on older games and `sv_gravity = 800` on newer ones.
+fn parse_cvar<'a>(name: &str, response: &'a str) -> Option<&'a str> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim().trim_matches('"') != name {
+            return None;
+        }
+        let value = value.trim_start();
+        match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').map(|(value, _)| value),
+            None => value.split_whitespace().next().or(Some("")),
+        }
+    })
+}
+
+/// Typed wrappers for the commands of a Source engine server, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Source<S> {
+    service: S,
+}
+
+impl<S: CommandService> Source<S> {
+    /// Creates a new instance of the `Source` wrapper.
+    pub fn new(service: S) -> Source<S> {
+        Source { service }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+
+    /// Gets the wrapped service, to send commands that have no wrapper.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.service
+    }
+
+    /// Runs `status`.
+    pub fn status(&mut self) -> Result<Status, Error> {
+        self.service.call("status".to_string())?.parse()
+    }
+
+    /// Reads a console variable. Fails with [`ErrorKind::NotFound`] if the variable does not exist and [`ErrorKind::InvalidData`] if the value is not a `T`.
+    pub fn cvar_get<T: CvarValue>(&mut self, name: &str) -> Result<T, Error> {
+        let name = single_argument(name, "cvar name")?;
+        let response = self.service.call(name.to_string())?;
+        let value = parse_cvar(name, &response).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("Unknown cvar {name:?}: {response}"),
+            )
+        })?;
+        T::from_cvar(value)
+            .ok_or_else(|| invalid_data(format!("Invalid value {value:?} for cvar {name:?}")))
+    }
+
+    /// Sets a console variable.
+    pub fn cvar_set<T: CvarValue>(&mut self, name: &str, value: T) -> Result<String, Error> {
+        let name = single_argument(name, "cvar name")?;
+        let value = quoted(&value.to_cvar(), "cvar value")?;
+        self.service.call(format!("{name} {value}"))
+    }
+
+    /// Changes the map with `changelevel`.
+    pub fn changelevel(&mut self, map: &str) -> Result<String, Error> {
+        let map = single_argument(map, "map name")?;
+        self.service.call(format!("changelevel {map}"))
+    }
+
+    /// Kicks the player with the given userid, see [`Player::userid`].
+    pub fn kick(&mut self, userid: u32, reason: Option<&str>) -> Result<String, Error> {
+        match reason {
+            Some(reason) => {
+                let reason = quoted(reason, "kick reason")?;
+                self.service.call(format!("kickid {userid} {reason}"))
+            }
+            None => self.service.call(format!("kickid {userid}")),
+        }
+    }
+
+    /// Bans the player with the given userid for `minutes`, or permanently if it is 0, and kicks them.
+    /// Use `writeid` afterwards to keep the ban after a restart.
+    pub fn ban(&mut self, userid: u32, minutes: u32) -> Result<String, Error> {
+        self.service.call(format!("banid {minutes} {userid} kick"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+
+    #[test]
+    fn parse_csgo_status() {
+        let status: Status = include_str!("samples/csgo_status.txt").parse().unwrap();
+        assert_eq!(status.hostname, "[EU] Friendly Dust2 | 128 tick");
+        assert_eq!(status.map, "de_dust2");
+        assert_eq!(status.max_players, Some(16));
+        assert_eq!(status.players.len(), 3);
+
+        let alice = &status.players[0];
+        assert_eq!(alice.userid, 2);
+        assert_eq!(alice.name, "Alice");
+        assert_eq!(alice.unique_id, "STEAM_1:0:12345");
+        assert_eq!(alice.connected, Some(Duration::from_secs(5 * 60 + 12)));
+        assert_eq!((alice.ping, alice.loss), (Some(45), Some(0)));
+        assert_eq!(alice.address.as_deref(), Some("198.51.100.2:27005"));
+
+        let bot = &status.players[1];
+        assert!(bot.is_bot());
+        assert_eq!(bot.name, "BOT Bob");
+        assert_eq!((bot.ping, bot.loss), (Some(64), None));
+
+        let carol = &status.players[2];
+        assert_eq!(carol.name, "Carol \"the sniper\"");
+        assert_eq!(
+            carol.connected,
+            Some(Duration::from_secs(3600 + 2 * 60 + 33))
+        );
+    }
+
+    #[test]
+    fn parse_tf2_status() {
+        let status: Status = include_str!("samples/tf2_status.txt").parse().unwrap();
+        assert_eq!(status.map, "ctf_2fort");
+        assert_eq!(status.max_players, Some(32));
+        let dave = &status.players[0];
+        assert_eq!(dave.userid, 281);
+        assert_eq!(dave.unique_id, "[U:1:123456789]");
+        assert_eq!(dave.state, "active");
+        assert_eq!(dave.address.as_deref(), Some("198.51.100.4:27005"));
+    }
+
+    #[test]
+    fn typed_commands() {
+        let mut sent = Vec::new();
+        let mut server = Source::new(service_fn(|cmd: String| {
+            sent.push(cmd.clone());
+            Ok(match cmd.as_str() {
+                "sv_gravity" => {
+                    "\"sv_gravity\" = \"800\" ( def. \"800\" ) notify replicated".to_string()
+                }
+                "sv_cheats" => "sv_cheats = false".to_string(),
+                _ => format!("Unknown command \"{cmd}\""),
+            })
+        }));
+        assert_eq!(server.cvar_get::<f32>("sv_gravity").unwrap(), 800.0);
+        assert!(!server.cvar_get::<bool>("sv_cheats").unwrap());
+        assert_eq!(
+            server.cvar_get::<i32>("nope").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        server.cvar_set("sv_cheats", true).unwrap();
+        server.kick(7, Some("AFK")).unwrap();
+        server.ban(7, 30).unwrap();
+        assert_eq!(
+            server.changelevel("de_dust2; quit").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        server.into_inner();
+        assert_eq!(
+            sent[3..],
+            ["sv_cheats \"1\"", "kickid 7 \"AFK\"", "banid 30 7 kick"]
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! Contains typed wrappers and event parsing for Squad and Post Scriptum servers.
+//!
+//! Besides responses to commands, these servers push chat messages and admin events as packets of type 1 ([`PacketType::Raw(1)`](PacketType::Raw)).
+//! [`SquadEvent`] parses those pushes and [`Events`] reads them from a connection that is only used for listening.
+//!
+//! # Example
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::games::squad::{ChatChannel, Events, Squad, SquadEvent};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let connect = || {
+//!     let stream = TcpStream::connect("127.0.0.1:21114")?;
+//!     let events = stream.try_clone()?;
+//!     let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string())?;
+//!     Ok::<_, std::io::Error>((client, events))
+//! };
+//! let (client, _) = connect().unwrap();
+//! let mut server = Squad::new(client);
+//! // The client of the second connection is kept alive but never used, its stream is only read from.
+//! let (_listener, events) = connect().unwrap();
+//! for event in Events::new(events) {
+//!     if let SquadEvent::ChatMessage { channel: ChatChannel::All, player, message, .. } = event.unwrap() {
+//!         if message.contains("!admin") {
+//!             server.broadcast(&format!("{} needs an admin", player.name)).unwrap();
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::{
+    io::{Error, ErrorKind, Read},
+    str::FromStr,
+};
+
+use super::quoted;
+use crate::{
+    packet::{Packet, PacketType},
+    service::CommandService,
+};
+
+/// The IDs of a player, newer versions of Squad show an Epic Online Services ID next to the SteamID.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlayerIds {
+    /// The SteamID64.
+    pub steam_id: Option<String>,
+    /// The Epic Online Services ID.
+    pub eos_id: Option<String>,
+}
+
+impl PlayerIds {
+    /// Finds the IDs in text such as `SteamID:7656...`, `steamid=7656...` or `Online IDs: EOS: 0002... steam: 7656...`.
+    fn parse(text: &str) -> PlayerIds {
+        let words: Vec<&str> = text
+            .split(|c: char| c.is_whitespace() || c == ':' || c == '=')
+            .filter(|word| !word.is_empty())
+            .collect();
+        let mut ids = PlayerIds::default();
+        for (i, word) in words.iter().enumerate() {
+            if word.len() == 17
+                && word.starts_with("7656")
+                && word.bytes().all(|b| b.is_ascii_digit())
+            {
+                ids.steam_id = Some(word.to_string());
+            } else if word.eq_ignore_ascii_case("eos") {
+                ids.eos_id = words.get(i + 1).map(|id| id.to_string());
+            }
+        }
+        ids
+    }
+}
+
+/// A player mentioned in an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventPlayer {
+    /// The name of the player, including any clan tag.
+    pub name: String,
+    /// The IDs of the player.
+    pub ids: PlayerIds,
+}
+
+/// The chat a message was sent in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatChannel {
+    /// `ChatAll`
+    All,
+    /// `ChatTeam`
+    Team,
+    /// `ChatSquad`
+    Squad,
+    /// `ChatAdmin`
+    Admin,
+    /// A channel this crate does not know about.
+    Other(String),
+}
+
+/// A message pushed by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SquadEvent {
+    /// A chat message, `[ChatAll] [SteamID:7656...] Alice : gg`.
+    ChatMessage {
+        /// The chat it was sent in.
+        channel: ChatChannel,
+        /// Who sent it.
+        player: EventPlayer,
+        /// The message.
+        message: String,
+    },
+    /// An admin entered or left the admin camera.
+    AdminCamera {
+        /// The admin.
+        player: EventPlayer,
+        /// `true` when entering the camera, `false` when leaving it.
+        possessed: bool,
+    },
+    /// A player was warned with `AdminWarn`.
+    PlayerWarned {
+        /// The name of the player.
+        name: String,
+        /// The warning.
+        message: String,
+    },
+    /// A player was kicked.
+    PlayerKicked {
+        /// The ID of the player in this match.
+        player_id: u32,
+        /// The player.
+        player: EventPlayer,
+    },
+    /// A player was banned.
+    PlayerBanned {
+        /// The ID of the player in this match.
+        player_id: u32,
+        /// The player.
+        player: EventPlayer,
+        /// How long, such as `1d` or `0` for a permanent ban.
+        interval: String,
+    },
+    /// A player created a squad.
+    SquadCreated {
+        /// The creator of the squad.
+        player: EventPlayer,
+        /// The ID of the squad within its team.
+        squad_id: u32,
+        /// The name of the squad.
+        squad_name: String,
+        /// The team, either its faction name or `Team 1`/`Team 2` depending on the version.
+        team: String,
+    },
+    /// Any other message.
+    Other(String),
+}
+
+/// Splits `[ids] name` into the player.
+fn bracketed_player(text: &str) -> Option<EventPlayer> {
+    let (ids, name) = text.strip_prefix('[')?.split_once(']')?;
+    Some(EventPlayer {
+        name: name.trim().to_string(),
+        ids: PlayerIds::parse(ids),
+    })
+}
+
+fn parse_chat(text: &str) -> Option<SquadEvent> {
+    let (channel, rest) = text.strip_prefix('[')?.split_once(']')?;
+    let channel = match channel {
+        "ChatAll" => ChatChannel::All,
+        "ChatTeam" => ChatChannel::Team,
+        "ChatSquad" => ChatChannel::Squad,
+        "ChatAdmin" => ChatChannel::Admin,
+        other if other.starts_with("Chat") => ChatChannel::Other(other.to_string()),
+        _ => return None,
+    };
+    let (player, message) = rest.trim_start().split_once(" : ")?;
+    Some(SquadEvent::ChatMessage {
+        channel,
+        player: bracketed_player(player)?,
+        message: message.to_string(),
+    })
+}
+
+fn parse_camera(text: &str) -> Option<SquadEvent> {
+    let (player, possessed) = match text.strip_suffix(" has possessed admin camera.") {
+        Some(player) => (player, true),
+        None => (text.strip_suffix(" has unpossessed admin camera.")?, false),
+    };
+    Some(SquadEvent::AdminCamera {
+        player: bracketed_player(player)?,
+        possessed,
+    })
+}
+
+fn parse_warned(text: &str) -> Option<SquadEvent> {
+    let rest = text.strip_prefix("Remote admin has warned player ")?;
+    let (name, message) = rest.split_once(". Message was \"")?;
+    Some(SquadEvent::PlayerWarned {
+        name: name.to_string(),
+        message: message.strip_suffix('"').unwrap_or(message).to_string(),
+    })
+}
+
+/// Splits `3. [ids] name` into the player ID and the player.
+fn numbered_player(text: &str) -> Option<(u32, EventPlayer)> {
+    let (player_id, player) = text.split_once(". ")?;
+    Some((player_id.parse().ok()?, bracketed_player(player)?))
+}
+
+fn parse_kicked(text: &str) -> Option<SquadEvent> {
+    let (player_id, player) = numbered_player(text.strip_prefix("Kicked player ")?)?;
+    Some(SquadEvent::PlayerKicked { player_id, player })
+}
+
+fn parse_banned(text: &str) -> Option<SquadEvent> {
+    let (player, interval) = text
+        .strip_prefix("Banned player ")?
+        .rsplit_once(" for interval ")?;
+    let (player_id, player) = numbered_player(player)?;
+    Some(SquadEvent::PlayerBanned {
+        player_id,
+        player,
+        interval: interval.to_string(),
+    })
+}
+
+fn parse_squad_created(text: &str) -> Option<SquadEvent> {
+    let (player, squad) = text.split_once(") has created Squad ")?;
+    let (name, ids) = player.rsplit_once(" (")?;
+    let (squad_id, rest) = squad.split_once(" (Squad Name: ")?;
+    let (squad_name, team) = rest.rsplit_once(") on ")?;
+    Some(SquadEvent::SquadCreated {
+        player: EventPlayer {
+            name: name.to_string(),
+            ids: PlayerIds::parse(ids),
+        },
+        squad_id: squad_id.parse().ok()?,
+        squad_name: squad_name.to_string(),
+        team: team.to_string(),
+    })
+}
+
+impl SquadEvent {
+    /// Parses the body of a pushed packet, messages that are not understood become [`SquadEvent::Other`].
+    ///
+    /// The formats come from the community tools that parse these messages, the tests use hand-written samples rather than captured pushes, see the [`crate::games`] module.
+    pub fn parse(body: &str) -> SquadEvent {
+        let text = body.trim();
+        [
+            parse_chat,
+            parse_camera,
+            parse_warned,
+            parse_kicked,
+            parse_banned,
+            parse_squad_created,
+        ]
+        .iter()
+        .find_map(|parse| parse(text))
+        .unwrap_or_else(|| SquadEvent::Other(text.to_string()))
+    }
+
+    /// Parses a pushed packet, returns `None` for packets that are not pushes, such as responses to commands.
+    pub fn from_packet(packet: &Packet) -> Option<SquadEvent> {
+        if packet.get_type() != PacketType::Raw(1) {
+            return None;
+        }
+        Some(SquadEvent::parse(&packet.get_body()))
+    }
+}
+
+/// Reads the events pushed on a connection, see the [module documentation](self).
+/// Responses to commands are skipped, so no commands should be sent on the same connection.
+#[derive(Debug)]
+pub struct Events<R> {
+    reader: R,
+}
+
+impl<R: Read> Events<R> {
+    /// Creates a new instance of `Events` that reads from an authenticated connection.
+    pub fn new(reader: R) -> Events<R> {
+        Events { reader }
+    }
+}
+
+impl<R: Read> Iterator for Events<R> {
+    type Item = Result<SquadEvent, Error>;
+
+    /// Waits for the next event, returns `None` once the server closed the connection.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match Packet::read_from(&mut self.reader) {
+                Ok(packet) => {
+                    if let Some(event) = SquadEvent::from_packet(&packet) {
+                        return Some(Ok(event));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Splits a `Key: Value | Key: Value` line into its fields.
+fn fields(line: &str) -> impl Iterator<Item = (&str, &str)> {
+    line.split(" | ")
+        .filter_map(|field| field.split_once(": "))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+fn optional_number(value: &str) -> Option<u32> {
+    value.parse().ok()
+}
+
+fn invalid_line(line: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid line {line:?}"))
+}
+
+/// A connected player in the response to `ListPlayers`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Player {
+    /// The ID of the player in this match, used by `AdminKickById`.
+    pub id: u32,
+    /// The IDs of the player.
+    pub ids: PlayerIds,
+    /// The name of the player, including any clan tag.
+    pub name: String,
+    /// The team, 1 or 2.
+    pub team_id: Option<u32>,
+    /// The squad within the team, `None` if the player is not in a squad.
+    pub squad_id: Option<u32>,
+    /// Whether the player leads their squad.
+    pub is_leader: bool,
+    /// The kit of the player, such as `USA_Rifleman_01`.
+    pub role: String,
+}
+
+/// A recently disconnected player in the response to `ListPlayers`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DisconnectedPlayer {
+    /// The ID the player had in this match.
+    pub id: u32,
+    /// The IDs of the player.
+    pub ids: PlayerIds,
+    /// The name of the player.
+    pub name: String,
+    /// How long ago the player disconnected, such as `02m.30s`.
+    pub since_disconnect: String,
+}
+
+/// The response to `ListPlayers`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlayerList {
+    /// The connected players.
+    pub active: Vec<Player>,
+    /// The players that disconnected recently.
+    pub disconnected: Vec<DisconnectedPlayer>,
+}
+
+impl FromStr for PlayerList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut list = PlayerList::default();
+        let mut disconnected = false;
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with("-----") {
+                disconnected = line.contains("Disconnected");
+                continue;
+            }
+            let mut player = Player::default();
+            let mut since_disconnect = String::new();
+            let mut has_id = false;
+            for (key, value) in fields(line) {
+                match key {
+                    "ID" => {
+                        player.id = value.parse().map_err(|_| invalid_line(line))?;
+                        has_id = true;
+                    }
+                    "SteamID" | "Online IDs" => player.ids = PlayerIds::parse(value),
+                    "Name" => player.name = value.to_string(),
+                    "Team ID" => player.team_id = optional_number(value),
+                    "Squad ID" => player.squad_id = optional_number(value),
+                    "Is Leader" => player.is_leader = value == "True",
+                    "Role" => player.role = value.to_string(),
+                    "Since Disconnect" => since_disconnect = value.to_string(),
+                    _ => {}
+                }
+            }
+            if !has_id {
+                return Err(invalid_line(line));
+            }
+            if disconnected {
+                list.disconnected.push(DisconnectedPlayer {
+                    id: player.id,
+                    ids: player.ids,
+                    name: player.name,
+                    since_disconnect,
+                });
+            } else {
+                list.active.push(player);
+            }
+        }
+        Ok(list)
+    }
+}
+
+/// A squad in the response to `ListSquads`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SquadInfo {
+    /// The team, 1 or 2.
+    pub team_id: u32,
+    /// The faction of the team, such as `United States Army`.
+    pub team_name: String,
+    /// The ID of the squad within its team.
+    pub id: u32,
+    /// The name of the squad.
+    pub name: String,
+    /// How many players are in the squad.
+    pub size: u32,
+    /// Whether the squad is locked.
+    pub locked: bool,
+    /// The name of the player that created the squad.
+    pub creator_name: String,
+    /// The IDs of the player that created the squad.
+    pub creator_ids: PlayerIds,
+}
+
+/// Parses the response to `ListSquads`, squads are listed under a `Team ID: 1 (United States Army)` line for their team.
+pub fn parse_squads(response: &str) -> Result<Vec<SquadInfo>, Error> {
+    let mut squads = Vec::new();
+    let mut team = (0, String::new());
+    for line in response
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+    {
+        if let Some(rest) = line.strip_prefix("Team ID: ") {
+            let (id, name) = rest.split_once(" (").ok_or_else(|| invalid_line(line))?;
+            let id = id.parse().map_err(|_| invalid_line(line))?;
+            team = (id, name.trim_end_matches(')').to_string());
+            continue;
+        }
+        let mut squad = SquadInfo {
+            team_id: team.0,
+            team_name: team.1.clone(),
+            ..SquadInfo::default()
+        };
+        for (key, value) in fields(line) {
+            match key {
+                "ID" => squad.id = value.parse().map_err(|_| invalid_line(line))?,
+                "Name" => squad.name = value.to_string(),
+                "Size" => squad.size = value.parse().map_err(|_| invalid_line(line))?,
+                "Locked" => squad.locked = value == "True",
+                "Creator Name" => squad.creator_name = value.to_string(),
+                "Creator Steam ID" | "Creator Online IDs" => {
+                    squad.creator_ids = PlayerIds::parse(value)
+                }
+                _ => {}
+            }
+        }
+        squads.push(squad);
+    }
+    Ok(squads)
+}
+
+/// Typed wrappers for the commands of a Squad server, see the [module documentation](self).
+#[derive(Debug)]
+pub struct Squad<S> {
+    service: S,
+}
+
+impl<S: CommandService> Squad<S> {
+    /// Creates a new instance of the `Squad` wrapper.
+    pub fn new(service: S) -> Squad<S> {
+        Squad { service }
+    }
+
+    /// Gets the wrapped service back, to send commands that have no wrapper.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+
+    /// Runs `ListPlayers`.
+    pub fn list_players(&mut self) -> Result<PlayerList, Error> {
+        self.service.call("ListPlayers".to_string())?.parse()
+    }
+
+    /// Runs `ListSquads`.
+    pub fn list_squads(&mut self) -> Result<Vec<SquadInfo>, Error> {
+        parse_squads(&self.service.call("ListSquads".to_string())?)
+    }
+
+    /// Shows a message to every player with `AdminBroadcast`.
+    pub fn broadcast(&mut self, message: &str) -> Result<String, Error> {
+        if message.chars().any(char::is_control) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid message {message:?}"),
+            ));
+        }
+        self.service.call(format!("AdminBroadcast {message}"))
+    }
+
+    /// Warns a player, given by name or ID, with `AdminWarn`.
+    pub fn warn(&mut self, player: &str, message: &str) -> Result<String, Error> {
+        let player = quoted(player, "player")?;
+        let message = quoted(message, "message")?;
+        self.service.call(format!("AdminWarn {player} {message}"))
+    }
+
+    /// Kicks a player by their ID in this match, see [`Player::id`].
+    pub fn kick_by_id(&mut self, id: u32, reason: &str) -> Result<String, Error> {
+        let reason = quoted(reason, "reason")?;
+        self.service.call(format!("AdminKickById {id} {reason}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::packet_id::ID;
+
+    fn alice() -> EventPlayer {
+        EventPlayer {
+            name: "Alice".to_string(),
+            ids: PlayerIds {
+                steam_id: Some("76561198000000001".to_string()),
+                eos_id: None,
+            },
+        }
+    }
+
+    fn bob() -> EventPlayer {
+        EventPlayer {
+            name: "[TAG] Bob".to_string(),
+            ids: PlayerIds {
+                steam_id: Some("76561198000000002".to_string()),
+                eos_id: Some("0002a1b2c3d4e5f60718293a4b5c6d7e".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn parse_sample_events() {
+        let events: Vec<SquadEvent> = include_str!("samples/squad_events.txt")
+            .lines()
+            .map(SquadEvent::parse)
+            .collect();
+        assert_eq!(
+            events[0],
+            SquadEvent::ChatMessage {
+                channel: ChatChannel::All,
+                player: alice(),
+                message: "gg wp".to_string()
+            }
+        );
+        assert_eq!(
+            events[1],
+            SquadEvent::ChatMessage {
+                channel: ChatChannel::Team,
+                player: bob(),
+                message: "need a medic: now".to_string()
+            }
+        );
+        assert!(matches!(
+            &events[2],
+            SquadEvent::ChatMessage {
+                channel: ChatChannel::Admin,
+                ..
+            }
+        ));
+        assert_eq!(
+            events[3],
+            SquadEvent::AdminCamera {
+                player: alice(),
+                possessed: true
+            }
+        );
+        assert_eq!(
+            events[4],
+            SquadEvent::AdminCamera {
+                player: bob(),
+                possessed: false
+            }
+        );
+        assert_eq!(
+            events[5],
+            SquadEvent::PlayerWarned {
+                name: "[TAG] Bob".to_string(),
+                message: "Stop teamkilling".to_string()
+            }
+        );
+        assert_eq!(
+            events[6],
+            SquadEvent::PlayerKicked {
+                player_id: 3,
+                player: bob()
+            }
+        );
+        let SquadEvent::PlayerBanned {
+            player_id: 3,
+            interval,
+            ..
+        } = &events[7]
+        else {
+            panic!("{:?}", events[7]);
+        };
+        assert_eq!(interval, "1d");
+        let SquadEvent::SquadCreated {
+            player,
+            squad_id: 1,
+            squad_name,
+            team,
+        } = &events[8]
+        else {
+            panic!("{:?}", events[8]);
+        };
+        assert_eq!(player.name, "Alice");
+        assert_eq!(
+            (squad_name.as_str(), team.as_str()),
+            ("Alpha", "United States Army")
+        );
+        assert!(matches!(
+            &events[9],
+            SquadEvent::SquadCreated { squad_id: 2, .. }
+        ));
+        assert_eq!(
+            events[10],
+            SquadEvent::Other("Server is restarting in 60 seconds".to_string())
+        );
+    }
+
+    #[test]
+    fn events_skip_responses() {
+        let mut stream = Vec::new();
+        for (pkt_type, body) in [
+            (
+                PacketType::Raw(1),
+                "[ChatAll] [SteamID:76561198000000001] Alice : hi",
+            ),
+            (PacketType::ResponseValue, "----- Active Players -----"),
+            (
+                PacketType::Raw(1),
+                "[SteamID:76561198000000001] Alice has possessed admin camera.",
+            ),
+        ] {
+            let packet = Packet::new(pkt_type, body.to_string(), ID::from(0)).unwrap();
+            stream.extend(Vec::from(packet));
+        }
+        let events: Vec<SquadEvent> = Events::new(&stream[..]).map(Result::unwrap).collect();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[1],
+            SquadEvent::AdminCamera {
+                possessed: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_lists() {
+        let players: PlayerList = include_str!("samples/squad_listplayers.txt")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            players.active[0],
+            Player {
+                id: 0,
+                ids: alice().ids,
+                name: "Alice".to_string(),
+                team_id: Some(1),
+                squad_id: Some(1),
+                is_leader: true,
+                role: "USA_SL_01".to_string(),
+            }
+        );
+        assert_eq!(players.active[1].ids, bob().ids);
+        assert_eq!(players.active[1].squad_id, None);
+        assert_eq!(players.disconnected[0].name, "Carol");
+        assert_eq!(players.disconnected[0].since_disconnect, "02m.30s");
+
+        let squads = parse_squads(include_str!("samples/squad_listsquads.txt")).unwrap();
+        assert_eq!(squads.len(), 2);
+        assert_eq!(squads[0].team_name, "United States Army");
+        assert_eq!(squads[0].creator_ids, alice().ids);
+        assert_eq!(
+            (
+                squads[1].team_id,
+                squads[1].name.as_str(),
+                squads[1].size,
+                squads[1].locked
+            ),
+            (2, "Squad 1", 2, true)
+        );
+        assert_eq!(squads[1].creator_ids, bob().ids);
+    }
+}
@@ -0,0 +1,523 @@
+//! Contains the implementation for [`Gateway`], an HTTP/JSON gateway built with [axum](https://docs.rs/axum) that runs commands on pooled [`AsyncRCONClient`] connections.
+//!
+//! # Protocol
+//! `POST /servers/{name}/command` with the header `Authorization: Bearer <token>` and the body `{"command": "status"}`.
+//!
+//! On success the status is `200 OK` and the body is
+//! ```json
+//! {"server": "survival", "command": "status", "response": "...", "elapsed_ms": 12.5}
+//! ```
+//! On failure the body is
+//! ```json
+//! {"error": {"kind": "timeout", "message": "..."}, "elapsed_ms": 5000.0}
+//! ```
+//! where the kind is one of the [`GatewayErrorKind`]s, which also decides the status code.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use ya_rcon::gateway::{Gateway, ServerConfig};
+//!
+//! let gateway = Gateway::new()
+//!     .with_token("secret-token".to_string())
+//!     .with_server(
+//!         "survival".to_string(),
+//!         ServerConfig::new("127.0.0.1:25575".to_string(), "password".to_string()),
+//!     );
+//! gateway.serve(tokio::net::TcpListener::bind("0.0.0.0:8080").await?).await
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use tokio::{net::TcpStream, sync::Semaphore};
+
+use crate::{client_async::AsyncRCONClient, SimpleIDGenerator, TextEncoding};
+
+type Client = AsyncRCONClient<TcpStream, SimpleIDGenerator>;
+
+/// How to reach one game server, see [`Gateway::with_server()`].
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub(crate) address: String,
+    pub(crate) password: String,
+    max_connections: usize,
+    pub(crate) timeout: Duration,
+    pub(crate) encoding: TextEncoding,
+}
+
+impl ServerConfig {
+    /// Creates a configuration with at most 4 connections, a timeout of 10 seconds and UTF-8 packet bodies.
+    pub fn new(address: String, password: String) -> ServerConfig {
+        ServerConfig {
+            address,
+            password,
+            max_connections: 4,
+            timeout: Duration::from_secs(10),
+            encoding: TextEncoding::default(),
+        }
+    }
+
+    /// Sets the [`TextEncoding`] of the packet bodies, for servers that do not use UTF-8.
+    pub fn with_encoding(mut self, encoding: TextEncoding) -> ServerConfig {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets how many connections to the server may be open at the same time, further requests wait for a free connection.
+    pub fn with_max_connections(mut self, max_connections: usize) -> ServerConfig {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// Sets how long a request may take, including waiting for a connection and connecting.
+    pub fn with_timeout(mut self, timeout: Duration) -> ServerConfig {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// The connections to one server. Idle connections are reused, a connection that failed is dropped.
+#[derive(Debug)]
+struct Pool {
+    config: ServerConfig,
+    idle: Mutex<Vec<Client>>,
+    permits: Semaphore,
+}
+
+impl Pool {
+    fn new(config: ServerConfig) -> Pool {
+        Pool {
+            permits: Semaphore::new(config.max_connections),
+            idle: Mutex::new(Vec::new()),
+            config,
+        }
+    }
+
+    async fn execute(&self, command: String) -> Result<String, GatewayError> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        let idle = self.idle.lock().expect("pool poisoned").pop();
+        let (client, response) = match idle {
+            Some(mut client) => match client.send_command(command.clone()).await {
+                // The server may have closed the idle connection, for example because it restarted, try once more on a new one.
+                Err(e) if is_connection_lost(&e) => {
+                    let mut client = self.connect().await?;
+                    let response = client.send_command(command).await;
+                    (client, response)
+                }
+                response => (client, response),
+            },
+            None => {
+                let mut client = self.connect().await?;
+                let response = client.send_command(command).await;
+                (client, response)
+            }
+        };
+        let response = response?;
+        self.idle.lock().expect("pool poisoned").push(client);
+        Ok(response)
+    }
+
+    async fn connect(&self) -> Result<Client, Error> {
+        let stream = TcpStream::connect(self.config.address.as_str()).await?;
+        AsyncRCONClient::with_encoding(
+            stream,
+            SimpleIDGenerator::new(),
+            self.config.password.clone(),
+            self.config.encoding,
+        )
+        .await
+    }
+}
+
+/// Whether the error means the connection is gone, rather than that the server rejected the command.
+fn is_connection_lost(error: &Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe | ErrorKind::ConnectionReset
+    )
+}
+
+/// The classification of a failed request, serialized in snake case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GatewayErrorKind {
+    /// The bearer token is missing or unknown, `401 Unauthorized`.
+    Unauthorized,
+    /// There is no server with that name, `404 Not Found`.
+    UnknownServer,
+    /// The request body is not a valid command, or the command can not be sent over RCON (for example because it is too long), `400 Bad Request`.
+    BadRequest,
+    /// The gateway could not connect to the server, `502 Bad Gateway`.
+    Connection,
+    /// The server rejected the RCON password, `502 Bad Gateway`.
+    AuthFailed,
+    /// The server sent a response that does not match the command, `502 Bad Gateway`.
+    Protocol,
+    /// The server did not respond in time, `504 Gateway Timeout`.
+    Timeout,
+}
+
+impl GatewayErrorKind {
+    fn status(self) -> StatusCode {
+        match self {
+            GatewayErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            GatewayErrorKind::UnknownServer => StatusCode::NOT_FOUND,
+            GatewayErrorKind::BadRequest => StatusCode::BAD_REQUEST,
+            GatewayErrorKind::Connection
+            | GatewayErrorKind::AuthFailed
+            | GatewayErrorKind::Protocol => StatusCode::BAD_GATEWAY,
+            GatewayErrorKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// The error of a failed request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct GatewayError {
+    kind: GatewayErrorKind,
+    message: String,
+}
+
+impl GatewayError {
+    pub(crate) fn new(kind: GatewayErrorKind, message: impl Into<String>) -> GatewayError {
+        GatewayError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Gets the classification of the error.
+    pub fn get_kind(&self) -> GatewayErrorKind {
+        self.kind
+    }
+
+    /// Gets the description of the error.
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<Error> for GatewayError {
+    fn from(value: Error) -> Self {
+        let kind = match value.kind() {
+            ErrorKind::PermissionDenied => GatewayErrorKind::AuthFailed,
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => GatewayErrorKind::Timeout,
+            ErrorKind::InvalidInput => GatewayErrorKind::BadRequest,
+            ErrorKind::InvalidData => GatewayErrorKind::Protocol,
+            _ => GatewayErrorKind::Connection,
+        };
+        GatewayError::new(kind, value.to_string())
+    }
+}
+
+/// The body of a command request.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CommandRequest {
+    /// The command to run.
+    pub command: String,
+}
+
+/// The body of a successful command response.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommandResponse {
+    /// The name of the server the command ran on.
+    pub server: String,
+    /// The command.
+    pub command: String,
+    /// The response of the server.
+    pub response: String,
+    /// How long the request took in milliseconds.
+    pub elapsed_ms: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ErrorResponse {
+    error: GatewayError,
+    elapsed_ms: f64,
+}
+
+/// An HTTP gateway to a set of named servers, see the [module documentation](self) for the protocol.
+#[derive(Debug, Clone, Default)]
+pub struct Gateway {
+    servers: HashMap<String, Arc<Pool>>,
+    tokens: Vec<String>,
+}
+
+impl Gateway {
+    /// Creates a gateway without servers or tokens. A gateway without tokens rejects every request.
+    pub fn new() -> Gateway {
+        Gateway::default()
+    }
+
+    /// Adds a server that is reachable as `/servers/{name}`.
+    pub fn with_server(mut self, name: String, config: ServerConfig) -> Gateway {
+        self.servers.insert(name, Arc::new(Pool::new(config)));
+        self
+    }
+
+    /// Adds a bearer token that is allowed to run commands.
+    pub fn with_token(mut self, token: String) -> Gateway {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Runs the command on the named server, without going through HTTP.
+    pub async fn execute(&self, server: &str, command: String) -> Result<String, GatewayError> {
+        let pool = self.servers.get(server).ok_or_else(|| {
+            GatewayError::new(
+                GatewayErrorKind::UnknownServer,
+                format!("Unknown server {server:?}"),
+            )
+        })?;
+        tokio::time::timeout(pool.config.timeout, pool.execute(command))
+            .await
+            .unwrap_or_else(|_| {
+                Err(GatewayError::new(
+                    GatewayErrorKind::Timeout,
+                    "The server did not respond in time",
+                ))
+            })
+    }
+
+    /// Creates the axum [`Router`], so the gateway can be nested in a larger application.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/servers/{name}/command", post(run_command))
+            .with_state(Arc::new(self))
+    }
+
+    /// Serves the gateway on the listener until it fails.
+    pub async fn serve(self, listener: tokio::net::TcpListener) -> Result<(), Error> {
+        axum::serve(listener, self.router()).await
+    }
+}
+
+async fn run_command(
+    State(gateway): State<Arc<Gateway>>,
+    Path(server): Path<String>,
+    headers: HeaderMap,
+    body: Result<Json<CommandRequest>, axum::extract::rejection::JsonRejection>,
+) -> Response {
+    let start = Instant::now();
+    let result = async {
+        if !is_authorized(&gateway.tokens, &headers, None) {
+            return Err(GatewayError::new(
+                GatewayErrorKind::Unauthorized,
+                "Missing or unknown bearer token",
+            ));
+        }
+        let Json(request) =
+            body.map_err(|e| GatewayError::new(GatewayErrorKind::BadRequest, e.body_text()))?;
+        let response = gateway.execute(&server, request.command.clone()).await?;
+        Ok((request.command, response))
+    }
+    .await;
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match result {
+        Ok((command, response)) => Json(CommandResponse {
+            server,
+            command,
+            response,
+            elapsed_ms,
+        })
+        .into_response(),
+        Err(error) => (
+            error.kind.status(),
+            Json(ErrorResponse { error, elapsed_ms }),
+        )
+            .into_response(),
+    }
+}
+
+/// Checks the bearer token in the `Authorization` header, or the token passed in the query string for clients that can not set headers (such as browsers opening a WebSocket).
+pub(crate) fn is_authorized(
+    tokens: &[String],
+    headers: &HeaderMap,
+    query_token: Option<&str>,
+) -> bool {
+    let header_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(token) = header_token.or(query_token) else {
+        return false;
+    };
+    tokens
+        .iter()
+        .any(|known| constant_time_eq(known.as_bytes(), token.as_bytes()))
+}
+
+/// Compares the tokens without returning early, so the time taken does not reveal how much of a token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::server::{serve_connection, PasswordHandler};
+
+    fn start_simulator() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let handler =
+                    PasswordHandler::new("secret".to_string(), |cmd: &str| format!("ran {cmd}"));
+                std::thread::spawn(move || serve_connection(stream.unwrap(), handler));
+            }
+        });
+        address
+    }
+
+    /// Starts a simulator and returns the server side of its connections, shutting them down is like restarting the server.
+    fn start_restartable_simulator() -> (String, Arc<Mutex<Vec<std::net::TcpStream>>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                accepted.lock().unwrap().push(stream.try_clone().unwrap());
+                let handler =
+                    PasswordHandler::new("secret".to_string(), |cmd: &str| format!("ran {cmd}"));
+                std::thread::spawn(move || serve_connection(stream, handler));
+            }
+        });
+        (address, connections)
+    }
+
+    async fn post(address: &str, path: &str, token: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio_macros::test]
+    async fn gateway_against_simulator() {
+        let gateway = Gateway::new()
+            .with_token("token".to_string())
+            .with_server(
+                "local".to_string(),
+                ServerConfig::new(start_simulator(), "secret".to_string()),
+            )
+            .with_server(
+                "wrong-password".to_string(),
+                ServerConfig::new(start_simulator(), "nope".to_string()),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(gateway.serve(listener));
+
+        let body = r#"{"command": "status"}"#;
+        let ok = post(&address, "/servers/local/command", "token", body).await;
+        assert!(ok.starts_with("HTTP/1.1 200"), "{ok}");
+        assert!(ok.contains(r#""response":"ran status""#), "{ok}");
+        assert!(ok.contains(r#""elapsed_ms":"#), "{ok}");
+
+        let unauthorized = post(&address, "/servers/local/command", "guess", body).await;
+        assert!(unauthorized.starts_with("HTTP/1.1 401"), "{unauthorized}");
+        assert!(unauthorized.contains(r#""kind":"unauthorized""#));
+
+        let unknown = post(&address, "/servers/other/command", "token", body).await;
+        assert!(unknown.contains(r#""kind":"unknown_server""#), "{unknown}");
+
+        let denied = post(&address, "/servers/wrong-password/command", "token", body).await;
+        assert!(denied.starts_with("HTTP/1.1 502"), "{denied}");
+        assert!(denied.contains(r#""kind":"auth_failed""#), "{denied}");
+
+        let bad = post(&address, "/servers/local/command", "token", "{}").await;
+        assert!(bad.contains(r#""kind":"bad_request""#), "{bad}");
+
+        let too_long = format!(r#"{{"command": "{}"}}"#, "x".repeat(5000));
+        let too_long = post(&address, "/servers/local/command", "token", &too_long).await;
+        assert!(too_long.starts_with("HTTP/1.1 400"), "{too_long}");
+    }
+
+    #[tokio_macros::test]
+    async fn commands_use_the_configured_encoding() {
+        use crate::packet::{Packet, PacketType};
+        use crate::server::accept_auth;
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_auth(&mut stream, |password| (password == "secret").then_some(())).unwrap();
+            let packet = Packet::read_from(&mut stream).unwrap();
+            assert_eq!(packet.get_body_bytes(), b"say caf\xe9");
+            let response = Packet::new_encoded(
+                PacketType::ResponseValue,
+                "sent caf\u{e9}",
+                packet.get_id(),
+                TextEncoding::Windows1252,
+            );
+            stream.write_all(&Vec::from(response.unwrap())).unwrap();
+            let _ = Packet::read_from(&mut stream);
+        });
+        let gateway = Gateway::new().with_server(
+            "local".to_string(),
+            ServerConfig::new(address, "secret".to_string())
+                .with_encoding(TextEncoding::Windows1252),
+        );
+        assert_eq!(
+            gateway
+                .execute("local", "say caf\u{e9}".to_string())
+                .await
+                .unwrap(),
+            "sent caf\u{e9}"
+        );
+    }
+
+    #[tokio_macros::test]
+    async fn idle_connections_are_replaced_after_a_restart() {
+        let (address, connections) = start_restartable_simulator();
+        let gateway = Gateway::new().with_server(
+            "local".to_string(),
+            ServerConfig::new(address, "secret".to_string()),
+        );
+        assert_eq!(
+            gateway.execute("local", "first".to_string()).await.unwrap(),
+            "ran first"
+        );
+
+        for connection in connections.lock().unwrap().drain(..) {
+            connection.shutdown(std::net::Shutdown::Both).unwrap();
+        }
+        assert_eq!(
+            gateway
+                .execute("local", "second".to_string())
+                .await
+                .unwrap(),
+            "ran second"
+        );
+        assert_eq!(connections.lock().unwrap().len(), 1);
+    }
+}
@@ -5,14 +5,47 @@
 use std::net::TcpStream;
 
 pub use client::RCONClient;
+pub use command::Command;
+pub use encoding::TextEncoding;
 pub use id_generator::SimpleIDGenerator;
 pub use packet::Packet;
 
+#[cfg(feature = "broadcast")]
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+pub mod broadcast;
+pub mod chunk;
 pub mod client;
 #[cfg(any(feature = "tokio", feature = "async-net"))]
 pub mod client_async;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod command;
+#[cfg(feature = "queue")]
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+pub mod command_queue;
+mod correlation;
+pub mod encoding;
+pub mod games;
+#[cfg(feature = "gateway")]
+pub mod gateway;
 pub mod id_generator;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod packet;
+#[cfg(feature = "policy")]
+pub mod policy;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod rate_limit;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod service;
+mod trace;
+pub mod transcript;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 /// A simple RCON client using the [`TcpStream`] from the standard library.
 ///
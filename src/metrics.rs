@@ -0,0 +1,567 @@
+//! Contains the implementation for [`Exporter`], which periodically runs RCON commands and exposes the values parsed from the responses in the Prometheus text format.
+//!
+//! # Example
+//! ```no_run
+//! use std::{net::{TcpListener, TcpStream}, time::Duration};
+//! use ya_rcon::metrics::{Exporter, Metric, ValueParser};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let exporter = Exporter::new(vec![
+//!     Metric::new("minecraft_players_online".to_string(), "list".to_string(), ValueParser::MinecraftList)
+//!         .unwrap()
+//!         .with_help("Players currently online".to_string()),
+//! ])
+//! .unwrap()
+//! .with_label("server".to_string(), "survival".to_string())
+//! .unwrap();
+//!
+//! let collector = exporter.clone();
+//! std::thread::spawn(move || {
+//!     collector.run(
+//!         || RCONClient::new(TcpStream::connect("127.0.0.1:25575")?, SimpleIDGenerator::new(), "password".to_string()),
+//!         Duration::from_secs(15),
+//!     )
+//! });
+//! exporter.serve(TcpListener::bind("0.0.0.0:9100").unwrap()).unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    io::{BufRead, BufReader, Error, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+
+use crate::service::CommandService;
+
+/// How a numeric value is taken from a command response.
+#[derive(Debug, Clone)]
+pub enum ValueParser {
+    /// The first number in the response.
+    FirstNumber,
+    /// The first capture group of the regex, or the whole match if it has no groups.
+    Regex(Regex),
+    /// The number of players online from the response to the Minecraft `list` command, `There are 3 of a max of 20 players online: ...`.
+    MinecraftList,
+    /// The TPS over the last minute from the response to the `tps` command of Spigot and Paper, `TPS from last 1m, 5m, 15m: 20.0, 19.98, 19.97`.
+    Tps,
+    /// A column of the response to the Source `stats` command, which is a header line followed by a line of values, for example `Players` or `FPS`.
+    SourceStats(String),
+    /// A custom parser.
+    Custom(fn(&str) -> Option<f64>),
+}
+
+impl ValueParser {
+    /// Takes the value from the response, returns `None` if the response does not contain it.
+    pub fn parse(&self, response: &str) -> Option<f64> {
+        match self {
+            ValueParser::FirstNumber => numbers(response).next(),
+            ValueParser::Regex(regex) => {
+                let captures = regex.captures(response)?;
+                let value = captures.get(1).or_else(|| captures.get(0))?;
+                value.as_str().trim().parse().ok()
+            }
+            ValueParser::MinecraftList => {
+                let (_, rest) = response.split_once("There are ")?;
+                numbers(rest).next()
+            }
+            ValueParser::Tps => {
+                let (_, rest) = response.split_once(':')?;
+                numbers(rest).next()
+            }
+            ValueParser::SourceStats(column) => {
+                let mut lines = response.lines().filter(|line| !line.trim().is_empty());
+                let header = lines.next()?;
+                let index = header
+                    .split_whitespace()
+                    .position(|name| name.eq_ignore_ascii_case(column))?;
+                lines.next()?.split_whitespace().nth(index)?.parse().ok()
+            }
+            ValueParser::Custom(parse) => parse(response),
+        }
+    }
+}
+
+/// The numbers in the text, ignoring any formatting codes such as `§6` around them.
+/// A leading `-` is kept as the sign, dots around a number and dashes after it are punctuation.
+fn numbers(text: &str) -> impl Iterator<Item = f64> + '_ {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter_map(|word| {
+            word.trim_start_matches('.')
+                .trim_end_matches(['.', '-'])
+                .parse()
+                .ok()
+        })
+}
+
+impl FromStr for ValueParser {
+    type Err = Error;
+
+    /// Parses `number`, `minecraft-list`, `tps`, `source-stats:<column>` or `regex:<pattern>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "number" => Ok(ValueParser::FirstNumber),
+            None if s == "minecraft-list" => Ok(ValueParser::MinecraftList),
+            None if s == "tps" => Ok(ValueParser::Tps),
+            Some(("source-stats", column)) => Ok(ValueParser::SourceStats(column.to_string())),
+            Some(("regex", pattern)) => Regex::new(pattern)
+                .map(ValueParser::Regex)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e)),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown value parser {s:?}"),
+            )),
+        }
+    }
+}
+
+/// A gauge whose value is parsed from the response to a command.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    name: String,
+    help: String,
+    command: String,
+    parser: ValueParser,
+}
+
+impl Metric {
+    /// Creates a new instance of the `Metric`.
+    /// Fails with [`ErrorKind::InvalidInput`] if the name is not a valid Prometheus metric name, `[a-zA-Z_:][a-zA-Z0-9_:]*`.
+    pub fn new(name: String, command: String, parser: ValueParser) -> Result<Metric, Error> {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        if !valid {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid metric name {name:?}"),
+            ));
+        }
+        Ok(Metric {
+            name,
+            help: String::new(),
+            command,
+            parser,
+        })
+    }
+
+    /// Sets the description shown in the `# HELP` line.
+    pub fn with_help(mut self, help: String) -> Metric {
+        self.help = help;
+        self
+    }
+
+    /// Gets the name of the metric.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the command the value is parsed from.
+    pub fn get_command(&self) -> &str {
+        &self.command
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    values: Vec<Option<f64>>,
+    up: bool,
+    duration: f64,
+    errors: u64,
+}
+
+/// Runs the commands of its [`Metric`]s and serves the values on `/metrics`. Cloning an exporter shares its state, so one clone can collect while another serves.
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    metrics: Arc<[Metric]>,
+    labels: Vec<(String, String)>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Exporter {
+    /// Creates a new instance of the `Exporter`.
+    /// Fails with [`ErrorKind::InvalidInput`] if two metrics have the same name or a metric uses a name of the exporter itself, `rcon_up` or `rcon_collect_*`.
+    pub fn new(metrics: Vec<Metric>) -> Result<Exporter, Error> {
+        let mut names = HashSet::new();
+        for metric in &metrics {
+            if metric.name == "rcon_up" || metric.name.starts_with("rcon_collect_") {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("The metric name {:?} is used by the exporter", metric.name),
+                ));
+            }
+            if !names.insert(metric.name.as_str()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Duplicate metric name {:?}", metric.name),
+                ));
+            }
+        }
+        Ok(Exporter {
+            metrics: metrics.into(),
+            labels: Vec::new(),
+            state: Arc::default(),
+        })
+    }
+
+    /// Adds a label to every sample, for example the name of the server.
+    /// Fails with [`ErrorKind::InvalidInput`] if the name is not a valid Prometheus label name, `[a-zA-Z_][a-zA-Z0-9_]*` not starting with `__`, or the label was already added.
+    pub fn with_label(mut self, name: String, value: String) -> Result<Exporter, Error> {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !name.starts_with("__");
+        if !valid {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid label name {name:?}"),
+            ));
+        }
+        if self.labels.iter().any(|(known, _)| *known == name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Duplicate label name {name:?}"),
+            ));
+        }
+        self.labels.push((name, value));
+        Ok(self)
+    }
+
+    /// Runs every command once (metrics with the same command share the response) and stores the parsed values.
+    /// If a command fails the values are cleared, `rcon_up` becomes 0 and the error is returned.
+    pub fn collect<S: CommandService>(&self, service: &mut S) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut responses: HashMap<&str, String> = HashMap::new();
+        let mut result = Ok(());
+        for metric in self.metrics.iter() {
+            if responses.contains_key(metric.command.as_str()) {
+                continue;
+            }
+            match service.call(metric.command.clone()) {
+                Ok(response) => {
+                    responses.insert(&metric.command, response);
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let mut state = self.state.lock().expect("metrics state poisoned");
+        state.duration = start.elapsed().as_secs_f64();
+        state.up = result.is_ok();
+        if result.is_err() {
+            state.errors += 1;
+            responses.clear();
+        }
+        state.values = self
+            .metrics
+            .iter()
+            .map(|metric| {
+                let response = responses.get(metric.command.as_str())?;
+                metric.parser.parse(response)
+            })
+            .collect();
+        result
+    }
+
+    /// Collects every `interval` forever. `connect` is called to get a connection at the start and again after every failed collection.
+    pub fn run<S, F>(&self, mut connect: F, interval: Duration) -> !
+    where
+        S: CommandService,
+        F: FnMut() -> Result<S, Error>,
+    {
+        let mut service = None;
+        loop {
+            let next = Instant::now() + interval;
+            if service.is_none() {
+                match connect() {
+                    Ok(connected) => service = Some(connected),
+                    Err(_) => self.mark_down(),
+                }
+            }
+            if let Some(connected) = &mut service {
+                if self.collect(connected).is_err() {
+                    service = None;
+                }
+            }
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    fn mark_down(&self) {
+        let mut state = self.state.lock().expect("metrics state poisoned");
+        state.up = false;
+        state.errors += 1;
+        state.values.clear();
+    }
+
+    /// Renders the last collected values in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().expect("metrics state poisoned");
+        let labels = self.render_labels();
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, value: f64| {
+            if !help.is_empty() {
+                let _ = writeln!(out, "# HELP {name} {}", escape_help(help));
+            }
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            let _ = writeln!(out, "{name}{labels} {value}");
+        };
+        gauge(
+            "rcon_up",
+            "Whether the last collection succeeded",
+            f64::from(u8::from(state.up)),
+        );
+        gauge(
+            "rcon_collect_duration_seconds",
+            "How long the last collection took",
+            state.duration,
+        );
+        for (metric, value) in self.metrics.iter().zip(&state.values) {
+            if let Some(value) = value {
+                gauge(&metric.name, &metric.help, *value);
+            }
+        }
+        let _ = writeln!(
+            out,
+            "# HELP rcon_collect_errors_total Collections that failed\n# TYPE rcon_collect_errors_total counter\nrcon_collect_errors_total{labels} {}",
+            state.errors
+        );
+        out
+    }
+
+    fn render_labels(&self) -> String {
+        if self.labels.is_empty() {
+            return String::new();
+        }
+        let labels = self
+            .labels
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+            .collect::<Vec<_>>();
+        format!("{{{}}}", labels.join(","))
+    }
+
+    /// Serves `GET /metrics` on every connection to the listener, one request per connection.
+    /// Every connection is handled on its own thread, so a slow scraper does not hold up the others.
+    pub fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let exporter = self.clone();
+            // A misbehaving scraper should not stop the exporter.
+            std::thread::spawn(move || exporter.handle_request(stream));
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, mut stream: TcpStream) -> Result<(), Error> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", self.render()),
+            (Some("GET"), Some(_)) => ("404 Not Found", "Not Found\n".to_string()),
+            _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        stream.flush()
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    escape_help(value).replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_parsers() {
+        let list = "There are 3 of a max of 20 players online: alice, bob, carol";
+        assert_eq!(ValueParser::MinecraftList.parse(list), Some(3.0));
+        let tps = "§6TPS from last 1m, 5m, 15m: §a19.5, §a20.0, §a20.0";
+        assert_eq!(ValueParser::Tps.parse(tps), Some(19.5));
+        let stats =
+            "CPU   In_(KB/s)  Out_(KB/s)  Uptime  Map_changes  FPS      Players  Connects\n\
+                     12.0  1.50       3.25        42      2            128.00   7        31\n";
+        let players = ValueParser::SourceStats("Players".to_string());
+        assert_eq!(players.parse(stats), Some(7.0));
+        let regex: ValueParser = "regex:entities: (\\d+)".parse().unwrap();
+        assert_eq!(regex.parse("loaded entities: 1234"), Some(1234.0));
+        assert_eq!(ValueParser::FirstNumber.parse("no numbers"), None);
+        assert_eq!(ValueParser::FirstNumber.parse("offset -5"), Some(-5.0));
+        assert_eq!(ValueParser::FirstNumber.parse("temp: -2.5."), Some(-2.5));
+        assert_eq!(ValueParser::FirstNumber.parse("players 3-"), Some(3.0));
+    }
+
+    #[test]
+    fn metric_names() {
+        for name in ["players", "_players", "game:players_online", "tps_1m"] {
+            assert!(Metric::new(
+                name.to_string(),
+                "list".to_string(),
+                ValueParser::FirstNumber
+            )
+            .is_ok());
+        }
+        for name in [
+            "",
+            "1m_tps",
+            "players-online",
+            "players online",
+            "spieler_ä",
+        ] {
+            assert_eq!(
+                Metric::new(
+                    name.to_string(),
+                    "list".to_string(),
+                    ValueParser::FirstNumber
+                )
+                .unwrap_err()
+                .kind(),
+                ErrorKind::InvalidInput,
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn exporter_names() {
+        let metric = |name: &str| {
+            Metric::new(
+                name.to_string(),
+                "list".to_string(),
+                ValueParser::FirstNumber,
+            )
+            .unwrap()
+        };
+        assert!(Exporter::new(vec![metric("players"), metric("tps")]).is_ok());
+        for metrics in [
+            vec![metric("players"), metric("players")],
+            vec![metric("rcon_up")],
+            vec![metric("rcon_collect_errors_total")],
+        ] {
+            assert_eq!(
+                Exporter::new(metrics).unwrap_err().kind(),
+                ErrorKind::InvalidInput
+            );
+        }
+
+        let exporter = Exporter::new(vec![metric("players")]).unwrap();
+        let exporter = exporter
+            .with_label("server".to_string(), "a".to_string())
+            .unwrap()
+            .with_label("_region2".to_string(), "b".to_string())
+            .unwrap();
+        for name in [
+            "",
+            "2server",
+            "server-name",
+            "game:server",
+            "__name__",
+            "server",
+        ] {
+            assert_eq!(
+                exporter
+                    .clone()
+                    .with_label(name.to_string(), "c".to_string())
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::InvalidInput,
+                "{name:?}"
+            );
+        }
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn exporter_against_simulator() {
+        use crate::server::{serve_connection, PasswordHandler};
+        use crate::{RCONClient, SimpleIDGenerator};
+        use std::io::Read;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let handler = PasswordHandler::new("secret".to_string(), |cmd: &str| match cmd {
+                "list" => "There are 2 of a max of 10 players online: alice, bob".to_string(),
+                _ => "Unknown command".to_string(),
+            });
+            serve_connection(listener.accept().unwrap().0, handler)
+        });
+
+        let exporter = Exporter::new(vec![
+            Metric::new(
+                "players_online".to_string(),
+                "list".to_string(),
+                ValueParser::MinecraftList,
+            )
+            .unwrap()
+            .with_help("Players online".to_string()),
+            Metric::new(
+                "players_max".to_string(),
+                "list".to_string(),
+                "regex:max of (\\d+)".parse().unwrap(),
+            )
+            .unwrap(),
+            Metric::new("tps".to_string(), "tps".to_string(), ValueParser::Tps).unwrap(),
+        ])
+        .unwrap()
+        .with_label("server".to_string(), "test \"one\"".to_string())
+        .unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let mut client =
+            RCONClient::new(stream, SimpleIDGenerator::new(), "secret".to_string()).unwrap();
+        exporter.collect(&mut client).unwrap();
+
+        let http = TcpListener::bind("127.0.0.1:0").unwrap();
+        let http_address = http.local_addr().unwrap();
+        let server = exporter.clone();
+        std::thread::spawn(move || server.serve(http));
+        // A scraper that never sends its request does not hold up the others.
+        let _stalled = TcpStream::connect(http_address).unwrap();
+        let mut scrape = TcpStream::connect(http_address).unwrap();
+        scrape
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        scrape
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        scrape.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("# HELP players_online Players online\n"));
+        assert!(response.contains("players_online{server=\"test \\\"one\\\"\"} 2\n"));
+        assert!(response.contains("players_max{server=\"test \\\"one\\\"\"} 10\n"));
+        assert!(response.contains("rcon_up{server=\"test \\\"one\\\"\"} 1\n"));
+        assert!(!response.contains("tps{"));
+    }
+}
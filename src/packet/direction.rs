@@ -0,0 +1,11 @@
+//! Contains the implementation for [`Direction`]
+
+/// Which way a packet travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// A packet sent from the client to the server, such as [`crate::packet::PacketType::Auth`] or [`crate::packet::PacketType::ExecCommand`].
+    ClientToServer,
+    /// A packet sent from the server to the client, such as [`crate::packet::PacketType::AuthResponse`] or [`crate::packet::PacketType::ResponseValue`].
+    ServerToClient,
+}
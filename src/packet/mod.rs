@@ -1,43 +1,77 @@
 //! Contains the implementation for [`Packet`]
 
+pub mod direction;
 pub mod packet_error;
 pub mod packet_id;
+pub mod packet_kind;
+pub mod packet_ref;
+#[cfg(feature = "serde")]
+mod packet_serde;
 pub mod packet_type;
 
+use std::io::{Read, Write};
+
 use packet_id::ID;
+use packet_kind::{PacketKind, SessionState};
+
+use crate::encoding::TextEncoding;
 
-pub use crate::packet::{packet_error::PacketError, packet_type::PacketType};
+pub use crate::packet::{direction::Direction, packet_error::PacketError, packet_type::PacketType};
 
 /// The minimum packet size (in bytes) for an RCON packet.
 /// Citation: <https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#Packet_Size>
 pub const MIN_PACKET_SIZE: usize = 10;
 /// The max packet size is 4096 not including the size field of 4 bytes.
 pub const MAX_PACKET_SIZE: usize = 4096 + 4;
-/// The max size (in bytes) of a payload that can be sent.
-pub const MAX_PAYLOAD_SIZE: usize = MAX_PACKET_SIZE - MIN_PACKET_SIZE;
+/// The max size (in bytes) of a payload that can be sent, so that the whole packet including the size field fits in [`MAX_PACKET_SIZE`].
+/// That is 4096 minus the ID, type and two null bytes, 4086 bytes.
+pub const MAX_PAYLOAD_SIZE: usize = MAX_PACKET_SIZE - 4 - MIN_PACKET_SIZE;
+/// The size (in bytes) of the size, ID and type fields that come before the body.
+const HEADER_SIZE: usize = 12;
 
 /// Used to construct a RCON packet.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
     /// The packet size field is a 32-bit little endian integer, representing the length of the request in bytes. Note that the packet size field itself is not included when determining the size of the packet, so the value of this field is always 4 less than the packet's actual length. The minimum possible value for packet size is 10:
     size: i32,
     /// The packet id field is a 32-bit little endian integer chosen by the client for each request. It may be set to any positive integer. When the server responds to the request, the response packet will have the same packet id as the original request (unless it is a failed SERVERDATA_AUTH_RESPONSE packet - see below.) It need not be unique, but if a unique packet id is assigned, it can be used to match incoming responses to their corresponding requests.
     id: ID,
     pkt_type: PacketType,
-    body: String,
+    /// The raw bytes of the body, without the null terminator. See [`TextEncoding`] for how it is turned into a `String`.
+    body: Vec<u8>,
 }
 
 impl Packet {
     /// Creates a new packet with the given parameters, checks the body/payload length and calculates the size field of the packet.
     pub fn new(pkt_type: PacketType, body: String, id: ID) -> Result<Packet, PacketError> {
-        if body.len() >= MAX_PAYLOAD_SIZE {
+        Packet::new_bytes(pkt_type, body.into_bytes(), id)
+    }
+
+    /// Same as [`Packet::new()`], but the body is encoded with the given [`TextEncoding`] instead of UTF-8.
+    pub fn new_encoded(
+        pkt_type: PacketType,
+        body: &str,
+        id: ID,
+        encoding: TextEncoding,
+    ) -> Result<Packet, PacketError> {
+        Packet::new_bytes(pkt_type, encoding.encode(body)?.into_owned(), id)
+    }
+
+    /// Same as [`Packet::new()`], but takes the already encoded bytes of the body.
+    pub fn new_bytes(pkt_type: PacketType, body: Vec<u8>, id: ID) -> Result<Packet, PacketError> {
+        if body.len() > MAX_PAYLOAD_SIZE {
             return Err(PacketError::InvalidPayloadLength);
         }
         let size = (body.len() + MIN_PACKET_SIZE)
             .try_into()
             .expect("Earlier asertion should garentee this to pass");
 
-        Ok(Packet::new_raw(pkt_type, body, size, id))
+        Ok(Packet {
+            size,
+            id,
+            pkt_type,
+            body,
+        })
     }
 
     /// Creates a new packet with the given parameters but with no checks, Allows creating for an invalid packet.
@@ -46,8 +80,66 @@ impl Packet {
             size,
             id,
             pkt_type,
-            body,
+            body: body.into_bytes(),
+        }
+    }
+
+    /// Parses a packet whose body is encoded with the given [`TextEncoding`].
+    /// The [`TryFrom<&[u8]>`](#impl-TryFrom%3C%26%5Bu8%5D%3E-for-Packet) implementation is the same as calling this with [`TextEncoding::Utf8`].
+    pub fn try_from_encoded(value: &[u8], encoding: TextEncoding) -> Result<Packet, PacketError> {
+        if value.len() < HEADER_SIZE + 2 {
+            return Err(PacketError::ParseError);
+        }
+        Packet::parse(value, value.len() - 2, encoding)
+    }
+
+    /// Same as [`Packet::try_from_encoded()`], but the two null bytes that end the body may be missing, some servers (Palworld) leave them out.
+    /// Only use it for those servers, up to two null bytes at the end of a body are taken as terminators even if they belong to the body.
+    pub fn try_from_lenient(value: &[u8], encoding: TextEncoding) -> Result<Packet, PacketError> {
+        if value.len() < HEADER_SIZE {
+            return Err(PacketError::ParseError);
         }
+        let terminators = value[HEADER_SIZE..]
+            .iter()
+            .rev()
+            .take(2)
+            .take_while(|&&byte| byte == 0)
+            .count();
+        Packet::parse(value, value.len() - terminators, encoding)
+    }
+
+    fn parse(value: &[u8], body_end: usize, encoding: TextEncoding) -> Result<Packet, PacketError> {
+        let size = i32::from_le_bytes(value[0..4].try_into().expect("slice with incorrect length"));
+        let id =
+            i32::from_le_bytes(value[4..8].try_into().expect("slice with incorrect length")).into();
+        let pkt_type = PacketType::from(i32::from_le_bytes(
+            value[8..12]
+                .try_into()
+                .expect("slice with incorrect length"),
+        ));
+        let body = value[HEADER_SIZE..body_end].to_vec();
+        encoding.decode(&body)?;
+
+        Ok(Packet {
+            size,
+            id,
+            pkt_type,
+            body,
+        })
+    }
+
+    /// Reads exactly one packet from the reader, using the size field to know how many bytes to read.
+    /// Unlike [`TryFrom<&[u8]>`](#impl-TryFrom%3C%26%5Bu8%5D%3E-for-Packet) this works when a packet is split across reads or several packets arrive at once. The body is not checked against any [`TextEncoding`].
+    pub fn read_from<R: Read>(mut reader: R) -> std::io::Result<Packet> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        reader.read_exact(&mut buf[..4])?;
+        let size = i32::from_le_bytes(buf[..4].try_into().expect("slice with incorrect length"));
+        let frame_len = match usize::try_from(size) {
+            Ok(size) if (MIN_PACKET_SIZE..=MAX_PACKET_SIZE - 4).contains(&size) => size + 4,
+            _ => return Err(PacketError::InvalidPayloadLength.into()),
+        };
+        reader.read_exact(&mut buf[4..frame_len])?;
+        Ok(packet_ref::PacketRef::parse(&buf[..frame_len])?.to_packet())
     }
 
     /// Gets the ID of the packet.
@@ -55,28 +147,101 @@ impl Packet {
         self.id
     }
 
-    /// Gets the packet body and performs a `clone()` to return it.
+    /// Gets the packet body as UTF-8 and performs a `clone()` to return it. Invalid UTF-8 is replaced with `U+FFFD`, use [`Packet::decode_body()`] for other encodings.
     pub fn get_body(&self) -> String {
-        self.body.clone()
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Gets the raw bytes of the packet body.
+    pub fn get_body_bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Gets the packet body as UTF-8 without copying it.
+    pub fn get_body_str(&self) -> Result<&str, PacketError> {
+        std::str::from_utf8(&self.body).map_err(|_| PacketError::InvalidPacketBody)
+    }
+
+    /// Decodes the packet body with the given [`TextEncoding`].
+    pub fn decode_body(&self, encoding: TextEncoding) -> Result<String, PacketError> {
+        Ok(encoding.decode(&self.body)?.into_owned())
+    }
+
+    /// The number of bytes the packet takes up once encoded, including the size field.
+    pub fn encoded_len(&self) -> usize {
+        HEADER_SIZE + self.body.len() + 2
+    }
+
+    /// Encodes the packet into the start of `buf` without allocating and returns the number of bytes written.
+    /// Returns [`PacketError::BufferTooSmall`] if `buf` is shorter than [`Packet::encoded_len()`].
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        encode_parts(self.size, self.id, self.pkt_type, &self.body, buf)
+    }
+
+    /// Writes the encoded packet to the given writer without allocating.
+    pub fn write_to<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        write_parts(self.size, self.id, self.pkt_type, &self.body, writer)
+    }
+
+    /// Appends the encoded packet to the given [`bytes::BufMut`]. Like the other `BufMut` methods this panics if `buf` does not have [`Packet::encoded_len()`] bytes of space left.
+    #[cfg(feature = "bytes")]
+    pub fn put_into<B: bytes::BufMut>(&self, buf: &mut B) {
+        buf.put_i32_le(self.size);
+        buf.put_i32_le(i32::from(self.id));
+        buf.put_i32_le(i32::from(self.pkt_type));
+        buf.put_slice(&self.body);
+        buf.put_slice(&[0, 0]);
     }
 
     /// Gets the [`PacketType`] of the packet.
     pub fn get_type(&self) -> PacketType {
         self.pkt_type
     }
+
+    /// Gets the unambiguous [`PacketKind`] of the packet, see [`PacketKind::decode()`].
+    pub fn get_kind(&self, direction: Direction, state: SessionState) -> PacketKind {
+        PacketKind::decode(self.pkt_type, direction, state)
+    }
+}
+
+fn encode_parts(
+    size: i32,
+    id: ID,
+    pkt_type: PacketType,
+    body: &[u8],
+    buf: &mut [u8],
+) -> Result<usize, PacketError> {
+    let len = HEADER_SIZE + body.len() + 2;
+    let buf = buf.get_mut(..len).ok_or(PacketError::BufferTooSmall)?;
+    buf[0..4].copy_from_slice(&size.to_le_bytes());
+    buf[4..8].copy_from_slice(&i32::from(id).to_le_bytes());
+    buf[8..12].copy_from_slice(&i32::from(pkt_type).to_le_bytes());
+    buf[HEADER_SIZE..len - 2].copy_from_slice(body);
+    buf[len - 2..].copy_from_slice(&[0, 0]);
+    Ok(len)
+}
+
+fn write_parts<W: Write>(
+    size: i32,
+    id: ID,
+    pkt_type: PacketType,
+    body: &[u8],
+    mut writer: W,
+) -> std::io::Result<()> {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(&size.to_le_bytes());
+    header[4..8].copy_from_slice(&i32::from(id).to_le_bytes());
+    header[8..12].copy_from_slice(&i32::from(pkt_type).to_le_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(body)?;
+    writer.write_all(&[0, 0])
 }
 
 impl From<Packet> for Vec<u8> {
     fn from(val: Packet) -> Self {
-        // MIN_PACKET_SIZE + 2 for the 2 null bytes at the end.
-        let mut output_vec = Vec::with_capacity(
-            usize::try_from(val.size).expect("Invalid packet size") + MIN_PACKET_SIZE + 2,
-        );
-        output_vec.extend(val.size.to_le_bytes());
-        output_vec.extend(i32::from(val.id).to_le_bytes());
-        output_vec.extend(i32::from(val.pkt_type).to_le_bytes());
-        output_vec.extend(val.body.as_bytes());
-        output_vec.extend([0u8, 0]);
+        let mut output_vec = vec![0u8; val.encoded_len()];
+        val.encode_into(&mut output_vec)
+            .expect("Buffer was created with the encoded length");
         output_vec
     }
 }
@@ -84,18 +249,7 @@ impl From<Packet> for Vec<u8> {
 impl TryFrom<&[u8]> for Packet {
     type Error = PacketError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let body_end = value.len() - 2;
-        let size = i32::from_le_bytes(value[0..4].try_into().expect("slice with incorrect length"));
-        let id =
-            i32::from_le_bytes(value[4..8].try_into().expect("slice with incorrect length")).into();
-        let pkt_type = PacketType::from(i32::from_le_bytes(
-            value[8..12]
-                .try_into()
-                .expect("slice with incorrect length"),
-        ));
-        let body = String::from_utf8(value[12..body_end].to_vec())?;
-
-        Ok(Packet::new_raw(pkt_type, body, size, id))
+        Packet::try_from_encoded(value, TextEncoding::Utf8)
     }
 }
 
@@ -140,4 +294,110 @@ mod tests {
 
         assert_eq!(expected_packet, pkt);
     }
+
+    #[test]
+    fn test_encoded_packet() {
+        let pkt = Packet::new_encoded(
+            PacketType::ExecCommand,
+            "say caf\u{E9}",
+            ID::from(1),
+            TextEncoding::Windows1252,
+        )
+        .unwrap();
+        assert_eq!(pkt.get_body_bytes(), b"say caf\xE9");
+        assert_eq!(pkt.size, 8 + MIN_PACKET_SIZE as i32);
+
+        let bytes = Vec::from(pkt);
+        assert!(Packet::try_from(&bytes[..]).is_err());
+        let parsed = Packet::try_from_encoded(&bytes[..], TextEncoding::Windows1252).unwrap();
+        assert_eq!(
+            parsed.decode_body(TextEncoding::Windows1252).unwrap(),
+            "say caf\u{E9}"
+        );
+    }
+
+    #[test]
+    fn test_max_payload_fits_max_packet() {
+        let body = "a".repeat(MAX_PAYLOAD_SIZE);
+        let pkt = Packet::new(PacketType::ExecCommand, body, ID::from(1)).unwrap();
+        assert_eq!(pkt.encoded_len(), MAX_PACKET_SIZE);
+        let body = "a".repeat(MAX_PAYLOAD_SIZE + 1);
+        assert!(Packet::new(PacketType::ExecCommand, body, ID::from(1)).is_err());
+    }
+
+    #[test]
+    fn test_max_payload_boundary() {
+        assert_eq!(MAX_PAYLOAD_SIZE, 4086);
+        let bytes = Vec::from(
+            Packet::new_bytes(PacketType::Raw(2), vec![b'a'; 4086], ID::from(7)).unwrap(),
+        );
+        assert_eq!(bytes.len(), 4100);
+        assert_eq!(bytes[..4], 4096i32.to_le_bytes());
+        let pkt = Packet::read_from(&bytes[..]).unwrap();
+        assert_eq!(pkt.get_body_bytes().len(), 4086);
+        assert!(Packet::new_bytes(PacketType::Raw(2), vec![b'a'; 4087], ID::from(7)).is_err());
+
+        // One byte more than the limit can not be read either.
+        let mut too_long = bytes;
+        too_long[..4].copy_from_slice(&4097i32.to_le_bytes());
+        too_long.insert(16, b'a');
+        assert!(Packet::read_from(&too_long[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_from_stream() {
+        let mut stream = Vec::from(
+            Packet::new(PacketType::ResponseValue, String::from("one"), ID::from(1)).unwrap(),
+        );
+        stream.extend(Vec::from(
+            Packet::new(PacketType::ResponseValue, String::from("two"), ID::from(2)).unwrap(),
+        ));
+        let mut reader = &stream[..];
+        assert_eq!(Packet::read_from(&mut reader).unwrap().get_body(), "one");
+        assert_eq!(Packet::read_from(&mut reader).unwrap().get_body(), "two");
+        assert!(Packet::read_from(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_encode_into_buffer() {
+        let pkt = Packet::new(PacketType::Auth, String::from("password"), ID::from(0)).unwrap();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let len = pkt.encode_into(&mut buf).unwrap();
+        assert_eq!(len, pkt.encoded_len());
+
+        let mut written = Vec::new();
+        pkt.write_to(&mut written).unwrap();
+        assert_eq!(&buf[..len], &written[..]);
+
+        assert!(matches!(
+            pkt.encode_into(&mut buf[..len - 1]),
+            Err(PacketError::BufferTooSmall)
+        ));
+        assert_eq!(&written[..], &Vec::from(pkt)[..]);
+    }
+
+    #[test]
+    fn test_missing_terminators() {
+        let mut bytes = Vec::from(
+            Packet::new(
+                PacketType::ResponseValue,
+                String::from("Saved"),
+                ID::from(3),
+            )
+            .unwrap(),
+        );
+        bytes.truncate(bytes.len() - 2);
+        let pkt = Packet::try_from_lenient(&bytes[..], TextEncoding::Utf8).unwrap();
+        assert_eq!(pkt.get_body(), "Saved");
+        assert_eq!(pkt.get_id(), ID::from(3));
+        // The strict parser takes the last two bytes as the terminators.
+        assert_eq!(Packet::try_from(&bytes[..]).unwrap().get_body(), "Sav");
+    }
+
+    #[test]
+    fn test_binary_body_keeps_trailing_nulls() {
+        let pkt = Packet::new_bytes(PacketType::Raw(5), vec![1, 0, 0], ID::from(4)).unwrap();
+        let parsed = Packet::try_from_encoded(&Vec::from(pkt.clone()), TextEncoding::Windows1252);
+        assert_eq!(parsed.unwrap().get_body_bytes(), pkt.get_body_bytes());
+    }
 }
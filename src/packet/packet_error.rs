@@ -8,12 +8,14 @@ use std::{
 /// The errors that can happen when working with a [`crate::Packet`]
 #[derive(Debug, Clone, Copy)]
 pub enum PacketError {
-    /// Turns out I have not actually used this anywhere.
+    /// Used when there are not enough bytes to parse a whole packet.
     ParseError,
-    /// Used if the packet is not valid ASCII/UTF8
+    /// Used if the packet body is not valid in the [`crate::encoding::TextEncoding`] being used (UTF8 by default)
     InvalidPacketBody,
     /// The String payload exceded the [`crate::packet::MAX_PAYLOAD_SIZE`]
     InvalidPayloadLength,
+    /// The buffer given to [`crate::Packet::encode_into()`] is too small for the packet.
+    BufferTooSmall,
     /// I am using this primarily in [`crate::RCONClient`]. Maybe remove it from here and put elsewhere?
     /// Used when comparing the ID of the recieved packed to the ID used when sending a packet.
     UnexpectedID,
@@ -40,6 +42,9 @@ impl From<PacketError> for Error {
             PacketError::InvalidPayloadLength => {
                 Error::new(ErrorKind::InvalidInput, "Invalid RCON payload length")
             }
+            PacketError::BufferTooSmall => {
+                Error::new(ErrorKind::InvalidInput, "Buffer too small for RCON packet")
+            }
             PacketError::UnexpectedID => {
                 Error::new(ErrorKind::InvalidData, "Unexpected ID for RCON packet")
             }
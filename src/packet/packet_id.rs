@@ -1,7 +1,12 @@
 //! Contains definition for a packet ID
 
 /// This struct is about explicitly stating how the ID is handled see `from_wrapping` for more info
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct ID(i32);
 
 impl ID {
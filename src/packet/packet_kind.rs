@@ -0,0 +1,159 @@
+//! Contains the implementation for [`PacketKind`]
+
+use crate::packet::{Direction, PacketType};
+
+/// The state of the session, used to work out what an incoming packet means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SessionState {
+    /// An [`ClientPacket::Auth`] packet was sent and the server has not answered it yet.
+    AuthPending,
+    /// The session is authenticated (or at least no authentication is in flight).
+    Authenticated,
+}
+
+/// The packets a client sends to a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClientPacket {
+    /// [SERVERDATA_AUTH](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#SERVERDATA_AUTH), value 3.
+    Auth,
+    /// [SERVERDATA_EXECCOMMAND](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#SERVERDATA_EXECCOMMAND), value 2.
+    ExecCommand,
+    /// Any other value, some games use their own types.
+    Other(i32),
+}
+
+/// The packets a server sends to a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ServerPacket {
+    /// [SERVERDATA_AUTH_RESPONSE](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#SERVERDATA_AUTH_RESPONSE), value 2. Only valid while [`SessionState::AuthPending`].
+    AuthResponse,
+    /// [SERVERDATA_RESPONSE_VALUE](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#SERVERDATA_RESPONSE_VALUE), value 0.
+    ResponseValue,
+    /// Any other value, or a value 2 packet while no authentication is pending. Some games use their own types for unsolicited messages.
+    Other(i32),
+}
+
+/// An unambiguous packet type, decoded from the raw value with [`PacketKind::decode()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PacketKind {
+    /// A packet sent from client to server.
+    Client(ClientPacket),
+    /// A packet sent from server to client.
+    Server(ServerPacket),
+}
+
+impl PacketKind {
+    /// Works out what a packet type means from the direction it travelled and the state of the session.
+    ///
+    /// # Example
+    /// ```
+    /// use ya_rcon::packet::{packet_kind::*, Direction, PacketType};
+    /// let kind = PacketKind::decode(
+    ///     PacketType::from(2),
+    ///     Direction::ServerToClient,
+    ///     SessionState::AuthPending,
+    /// );
+    /// assert_eq!(kind, PacketKind::Server(ServerPacket::AuthResponse));
+    /// ```
+    pub fn decode(pkt_type: PacketType, direction: Direction, state: SessionState) -> PacketKind {
+        match direction {
+            Direction::ClientToServer => PacketKind::Client(match i32::from(pkt_type) {
+                3 => ClientPacket::Auth,
+                2 => ClientPacket::ExecCommand,
+                x => ClientPacket::Other(x),
+            }),
+            Direction::ServerToClient => PacketKind::Server(match (i32::from(pkt_type), state) {
+                (2, SessionState::AuthPending) => ServerPacket::AuthResponse,
+                (0, _) => ServerPacket::ResponseValue,
+                (x, _) => ServerPacket::Other(x),
+            }),
+        }
+    }
+
+    /// The direction the packet travelled.
+    pub fn direction(&self) -> Direction {
+        match self {
+            PacketKind::Client(_) => Direction::ClientToServer,
+            PacketKind::Server(_) => Direction::ServerToClient,
+        }
+    }
+}
+
+impl From<ClientPacket> for PacketType {
+    fn from(value: ClientPacket) -> Self {
+        match value {
+            ClientPacket::Auth => PacketType::Auth,
+            ClientPacket::ExecCommand => PacketType::ExecCommand,
+            ClientPacket::Other(x) => PacketType::Raw(x),
+        }
+    }
+}
+
+impl From<ServerPacket> for PacketType {
+    fn from(value: ServerPacket) -> Self {
+        match value {
+            ServerPacket::AuthResponse => PacketType::AuthResponse,
+            ServerPacket::ResponseValue => PacketType::ResponseValue,
+            ServerPacket::Other(x) => PacketType::Raw(x),
+        }
+    }
+}
+
+impl From<PacketKind> for PacketType {
+    fn from(value: PacketKind) -> Self {
+        match value {
+            PacketKind::Client(x) => x.into(),
+            PacketKind::Server(x) => x.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_two_depends_on_context() {
+        let two = PacketType::ExecOrAuthResp;
+        assert_eq!(
+            PacketKind::decode(two, Direction::ClientToServer, SessionState::Authenticated),
+            PacketKind::Client(ClientPacket::ExecCommand)
+        );
+        assert_eq!(
+            PacketKind::decode(two, Direction::ServerToClient, SessionState::AuthPending),
+            PacketKind::Server(ServerPacket::AuthResponse)
+        );
+        assert_eq!(
+            PacketKind::decode(two, Direction::ServerToClient, SessionState::Authenticated),
+            PacketKind::Server(ServerPacket::Other(2))
+        );
+        assert_ne!(
+            PacketKind::Server(ServerPacket::AuthResponse),
+            PacketKind::Client(ClientPacket::ExecCommand)
+        );
+    }
+
+    #[test]
+    fn other_values() {
+        assert_eq!(
+            PacketKind::decode(
+                PacketType::Raw(1),
+                Direction::ServerToClient,
+                SessionState::Authenticated
+            ),
+            PacketKind::Server(ServerPacket::Other(1))
+        );
+        assert_eq!(
+            PacketKind::decode(
+                PacketType::from(0),
+                Direction::ServerToClient,
+                SessionState::AuthPending
+            ),
+            PacketKind::Server(ServerPacket::ResponseValue)
+        );
+    }
+}
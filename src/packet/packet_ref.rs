@@ -0,0 +1,166 @@
+//! Contains the implementation for [`PacketRef`]
+
+use std::{borrow::Cow, io::Write};
+
+use crate::encoding::TextEncoding;
+use crate::packet::{
+    packet_id::ID,
+    packet_kind::{PacketKind, SessionState},
+    Direction, Packet, PacketError, PacketType, HEADER_SIZE, MAX_PACKET_SIZE, MIN_PACKET_SIZE,
+};
+
+/// A borrowed view of an RCON packet, parsed from a buffer without copying the body.
+///
+/// # Example
+/// ```
+/// use ya_rcon::packet::packet_ref::PacketRef;
+/// let raw = [
+///     0x0eu8, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x68, 0x69,
+///     0x21, 0x0a, 0x00, 0x00,
+/// ];
+/// let packet = PacketRef::parse(&raw).unwrap();
+/// assert_eq!(packet.get_body_str().unwrap(), "hi!\n");
+/// assert_eq!(packet.frame_len(), raw.len());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketRef<'a> {
+    size: i32,
+    id: ID,
+    pkt_type: PacketType,
+    body: &'a [u8],
+}
+
+impl<'a> PacketRef<'a> {
+    /// Parses the packet at the start of `buf`, the size field is used to find the end of the packet so `buf` may contain more data after it.
+    /// Returns [`PacketError::ParseError`] if `buf` does not contain the whole packet and [`PacketError::InvalidPayloadLength`] if the size field is out of range.
+    pub fn parse(buf: &'a [u8]) -> Result<PacketRef<'a>, PacketError> {
+        if buf.len() < 4 {
+            return Err(PacketError::ParseError);
+        }
+        let size = i32::from_le_bytes(buf[0..4].try_into().expect("slice with incorrect length"));
+        let frame_len = match usize::try_from(size) {
+            Ok(size) if (MIN_PACKET_SIZE..=MAX_PACKET_SIZE - 4).contains(&size) => size + 4,
+            _ => return Err(PacketError::InvalidPayloadLength),
+        };
+        if buf.len() < frame_len {
+            return Err(PacketError::ParseError);
+        }
+        let id = i32::from_le_bytes(buf[4..8].try_into().expect("slice with incorrect length"));
+        let pkt_type =
+            i32::from_le_bytes(buf[8..12].try_into().expect("slice with incorrect length"));
+
+        Ok(PacketRef {
+            size,
+            id: id.into(),
+            pkt_type: pkt_type.into(),
+            body: &buf[HEADER_SIZE..frame_len - 2],
+        })
+    }
+
+    /// Gets the ID of the packet.
+    pub fn get_id(&self) -> ID {
+        self.id
+    }
+
+    /// Gets the [`PacketType`] of the packet.
+    pub fn get_type(&self) -> PacketType {
+        self.pkt_type
+    }
+
+    /// Gets the unambiguous [`PacketKind`] of the packet, see [`PacketKind::decode()`].
+    pub fn get_kind(&self, direction: Direction, state: SessionState) -> PacketKind {
+        PacketKind::decode(self.pkt_type, direction, state)
+    }
+
+    /// Gets the size field of the packet.
+    pub fn get_size(&self) -> i32 {
+        self.size
+    }
+
+    /// The number of bytes this packet takes up in the buffer it was parsed from, including the size field.
+    pub fn frame_len(&self) -> usize {
+        HEADER_SIZE + self.body.len() + 2
+    }
+
+    /// Gets the raw bytes of the packet body.
+    pub fn get_body_bytes(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Gets the packet body as UTF-8 without copying it.
+    pub fn get_body_str(&self) -> Result<&'a str, PacketError> {
+        std::str::from_utf8(self.body).map_err(|_| PacketError::InvalidPacketBody)
+    }
+
+    /// Decodes the packet body with the given [`TextEncoding`], this only allocates if the encoding is not UTF-8.
+    pub fn decode_body(&self, encoding: TextEncoding) -> Result<Cow<'a, str>, PacketError> {
+        encoding.decode(self.body)
+    }
+
+    /// Encodes the packet into the start of `buf` and returns the number of bytes written. See [`Packet::encode_into()`].
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        super::encode_parts(self.size, self.id, self.pkt_type, self.body, buf)
+    }
+
+    /// Writes the packet to the given writer. See [`Packet::write_to()`].
+    pub fn write_to<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        super::write_parts(self.size, self.id, self.pkt_type, self.body, writer)
+    }
+
+    /// Copies the packet into an owned [`Packet`].
+    pub fn to_packet(&self) -> Packet {
+        Packet {
+            size: self.size,
+            id: self.id,
+            pkt_type: self.pkt_type,
+            body: self.body.to_vec(),
+        }
+    }
+}
+
+impl<'a> From<&'a Packet> for PacketRef<'a> {
+    fn from(value: &'a Packet) -> Self {
+        PacketRef {
+            size: value.size,
+            id: value.id,
+            pkt_type: value.pkt_type,
+            body: &value.body,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_back_to_back_packets() {
+        let mut buf = Vec::from(
+            Packet::new(PacketType::ResponseValue, "first".to_string(), ID::from(1)).unwrap(),
+        );
+        buf.extend(Vec::from(
+            Packet::new(PacketType::ResponseValue, "second".to_string(), ID::from(2)).unwrap(),
+        ));
+
+        let first = PacketRef::parse(&buf).unwrap();
+        assert_eq!(first.get_id(), ID::from(1));
+        assert_eq!(first.get_body_str().unwrap(), "first");
+        let second = PacketRef::parse(&buf[first.frame_len()..]).unwrap();
+        assert_eq!(second.get_body_str().unwrap(), "second");
+        assert_eq!(first.frame_len() + second.frame_len(), buf.len());
+    }
+
+    #[test]
+    fn parse_truncated_packet() {
+        let buf =
+            Vec::from(Packet::new(PacketType::Auth, "pass".to_string(), ID::from(1)).unwrap());
+        assert!(matches!(
+            PacketRef::parse(&buf[..buf.len() - 1]),
+            Err(PacketError::ParseError)
+        ));
+        assert!(matches!(
+            PacketRef::parse(&buf[..2]),
+            Err(PacketError::ParseError)
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+//! `serde` support for [`Packet`], the other packet types derive their implementations.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::packet::{packet_id::ID, Packet, PacketType};
+
+/// The body is written as a string when it is valid UTF-8 so it is readable in formats like JSON, otherwise as the raw bytes.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Body<'a> {
+    #[serde(borrow)]
+    Text(std::borrow::Cow<'a, str>),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct PacketRepr<'a> {
+    size: i32,
+    id: ID,
+    #[serde(rename = "type")]
+    pkt_type: PacketType,
+    #[serde(borrow)]
+    body: Body<'a>,
+}
+
+impl Serialize for Packet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let body = match std::str::from_utf8(&self.body) {
+            Ok(text) => Body::Text(text.into()),
+            Err(_) => Body::Bytes(self.body.clone()),
+        };
+        PacketRepr {
+            size: self.size,
+            id: self.id,
+            pkt_type: self.pkt_type,
+            body,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Packet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = PacketRepr::deserialize(deserializer)?;
+        let body = match repr.body {
+            Body::Text(text) => text.into_owned().into_bytes(),
+            Body::Bytes(bytes) => bytes,
+        };
+        Ok(Packet {
+            size: repr.size,
+            id: repr.id,
+            pkt_type: repr.pkt_type,
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_json_format() {
+        let pkt = Packet::new(PacketType::AuthResponse, String::from("ok"), ID::from(7)).unwrap();
+        let json = serde_json::to_string(&pkt).unwrap();
+        assert_eq!(
+            json,
+            r#"{"size":12,"id":7,"type":"AuthResponse","body":"ok"}"#
+        );
+        let parsed: Packet = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.get_type(), PacketType::AuthResponse);
+        assert!(matches!(parsed.get_type(), PacketType::AuthResponse));
+        assert_eq!(parsed, pkt);
+    }
+
+    #[test]
+    fn packet_type_names() {
+        for (pkt_type, name) in [
+            (PacketType::ExecCommand, r#""ExecCommand""#),
+            (PacketType::ExecOrAuthResp, r#""ExecOrAuthResp""#),
+            (PacketType::Raw(1), r#"{"Raw":1}"#),
+        ] {
+            assert_eq!(serde_json::to_string(&pkt_type).unwrap(), name);
+            let parsed: PacketType = serde_json::from_str(name).unwrap();
+            assert_eq!(format!("{parsed:?}"), format!("{pkt_type:?}"));
+        }
+    }
+}
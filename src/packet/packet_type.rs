@@ -3,7 +3,11 @@
 /// See <https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#Packet_Type> for more info about the types.
 /// The documentation for most of the types was taken directly from there.
 /// I have done this a little weirdly since the underlying value changed depending on context.
+/// Use [`crate::packet::packet_kind::PacketKind`] to tell the context dependent values apart.
+///
+/// With the `serde` feature the variants are serialized by name rather than by value, so [`PacketType::AuthResponse`], [`PacketType::ExecCommand`] and [`PacketType::ExecOrAuthResp`] stay distinct even though they all have the value 2.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PacketType {
     /// [SERVERDATA_RESPONSE_VALUE](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol#SERVERDATA_RESPONSE_VALUE): Server response to an [`PacketType::ExecCommand`]
     ResponseValue,
@@ -0,0 +1,504 @@
+//! Contains the implementation for [`Policy`], per-user access control, rate limits and an audit log for RCON commands.
+//!
+//! The protocol only has a single shared password, so the policy gives every user their own password and decides which commands they may run.
+//! It is used by [`crate::proxy::Proxy`], and [`Policy::execute()`] can wrap any client:
+//! ```no_run
+//! use std::net::TcpStream;
+//! use ya_rcon::policy::{Policy, Rule, UserPolicy};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let moderator = UserPolicy::new("moderator".to_string(), "hunter2".to_string())
+//!     .with_rule(Rule::allow("kick *"))
+//!     .with_rule(Rule::allow("ban *"));
+//! let policy = Policy::new(vec![moderator]);
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").unwrap();
+//! let mut client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let reply = policy.execute("moderator", "kick griefer", |cmd| client.send_command(cmd.to_string()));
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::rate_limit::TokenBucket;
+
+/// Whether a [`Rule`] allows or denies the commands it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    /// The command may be run.
+    Allow,
+    /// The command is rejected.
+    Deny,
+}
+
+/// A rule matching commands against a pattern. The pattern is matched case-insensitively against the whole statement, `*` matches any number of characters and `?` matches a single character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rule {
+    /// What to do with matching commands.
+    pub action: Action,
+    /// The pattern to match, for example `"kick *"` or `"rcon_password*"`.
+    pub pattern: String,
+}
+
+impl Rule {
+    /// A rule allowing the commands matching the pattern.
+    pub fn allow(pattern: &str) -> Rule {
+        Rule {
+            action: Action::Allow,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// A rule denying the commands matching the pattern.
+    pub fn deny(pattern: &str) -> Rule {
+        Rule {
+            action: Action::Deny,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// Checks if the statement matches the pattern of this rule.
+    /// Runs of whitespace count as a single space, like the tokenizer of the game server sees them.
+    pub fn matches(&self, statement: &str) -> bool {
+        glob_match(
+            normalize(&self.pattern).as_bytes(),
+            normalize(statement).as_bytes(),
+        )
+    }
+}
+
+/// The credentials, rules and rate limit of a single user.
+#[derive(Debug, Clone)]
+pub struct UserPolicy {
+    name: String,
+    password: String,
+    rules: Vec<Rule>,
+    default_action: Action,
+    rate_limit: Option<TokenBucket>,
+}
+
+impl UserPolicy {
+    /// Creates a new user that may not run any commands until rules are added with [`UserPolicy::with_rule()`].
+    pub fn new(name: String, password: String) -> UserPolicy {
+        UserPolicy {
+            name,
+            password,
+            rules: Vec::new(),
+            default_action: Action::Deny,
+            rate_limit: None,
+        }
+    }
+
+    /// Adds a rule. Rules are checked in the order they were added and the first matching rule decides.
+    pub fn with_rule(mut self, rule: Rule) -> UserPolicy {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Sets what happens to commands that match no rule, the default is [`Action::Deny`].
+    pub fn with_default_action(mut self, action: Action) -> UserPolicy {
+        self.default_action = action;
+        self
+    }
+
+    /// Limits how often the user may run commands.
+    pub fn with_rate_limit(mut self, bucket: TokenBucket) -> UserPolicy {
+        self.rate_limit = Some(bucket);
+        self
+    }
+
+    /// Gets the name of the user.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Decides what to do with a single statement.
+    pub fn action_for(&self, statement: &str) -> Action {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(statement))
+            .map_or(self.default_action, |rule| rule.action)
+    }
+}
+
+/// The reasons a command can be rejected by a [`Policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// There is no user with that name.
+    UnknownUser,
+    /// The statement is not allowed for the user.
+    Denied(String),
+    /// The user has run too many commands, the duration is how long until the next one is allowed.
+    RateLimited(Duration),
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::UnknownUser => write!(f, "Unknown RCON user"),
+            PolicyError::Denied(statement) => write!(f, "Command denied by policy: {statement}"),
+            PolicyError::RateLimited(wait) => write!(
+                f,
+                "Rate limited, retry in {:.1} seconds",
+                wait.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl From<PolicyError> for Error {
+    fn from(error: PolicyError) -> Error {
+        let kind = match error {
+            PolicyError::UnknownUser | PolicyError::Denied(_) => ErrorKind::PermissionDenied,
+            PolicyError::RateLimited(_) => ErrorKind::WouldBlock,
+        };
+        Error::new(kind, error.to_string())
+    }
+}
+
+/// What happened to an audited command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuditOutcome {
+    /// The command was run and the server responded with the body.
+    Executed(String),
+    /// The policy rejected the command, with the reason.
+    Rejected(String),
+    /// The command was allowed but running it failed, with the error.
+    Failed(String),
+}
+
+/// A single entry in an [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditRecord {
+    /// When the command finished.
+    pub timestamp: SystemTime,
+    /// The user that ran the command.
+    pub user: String,
+    /// The command as sent by the user.
+    pub command: String,
+    /// What happened.
+    pub outcome: AuditOutcome,
+}
+
+impl std::fmt::Display for AuditRecord {
+    /// Formats the record as a single tab separated line: timestamp in seconds since the epoch, user, outcome, command and response or reason. Strings are quoted and escaped so a record never spans multiple lines.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let (outcome, detail) = match &self.outcome {
+            AuditOutcome::Executed(response) => ("executed", response),
+            AuditOutcome::Rejected(reason) => ("rejected", reason),
+            AuditOutcome::Failed(error) => ("failed", error),
+        };
+        write!(
+            f,
+            "{timestamp:.3}\t{:?}\t{outcome}\t{:?}\t{detail:?}",
+            self.user, self.command
+        )
+    }
+}
+
+/// An append-only log of the commands run through a [`Policy`].
+pub trait AuditLog: Send + Sync {
+    /// Appends a record to the log.
+    fn record(&self, record: &AuditRecord);
+}
+
+/// An [`AuditLog`] writing one line per record (see the [`std::fmt::Display`] implementation of [`AuditRecord`]) to any writer.
+#[derive(Debug)]
+pub struct WriterAuditLog<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> WriterAuditLog<W> {
+    /// Creates a new instance of the `WriterAuditLog`.
+    pub fn new(writer: W) -> WriterAuditLog<W> {
+        WriterAuditLog {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Locks and gets the writer, for example to read back an in-memory log.
+    pub fn lock_writer(&self) -> MutexGuard<'_, W> {
+        self.writer.lock().expect("poisoned")
+    }
+
+    /// Gets the writer back.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().expect("poisoned")
+    }
+}
+
+impl WriterAuditLog<File> {
+    /// Opens the file in append mode, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<WriterAuditLog<File>, Error> {
+        Ok(WriterAuditLog::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        ))
+    }
+}
+
+impl<W: Write + Send> AuditLog for WriterAuditLog<W> {
+    fn record(&self, record: &AuditRecord) {
+        let mut writer = self.writer.lock().expect("poisoned");
+        // A failing audit log should not stop the commands, there is nowhere better to report it.
+        let _ = writeln!(writer, "{record}").and_then(|_| writer.flush());
+    }
+}
+
+/// A set of [`UserPolicy`]s with an optional [`AuditLog`]. The policy can be shared between threads.
+pub struct Policy {
+    users: Vec<UserPolicy>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    audit_log: Option<Arc<dyn AuditLog>>,
+}
+
+impl std::fmt::Debug for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Policy")
+            .field("users", &self.users)
+            .field("audit_log", &self.audit_log.is_some())
+            .finish()
+    }
+}
+
+impl Policy {
+    /// Creates a new policy for the given users.
+    pub fn new(users: Vec<UserPolicy>) -> Policy {
+        let buckets = users
+            .iter()
+            .filter_map(|user| Some((user.name.clone(), user.rate_limit.clone()?)))
+            .collect();
+        Policy {
+            users,
+            buckets: Mutex::new(buckets),
+            audit_log: None,
+        }
+    }
+
+    /// Records every command checked by [`Policy::execute()`] in the given log.
+    pub fn with_audit_log(mut self, audit_log: Arc<dyn AuditLog>) -> Policy {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Finds the user with the given password.
+    pub fn authenticate(&self, password: &str) -> Option<&UserPolicy> {
+        self.users.iter().find(|user| user.password == password)
+    }
+
+    /// Checks if the user may run the command now, taking a token from the rate limit if they may.
+    ///
+    /// Every statement of the command is checked, so `kick bob; quit` is rejected unless both `kick bob` and `quit` are allowed.
+    pub fn check(&self, user: &str, command: &str) -> Result<(), PolicyError> {
+        let policy = self
+            .users
+            .iter()
+            .find(|policy| policy.name == user)
+            .ok_or(PolicyError::UnknownUser)?;
+        if let Some(statement) =
+            split_statements(command).find(|statement| policy.action_for(statement) == Action::Deny)
+        {
+            return Err(PolicyError::Denied(statement.trim().to_string()));
+        }
+        if let Some(bucket) = self.buckets.lock().expect("poisoned").get_mut(user) {
+            bucket.try_acquire().map_err(PolicyError::RateLimited)?;
+        }
+        Ok(())
+    }
+
+    /// Appends a record to the audit log, if there is one.
+    pub fn audit(&self, user: &str, command: &str, outcome: AuditOutcome) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(&AuditRecord {
+                timestamp: SystemTime::now(),
+                user: user.to_string(),
+                command: command.to_string(),
+                outcome,
+            });
+        }
+    }
+
+    /// Checks the command with [`Policy::check()`] and runs it with `run` if it is allowed, recording the outcome in the audit log.
+    pub fn execute(
+        &self,
+        user: &str,
+        command: &str,
+        run: impl FnOnce(&str) -> Result<String, Error>,
+    ) -> Result<String, Error> {
+        if let Err(error) = self.check(user, command) {
+            self.audit(user, command, AuditOutcome::Rejected(error.to_string()));
+            return Err(error.into());
+        }
+        let result = run(command);
+        let outcome = match &result {
+            Ok(response) => AuditOutcome::Executed(response.clone()),
+            Err(error) => AuditOutcome::Failed(error.to_string()),
+        };
+        self.audit(user, command, outcome);
+        result
+    }
+}
+
+/// Splits a command into the statements the server will run, on `;` and newlines outside of double quotes.
+fn split_statements(command: &str) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    command
+        .split(move |c| match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                false
+            }
+            ';' | '\n' | '\r' => !in_quotes,
+            _ => false,
+        })
+        .filter(|statement| !statement.trim().is_empty())
+}
+
+/// Lowercases the text and collapses every run of whitespace into a single space, without leading or trailing whitespace.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Matches `text` against a pattern where `*` matches any number of bytes and `?` matches one.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    p = star + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moderator_policy() -> Policy {
+        Policy::new(vec![UserPolicy::new(
+            "moderator".to_string(),
+            "hunter2".to_string(),
+        )
+        .with_rule(Rule::deny("kick admin*"))
+        .with_rule(Rule::allow("kick *"))
+        .with_rule(Rule::allow("ban *"))
+        .with_rule(Rule::allow("status"))])
+    }
+
+    #[test]
+    fn rules_and_statements() {
+        let policy = moderator_policy();
+        assert_eq!(
+            policy.authenticate("hunter2").unwrap().get_name(),
+            "moderator"
+        );
+        assert!(policy.authenticate("password").is_none());
+
+        assert!(policy.check("moderator", "kick griefer").is_ok());
+        assert!(policy.check("moderator", "KICK griefer").is_ok());
+        assert!(policy.check("moderator", "status").is_ok());
+        assert!(policy.check("moderator", "say \"hi; quit\"").is_err());
+        assert_eq!(
+            policy.check("moderator", "kick admin_bob"),
+            Err(PolicyError::Denied("kick admin_bob".to_string()))
+        );
+        for spaced in ["kick  admin_bob", "kick\tadmin_bob", " kick \t admin_bob "] {
+            assert!(
+                matches!(
+                    policy.check("moderator", spaced),
+                    Err(PolicyError::Denied(_))
+                ),
+                "{spaced:?}"
+            );
+        }
+        assert_eq!(
+            policy.check("moderator", "kick griefer; quit"),
+            Err(PolicyError::Denied("quit".to_string()))
+        );
+        assert_eq!(
+            policy.check("moderator", "rcon_password x"),
+            Err(PolicyError::Denied("rcon_password x".to_string()))
+        );
+        assert_eq!(
+            policy.check("nobody", "status"),
+            Err(PolicyError::UnknownUser)
+        );
+    }
+
+    #[test]
+    fn rate_limit() {
+        let policy = Policy::new(vec![UserPolicy::new("bot".to_string(), "x".to_string())
+            .with_default_action(Action::Allow)
+            .with_rate_limit(TokenBucket::new(2, Duration::from_secs(60)))]);
+        assert!(policy.check("bot", "status").is_ok());
+        assert!(policy.check("bot", "status").is_ok());
+        assert!(matches!(
+            policy.check("bot", "status"),
+            Err(PolicyError::RateLimited(_))
+        ));
+    }
+
+    #[test]
+    fn audit_log_records_everything() {
+        let log = Arc::new(WriterAuditLog::new(Vec::new()));
+        let policy = moderator_policy().with_audit_log(log.clone());
+
+        let reply = policy.execute("moderator", "kick griefer", |cmd| Ok(format!("ran {cmd}")));
+        assert_eq!(reply.unwrap(), "ran kick griefer");
+        let error = policy
+            .execute("moderator", "quit", |_| unreachable!())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+        drop(policy);
+        let log = String::from_utf8(Arc::into_inner(log).unwrap().into_inner()).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines[0].ends_with("\t\"moderator\"\texecuted\t\"kick griefer\"\t\"ran kick griefer\"")
+        );
+        assert!(lines[1].contains("\trejected\t\"quit\"\t"));
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match(b"kick *", b"kick bob"));
+        assert!(glob_match(b"*pass*", b"rcon_password"));
+        assert!(glob_match(b"b?n *", b"ban 5"));
+        assert!(!glob_match(b"kick *", b"kickall"));
+        assert!(!glob_match(b"status", b"status2"));
+    }
+}
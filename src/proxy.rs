@@ -0,0 +1,709 @@
+//! Contains the implementation for [`Proxy`], an RCON proxy that lets many clients share one upstream connection.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{Error, ErrorKind, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::packet::{
+    packet_id::ID,
+    packet_kind::{ClientPacket, PacketKind, ServerPacket, SessionState},
+    Direction, Packet, PacketError, PacketType,
+};
+use crate::policy::{Action, AuditOutcome, Policy, UserPolicy};
+use crate::server::{accept_auth, send_response};
+
+/// How many upstream IDs are remembered per downstream client. Responses to older commands are dropped.
+const MAX_PENDING_PER_CLIENT: usize = 64;
+/// How many upstream IDs of removed or evicted routes are remembered, late responses to them are dropped.
+const MAX_RETIRED: usize = 4096;
+/// How many packets can wait to be written to a downstream client. A client that falls this far behind is disconnected.
+const MAX_QUEUED_PER_CLIENT: usize = 4096;
+/// How long a write to a downstream client may take before the client is disconnected.
+const DOWNSTREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A downstream user of the [`Proxy`] that may run any command. RCON has no user names, so the password is what identifies the user.
+/// Use [`Proxy::connect_with_policy()`] to restrict what users may run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyUser {
+    /// The name of the user, only used by the proxy itself.
+    pub name: String,
+    /// The password the user authenticates to the proxy with.
+    pub password: String,
+}
+
+impl ProxyUser {
+    /// Creates a new instance of the `ProxyUser`.
+    pub fn new(name: String, password: String) -> ProxyUser {
+        ProxyUser { name, password }
+    }
+}
+
+/// An RCON proxy that accepts many downstream clients and forwards their commands over a single authenticated upstream connection.
+///
+/// The packet IDs of the downstream clients are rewritten so every response is routed back to the client that sent the command. Packets from the upstream server that do not answer a known command, such as chat messages some games push, are sent to every downstream client. Late responses to commands of clients that have disconnected are dropped.
+///
+/// Commands are checked against a [`Policy`] before they are forwarded. Rejected commands are answered by the proxy with the reason, and the first response packet of every forwarded command is recorded in the audit log of the policy.
+///
+/// # Example
+/// ```no_run
+/// use std::net::TcpListener;
+/// use ya_rcon::proxy::{Proxy, ProxyUser};
+/// let users = vec![ProxyUser::new("moderator".to_string(), "hunter2".to_string())];
+/// let proxy = Proxy::connect("127.0.0.1:27015", "password".to_string(), users).unwrap();
+/// proxy.serve(TcpListener::bind("0.0.0.0:27016").unwrap()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    upstream: Mutex<TcpStream>,
+    policy: Policy,
+    next_id: AtomicU32,
+    next_client: AtomicU64,
+    routes: Mutex<Routes>,
+}
+
+#[derive(Debug, Default)]
+struct Routes {
+    clients: HashMap<u64, Downstream>,
+    /// Upstream ID to where the response should go.
+    pending: HashMap<ID, Route>,
+    /// Upstream IDs whose route was removed or evicted, oldest first.
+    retired: VecDeque<ID>,
+    retired_ids: HashSet<ID>,
+    /// Set once the upstream connection is closed, no clients are registered after that.
+    upstream_gone: bool,
+}
+
+impl Routes {
+    /// Removes the route of an upstream ID and remembers it, so a late response is dropped instead of sent to everyone.
+    fn retire(&mut self, upstream_id: ID) {
+        self.pending.remove(&upstream_id);
+        if self.retired_ids.insert(upstream_id) {
+            self.retired.push_back(upstream_id);
+        }
+        if self.retired.len() > MAX_RETIRED {
+            if let Some(oldest) = self.retired.pop_front() {
+                self.retired_ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Downstream {
+    /// Only used to disconnect the client, packets go through `queue` so a slow client never blocks the routing.
+    stream: TcpStream,
+    queue: SyncSender<Vec<u8>>,
+    user: String,
+    upstream_ids: VecDeque<ID>,
+}
+
+/// Queues a packet for the writer thread of a client, returns `false` if the client can not keep up or is gone.
+fn enqueue(queue: &SyncSender<Vec<u8>>, bytes: Vec<u8>) -> bool {
+    match queue.try_send(bytes) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+    }
+}
+
+#[derive(Debug)]
+struct Route {
+    client_id: u64,
+    downstream_id: ID,
+    /// The command, until its response is recorded in the audit log.
+    command: Option<String>,
+}
+
+impl Proxy {
+    /// Connects and authenticates to the upstream server and starts a thread that routes its packets to the downstream clients.
+    /// The users may run any command, see [`Proxy::connect_with_policy()`].
+    pub fn connect(
+        upstream_addr: impl ToSocketAddrs,
+        upstream_password: String,
+        users: Vec<ProxyUser>,
+    ) -> Result<Proxy, Error> {
+        let users = users
+            .into_iter()
+            .map(|user| {
+                UserPolicy::new(user.name, user.password).with_default_action(Action::Allow)
+            })
+            .collect();
+        Proxy::connect_with_policy(upstream_addr, upstream_password, Policy::new(users))
+    }
+
+    /// Same as [`Proxy::connect()`], but the downstream users and what they may run come from the [`Policy`].
+    pub fn connect_with_policy(
+        upstream_addr: impl ToSocketAddrs,
+        upstream_password: String,
+        policy: Policy,
+    ) -> Result<Proxy, Error> {
+        let mut upstream = TcpStream::connect(upstream_addr)?;
+        authenticate_upstream(&mut upstream, upstream_password)?;
+        let reader = upstream.try_clone()?;
+
+        let proxy = Proxy {
+            shared: Arc::new(Shared {
+                upstream: Mutex::new(upstream),
+                policy,
+                next_id: AtomicU32::new(1),
+                next_client: AtomicU64::new(0),
+                routes: Mutex::new(Routes::default()),
+            }),
+        };
+        let shared = proxy.shared.clone();
+        std::thread::spawn(move || shared.route_upstream(reader));
+        Ok(proxy)
+    }
+
+    /// Accepts downstream clients from the listener, each one is handled on its own thread.
+    ///
+    /// Fails with [`ErrorKind::NotConnected`] when a client connects after the upstream connection was closed, the proxy has to be connected again.
+    pub fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if self.shared.routes.lock().expect("poisoned").upstream_gone {
+                let _ = stream.shutdown(Shutdown::Both);
+                return Err(upstream_gone());
+            }
+            let proxy = self.clone();
+            std::thread::spawn(move || proxy.handle_client(stream));
+        }
+        Ok(())
+    }
+
+    /// Handles a single downstream client until it disconnects.
+    /// The client is disconnected if the upstream connection is closed, after that every client is refused.
+    pub fn handle_client(&self, mut stream: TcpStream) -> Result<(), Error> {
+        let shutdown = stream.try_clone()?;
+        let (queue, queued) = sync_channel(MAX_QUEUED_PER_CLIENT);
+        let reply = queue.clone();
+        // The client is registered before the auth response is sent so it can not miss any unsolicited packets,
+        // they wait in the queue until the writer thread is started after the auth response.
+        let mut registration = Some((shutdown, queue));
+        let (client_id, user) = accept_auth(&mut stream, |password| {
+            let user = self.shared.policy.authenticate(password)?.get_name();
+            let client_id = self.shared.next_client.fetch_add(1, Ordering::Relaxed);
+            let (shutdown, queue) = registration.take()?;
+            let mut routes = self.shared.routes.lock().expect("poisoned");
+            if routes.upstream_gone {
+                // Fails the auth response, the upstream can not run any commands.
+                let _ = shutdown.shutdown(Shutdown::Both);
+                return None;
+            }
+            routes.clients.insert(
+                client_id,
+                Downstream {
+                    stream: shutdown,
+                    queue,
+                    user: user.to_string(),
+                    upstream_ids: VecDeque::new(),
+                },
+            );
+            Some((client_id, user.to_string()))
+        })
+        .map_err(|e| {
+            if self.shared.routes.lock().expect("poisoned").upstream_gone {
+                upstream_gone()
+            } else {
+                e
+            }
+        })?;
+        let writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(DOWNSTREAM_WRITE_TIMEOUT))?;
+        std::thread::spawn(move || write_downstream(writer, queued));
+
+        let result = self.forward_commands(client_id, &user, &mut stream, &reply);
+        self.shared.remove_client(client_id);
+        match result {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(()),
+            result => result,
+        }
+    }
+
+    fn forward_commands(
+        &self,
+        client_id: u64,
+        user: &str,
+        stream: &mut TcpStream,
+        reply: &SyncSender<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let send = |bytes: Vec<u8>| {
+            reply.send(bytes).map_err(|_| {
+                Error::new(
+                    ErrorKind::BrokenPipe,
+                    "The downstream client was disconnected",
+                )
+            })
+        };
+        loop {
+            let packet = Packet::read_from(&mut *stream)?;
+            if packet.get_kind(Direction::ClientToServer, SessionState::Authenticated)
+                == PacketKind::Client(ClientPacket::Auth)
+            {
+                // Never forward the downstream password, answer re-authentication locally.
+                // The session stays with the user it started as, so only their own password is accepted.
+                let id = if self
+                    .shared
+                    .policy
+                    .authenticate(&packet.get_body())
+                    .is_some_and(|policy| policy.get_name() == user)
+                {
+                    packet.get_id()
+                } else {
+                    ID::from(-1)
+                };
+                send(Vec::from(Packet::new(
+                    PacketType::AuthResponse,
+                    String::new(),
+                    id,
+                )?))?;
+                continue;
+            }
+            let command = packet.get_body();
+            if let Err(error) = self.shared.policy.check(user, &command) {
+                let reason = error.to_string();
+                self.shared
+                    .policy
+                    .audit(user, &command, AuditOutcome::Rejected(reason.clone()));
+                let mut bytes = Vec::new();
+                send_response(&mut bytes, packet.get_id(), &reason)?;
+                send(bytes)?;
+                continue;
+            }
+            let upstream_id =
+                ID::from_wrapping(self.shared.next_id.fetch_add(1, Ordering::Relaxed));
+            self.shared
+                .track(client_id, upstream_id, packet.get_id(), command);
+            let rewritten = Packet::new_bytes(
+                packet.get_type(),
+                packet.get_body_bytes().to_vec(),
+                upstream_id,
+            )?;
+            self.shared
+                .upstream
+                .lock()
+                .expect("poisoned")
+                .write_all(&Vec::from(rewritten))?;
+        }
+    }
+}
+
+/// Runs on its own thread per downstream client, writing the queued packets until the client is removed or a write fails.
+fn write_downstream(mut writer: TcpStream, queued: Receiver<Vec<u8>>) {
+    for bytes in queued {
+        if writer.write_all(&bytes).is_err() {
+            // Also ends the reading side, which removes the client.
+            let _ = writer.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+impl Shared {
+    fn track(&self, client_id: u64, upstream_id: ID, downstream_id: ID, command: String) {
+        let mut routes = self.routes.lock().expect("poisoned");
+        let Some(client) = routes.clients.get_mut(&client_id) else {
+            return;
+        };
+        client.upstream_ids.push_back(upstream_id);
+        let expired = (client.upstream_ids.len() > MAX_PENDING_PER_CLIENT)
+            .then(|| client.upstream_ids.pop_front())
+            .flatten();
+        if let Some(expired) = expired {
+            routes.retire(expired);
+        }
+        routes.retired_ids.remove(&upstream_id);
+        routes.pending.insert(
+            upstream_id,
+            Route {
+                client_id,
+                downstream_id,
+                command: Some(command),
+            },
+        );
+    }
+
+    fn remove_client(&self, client_id: u64) {
+        let mut routes = self.routes.lock().expect("poisoned");
+        if let Some(client) = routes.clients.remove(&client_id) {
+            for id in client.upstream_ids {
+                routes.retire(id);
+            }
+        }
+    }
+
+    /// Runs on its own thread, reading packets from the upstream server and queueing them for the downstream clients.
+    /// Nothing is written while the routes are locked, clients that can not keep up are disconnected instead.
+    fn route_upstream(&self, mut reader: TcpStream) {
+        while let Ok(packet) = Packet::read_from(&mut reader) {
+            let mut audit = None;
+            // Only the queues are looked up under the lock, the audit log and the queues are written after it is released.
+            let mut deliveries = Vec::new();
+            {
+                let mut routes = self.routes.lock().expect("poisoned");
+                let Routes {
+                    clients,
+                    pending,
+                    retired_ids,
+                    ..
+                } = &mut *routes;
+                match pending.get_mut(&packet.get_id()) {
+                    Some(route) => {
+                        let Some(client) = clients.get(&route.client_id) else {
+                            continue;
+                        };
+                        if let Some(command) = route.command.take() {
+                            audit = Some((client.user.clone(), command));
+                        }
+                        let rewritten = Packet::new_bytes(
+                            packet.get_type(),
+                            packet.get_body_bytes().to_vec(),
+                            route.downstream_id,
+                        );
+                        if let Ok(rewritten) = rewritten {
+                            deliveries.push((
+                                route.client_id,
+                                client.queue.clone(),
+                                Vec::from(rewritten),
+                            ));
+                        }
+                    }
+                    // A late response to a command whose client is gone, it must not reach anyone else.
+                    None if retired_ids.contains(&packet.get_id())
+                        || self.issued(packet.get_id()) => {}
+                    None => {
+                        let bytes = Vec::from(packet.clone());
+                        for (&client_id, client) in clients.iter() {
+                            deliveries.push((client_id, client.queue.clone(), bytes.clone()));
+                        }
+                    }
+                }
+            }
+            // Audited before the client can see the response.
+            if let Some((user, command)) = audit {
+                let outcome = AuditOutcome::Executed(packet.get_body());
+                self.policy.audit(&user, &command, outcome);
+            }
+            for (client_id, queue, bytes) in deliveries {
+                if !enqueue(&queue, bytes) {
+                    self.disconnect(client_id);
+                }
+            }
+        }
+
+        // The upstream connection is gone, disconnect everyone so they can reconnect later.
+        let mut routes = self.routes.lock().expect("poisoned");
+        routes.upstream_gone = true;
+        for client in routes.clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Whether the proxy has used the ID for a forwarded command, only packets with other IDs are unsolicited.
+    /// Once the IDs have wrapped around every positive ID counts as issued.
+    fn issued(&self, id: ID) -> bool {
+        let next = self.next_id.load(Ordering::Relaxed);
+        let id = i32::from(id);
+        id > 0 && (next > 0x1000_0000 || (id as u32) < next)
+    }
+
+    /// Disconnects a downstream client, its thread notices and removes it.
+    fn disconnect(&self, client_id: u64) {
+        let routes = self.routes.lock().expect("poisoned");
+        if let Some(client) = routes.clients.get(&client_id) {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn upstream_gone() -> Error {
+    Error::new(
+        ErrorKind::NotConnected,
+        "The upstream RCON server closed the connection.",
+    )
+}
+
+/// Authenticates the upstream connection, skipping the empty [`PacketType::ResponseValue`] some Source servers send before the [`PacketType::AuthResponse`].
+fn authenticate_upstream(stream: &mut TcpStream, password: String) -> Result<(), Error> {
+    let id = ID::from(0);
+    stream.write_all(&Vec::from(Packet::new(PacketType::Auth, password, id)?))?;
+    loop {
+        let packet = Packet::read_from(&mut *stream)?;
+        match packet.get_kind(Direction::ServerToClient, SessionState::AuthPending) {
+            PacketKind::Server(ServerPacket::ResponseValue) => continue,
+            PacketKind::Server(ServerPacket::AuthResponse) if packet.get_id() == id => {
+                return Ok(())
+            }
+            PacketKind::Server(ServerPacket::AuthResponse) if packet.get_id() == ID::from(-1) => {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "Authentication with the upstream RCON server failed.",
+                ))
+            }
+            PacketKind::Server(ServerPacket::AuthResponse) => {
+                return Err(PacketError::UnexpectedID.into())
+            }
+            _ => return Err(PacketError::UnexpectedType.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::send_response;
+    use crate::{RCONClient, SimpleIDGenerator};
+
+    /// Starts an upstream server that answers `cmd` with `ran cmd` and also pushes a chat message to the proxy when it receives `push`.
+    /// The response to `slow` is delayed and `quit` closes the connection.
+    fn start_upstream() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_auth(&mut stream, |password| {
+                (password == "upstream").then_some(())
+            })
+            .unwrap();
+            while let Ok(packet) = Packet::read_from(&mut stream) {
+                let body = packet.get_body();
+                if body == "quit" {
+                    break;
+                }
+                if body == "push" {
+                    let chat = Packet::new(PacketType::Raw(1), "chat".to_string(), ID::from(0));
+                    stream.write_all(&Vec::from(chat.unwrap())).unwrap();
+                }
+                if body == "slow" {
+                    std::thread::sleep(Duration::from_millis(300));
+                }
+                if body == "flood" {
+                    let chat = Packet::new(PacketType::Raw(1), "x".repeat(4000), ID::from(0));
+                    let bytes = Vec::from(chat.unwrap());
+                    for _ in 0..2000 {
+                        stream.write_all(&bytes).unwrap();
+                    }
+                }
+                send_response(&mut stream, packet.get_id(), &format!("ran {body}")).unwrap();
+            }
+        });
+        address
+    }
+
+    fn start_proxy() -> std::net::SocketAddr {
+        let users = vec![
+            ProxyUser::new("alice".to_string(), "alice-pass".to_string()),
+            ProxyUser::new("bob".to_string(), "bob-pass".to_string()),
+        ];
+        let proxy = Proxy::connect(start_upstream(), "upstream".to_string(), users).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || proxy.serve(listener));
+        address
+    }
+
+    fn raw_login(address: std::net::SocketAddr, password: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let auth = Packet::new(PacketType::Auth, password.to_string(), ID::from(1)).unwrap();
+        stream.write_all(&Vec::from(auth)).unwrap();
+        assert_eq!(
+            Packet::read_from(&mut stream).unwrap().get_id(),
+            ID::from(1)
+        );
+        stream
+    }
+
+    #[test]
+    fn responses_are_routed_to_their_client() {
+        let address = start_proxy();
+        let mut alice = RCONClient::new(
+            TcpStream::connect(address).unwrap(),
+            SimpleIDGenerator::new(),
+            "alice-pass".to_string(),
+        )
+        .unwrap();
+        let mut bob = RCONClient::new(
+            TcpStream::connect(address).unwrap(),
+            SimpleIDGenerator::new(),
+            "bob-pass".to_string(),
+        )
+        .unwrap();
+
+        for i in 0..5 {
+            assert_eq!(
+                alice.send_command(format!("a{i}")).unwrap(),
+                format!("ran a{i}")
+            );
+            assert_eq!(
+                bob.send_command(format!("b{i}")).unwrap(),
+                format!("ran b{i}")
+            );
+        }
+
+        let error = RCONClient::new(
+            TcpStream::connect(address).unwrap(),
+            SimpleIDGenerator::new(),
+            "upstream".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn unsolicited_packets_are_sent_to_everyone() {
+        let address = start_proxy();
+        let mut alice = raw_login(address, "alice-pass");
+        let mut bob = raw_login(address, "bob-pass");
+
+        let push = Packet::new(PacketType::ExecCommand, "push".to_string(), ID::from(9)).unwrap();
+        alice.write_all(&Vec::from(push)).unwrap();
+
+        let chat = Packet::read_from(&mut alice).unwrap();
+        assert_eq!(chat.get_type(), PacketType::Raw(1));
+        assert_eq!(chat.get_body(), "chat");
+        let response = Packet::read_from(&mut alice).unwrap();
+        assert_eq!(response.get_id(), ID::from(9));
+        assert_eq!(response.get_body(), "ran push");
+
+        let chat = Packet::read_from(&mut bob).unwrap();
+        assert_eq!(chat.get_body(), "chat");
+    }
+
+    #[test]
+    fn late_responses_to_disconnected_clients_are_dropped() {
+        let address = start_proxy();
+        let mut alice = raw_login(address, "alice-pass");
+        let mut bob = raw_login(address, "bob-pass");
+
+        let slow = Packet::new(PacketType::ExecCommand, "slow".to_string(), ID::from(5)).unwrap();
+        alice.write_all(&Vec::from(slow)).unwrap();
+        alice.shutdown(Shutdown::Both).unwrap();
+        drop(alice);
+
+        let status = Packet::new(PacketType::ExecCommand, "status".to_string(), ID::from(7));
+        bob.write_all(&Vec::from(status.unwrap())).unwrap();
+        let response = Packet::read_from(&mut bob).unwrap();
+        assert_eq!(response.get_id(), ID::from(7));
+        assert_eq!(response.get_body(), "ran status");
+
+        let push = Packet::new(PacketType::ExecCommand, "push".to_string(), ID::from(8)).unwrap();
+        bob.write_all(&Vec::from(push)).unwrap();
+        assert_eq!(Packet::read_from(&mut bob).unwrap().get_body(), "chat");
+        assert_eq!(Packet::read_from(&mut bob).unwrap().get_body(), "ran push");
+    }
+
+    #[test]
+    fn clients_are_refused_after_the_upstream_is_gone() {
+        let users = vec![ProxyUser::new(
+            "alice".to_string(),
+            "alice-pass".to_string(),
+        )];
+        let proxy = Proxy::connect(start_upstream(), "upstream".to_string(), users).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || proxy.serve(listener));
+
+        let mut alice = raw_login(address, "alice-pass");
+        let quit = Packet::new(PacketType::ExecCommand, "quit".to_string(), ID::from(2)).unwrap();
+        alice.write_all(&Vec::from(quit)).unwrap();
+        assert!(Packet::read_from(&mut alice).is_err());
+
+        let mut late = TcpStream::connect(address).unwrap();
+        late.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let auth = Packet::new(PacketType::Auth, "alice-pass".to_string(), ID::from(1)).unwrap();
+        let _ = late.write_all(&Vec::from(auth));
+        assert!(Packet::read_from(&mut late).is_err());
+        assert_eq!(
+            server.join().unwrap().unwrap_err().kind(),
+            ErrorKind::NotConnected
+        );
+    }
+
+    #[test]
+    fn slow_clients_do_not_block_others() {
+        let address = start_proxy();
+        // Never reads, so its socket buffers fill up and it falls behind.
+        // Its writer thread stays blocked until the write timeout disconnects it.
+        let stuck = raw_login(address, "bob-pass");
+        let mut alice = RCONClient::new(
+            TcpStream::connect(address).unwrap(),
+            SimpleIDGenerator::new(),
+            "alice-pass".to_string(),
+        )
+        .unwrap();
+        // The pushed packets are discarded by the client as they answer no command.
+        assert_eq!(
+            alice.send_command("flood".to_string()).unwrap(),
+            "ran flood"
+        );
+        assert_eq!(
+            alice.send_command("after".to_string()).unwrap(),
+            "ran after"
+        );
+        drop(stuck);
+    }
+
+    #[test]
+    fn reauthentication_only_accepts_the_same_user() {
+        let address = start_proxy();
+        let mut alice = raw_login(address, "alice-pass");
+        for (password, id) in [("bob-pass", -1), ("wrong", -1), ("alice-pass", 2)] {
+            let auth = Packet::new(PacketType::Auth, password.to_string(), ID::from(2)).unwrap();
+            alice.write_all(&Vec::from(auth)).unwrap();
+            let response = Packet::read_from(&mut alice).unwrap();
+            assert_eq!(response.get_type(), PacketType::AuthResponse);
+            assert_eq!(response.get_id(), ID::from(id), "{password}");
+        }
+    }
+
+    #[test]
+    fn policy_is_enforced_and_audited() {
+        use crate::policy::{Rule, WriterAuditLog};
+
+        let log = Arc::new(WriterAuditLog::new(Vec::new()));
+        let moderator = UserPolicy::new("moderator".to_string(), "mod-pass".to_string())
+            .with_rule(Rule::allow("kick *"));
+        let policy = Policy::new(vec![moderator]).with_audit_log(log.clone());
+        let proxy =
+            Proxy::connect_with_policy(start_upstream(), "upstream".to_string(), policy).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || proxy.serve(listener));
+
+        let mut client = RCONClient::new(
+            TcpStream::connect(address).unwrap(),
+            SimpleIDGenerator::new(),
+            "mod-pass".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            client.send_command("kick bob".to_string()).unwrap(),
+            "ran kick bob"
+        );
+        assert_eq!(
+            client.send_command("kick bob; quit".to_string()).unwrap(),
+            "Command denied by policy: quit"
+        );
+
+        let log = String::from_utf8(log.lock_writer().clone()).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\t\"moderator\"\texecuted\t\"kick bob\"\t\"ran kick bob\""));
+        assert!(lines[1].contains("\trejected\t\"kick bob; quit\""));
+    }
+}
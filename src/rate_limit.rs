@@ -0,0 +1,162 @@
+//! Contains the implementation for [`TokenBucket`]
+
+use std::{
+    io::{Error, ErrorKind},
+    time::{Duration, Instant},
+};
+
+/// A token bucket rate limiter. The bucket holds up to `capacity` tokens and is refilled at a constant rate, every command takes one token.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use ya_rcon::rate_limit::TokenBucket;
+/// // Bursts of up to 5 commands, then one command every 200ms.
+/// let mut bucket = TokenBucket::new(5, Duration::from_millis(200));
+/// for _ in 0..5 {
+///     assert!(bucket.try_acquire().is_ok());
+/// }
+/// assert!(bucket.try_acquire().is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: u32,
+    refill_interval: Duration,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a new, full bucket that holds `capacity` tokens and gains a token every `refill_interval`.
+    ///
+    /// # Panics
+    /// If `capacity` is 0 or `refill_interval` is zero, use [`TokenBucket::try_new()`] for values from a config file.
+    pub fn new(capacity: u32, refill_interval: Duration) -> TokenBucket {
+        TokenBucket::try_new(capacity, refill_interval).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`TokenBucket::new()`], but fails with [`ErrorKind::InvalidInput`] instead of panicking if `capacity` is 0 or `refill_interval` is zero.
+    pub fn try_new(capacity: u32, refill_interval: Duration) -> Result<TokenBucket, Error> {
+        if capacity == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "TokenBucket capacity must not be 0",
+            ));
+        }
+        if refill_interval.is_zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "TokenBucket refill interval must not be zero",
+            ));
+        }
+        Ok(TokenBucket {
+            capacity,
+            refill_interval,
+            tokens: f64::from(capacity),
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// Creates a new bucket that allows `per_second` commands every second, with bursts of up to `per_second` commands.
+    ///
+    /// # Panics
+    /// If `per_second` is 0, use [`TokenBucket::try_per_second()`] for values from a config file.
+    pub fn per_second(per_second: u32) -> TokenBucket {
+        TokenBucket::try_per_second(per_second).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`TokenBucket::per_second()`], but fails with [`ErrorKind::InvalidInput`] instead of panicking if `per_second` is 0.
+    pub fn try_per_second(per_second: u32) -> Result<TokenBucket, Error> {
+        TokenBucket::try_new(per_second, Duration::from_secs(1) / per_second.max(1))
+    }
+
+    /// Gets the maximum number of tokens in the bucket.
+    pub fn get_capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() / self.refill_interval.as_secs_f64())
+            .min(f64::from(self.capacity));
+        self.last_refill = now;
+    }
+
+    /// Takes a token from the bucket if there is one, otherwise returns how long until the next token is available.
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self
+                .refill_interval
+                .mul_f64(1.0 - self.tokens)
+                .max(Duration::from_millis(1)))
+        }
+    }
+
+    /// Takes a token from the bucket, sleeping the current thread until one is available.
+    pub fn acquire_blocking(&mut self) {
+        while let Err(wait) = self.try_acquire() {
+            std::thread::sleep(wait + Duration::from_millis(1));
+        }
+    }
+
+    /// Takes a token from the bucket, waiting without blocking the async runtime until one is available.
+    #[cfg(any(feature = "tokio", feature = "async-net"))]
+    pub async fn acquire(&mut self) {
+        while let Err(wait) = self.try_acquire() {
+            sleep(wait + Duration::from_millis(1)).await;
+        }
+    }
+}
+
+/// Sleeps without blocking the async runtime.
+#[cfg(feature = "async-net")]
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_io::Timer::after(duration).await;
+}
+
+/// Sleeps without blocking the async runtime.
+#[cfg(feature = "tokio")]
+#[cfg(not(feature = "async-net"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let mut bucket = TokenBucket::new(2, Duration::from_millis(20));
+        assert!(bucket.try_acquire().is_ok());
+        assert!(bucket.try_acquire().is_ok());
+        let wait = bucket.try_acquire().unwrap_err();
+        assert!(wait <= Duration::from_millis(20));
+
+        std::thread::sleep(wait + Duration::from_millis(1));
+        assert!(bucket.try_acquire().is_ok());
+        assert!(bucket.try_acquire().is_err());
+    }
+
+    #[test]
+    fn invalid_buckets() {
+        assert_eq!(
+            TokenBucket::try_per_second(0).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(TokenBucket::try_new(1, Duration::ZERO).is_err());
+        assert_eq!(TokenBucket::try_per_second(4).unwrap().get_capacity(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must not be 0")]
+    fn per_second_zero_panics() {
+        TokenBucket::per_second(0);
+    }
+}
@@ -0,0 +1,297 @@
+//! Contains the config file format of the [`Scheduler`].
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use super::{Comparison, Condition, Job, MissedRun, Scheduler, Step};
+
+fn invalid(message: impl Display) -> Error {
+    Error::new(ErrorKind::InvalidInput, message.to_string())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    utc_offset: i32,
+    #[serde(default)]
+    servers: BTreeMap<String, ServerFile>,
+    #[serde(default)]
+    jobs: BTreeMap<String, JobFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerFile {
+    address: String,
+    password: Option<String>,
+    password_env: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobFile {
+    server: String,
+    cron: String,
+    jitter: Option<String>,
+    #[serde(default)]
+    missed: MissedFile,
+    #[serde(default)]
+    steps: Vec<StepFile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+enum MissedFile {
+    Skip,
+    #[default]
+    RunOnce,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StepFile {
+    Command(String),
+    Conditional {
+        command: String,
+        #[serde(rename = "if")]
+        condition: ConditionFile,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConditionFile {
+    command: String,
+    parser: String,
+    comparison: String,
+    value: f64,
+}
+
+/// Parses `30`, `30s`, `5m` or `1h`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    match unit {
+        "s" => Some(Duration::from_secs(number)),
+        "m" => Some(Duration::from_secs(number * 60)),
+        "h" => Some(Duration::from_secs(number * 3600)),
+        _ => None,
+    }
+}
+
+fn parse_comparison(value: &str) -> Option<Comparison> {
+    Some(match value {
+        "<" => Comparison::Less,
+        "<=" => Comparison::LessOrEqual,
+        "==" => Comparison::Equal,
+        "!=" => Comparison::NotEqual,
+        ">=" => Comparison::GreaterOrEqual,
+        ">" => Comparison::Greater,
+        _ => return None,
+    })
+}
+
+/// A server from the `[servers]` table of a scheduler config file, see [`Scheduler::from_config()`].
+/// Lets other tools such as `rcon broadcast` reach the servers by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerProfile {
+    /// The name of the server in the config file.
+    pub name: String,
+    /// The address of the server.
+    pub address: String,
+    /// The RCON password, read from the environment if the file has `password_env`.
+    pub password: String,
+}
+
+impl ServerProfile {
+    fn from_file(name: String, server: ServerFile) -> Result<ServerProfile, Error> {
+        let password = match (server.password, server.password_env) {
+            (Some(password), None) => password,
+            (None, Some(variable)) => std::env::var(&variable)
+                .map_err(|_| invalid(format!("environment variable {variable} is not set")))?,
+            _ => {
+                return Err(invalid(format!(
+                    "server {name:?} needs either password or password_env"
+                )))
+            }
+        };
+        Ok(ServerProfile {
+            name,
+            address: server.address,
+            password,
+        })
+    }
+
+    /// Reads the servers of a scheduler config file, sorted by name.
+    /// Fails with [`ErrorKind::InvalidInput`] like [`Scheduler::from_config()`], the jobs are not checked.
+    pub fn from_config(text: &str) -> Result<Vec<ServerProfile>, Error> {
+        let file: ConfigFile = toml::from_str(text).map_err(invalid)?;
+        file.servers
+            .into_iter()
+            .map(|(name, server)| ServerProfile::from_file(name, server))
+            .collect()
+    }
+}
+
+impl StepFile {
+    fn into_step(self, job: &str) -> Result<Step, Error> {
+        let (command, condition) = match self {
+            StepFile::Command(command) => return Ok(Step::new(command)),
+            StepFile::Conditional { command, condition } => (command, condition),
+        };
+        let comparison = parse_comparison(&condition.comparison).ok_or_else(|| {
+            invalid(format!(
+                "job {job:?}: comparison must be <, <=, ==, !=, >= or >, not {:?}",
+                condition.comparison
+            ))
+        })?;
+        let parser = condition
+            .parser
+            .parse()
+            .map_err(|e| invalid(format!("job {job:?}: {e}")))?;
+        Ok(Step::new(command).with_condition(Condition::new(
+            condition.command,
+            parser,
+            comparison,
+            condition.value,
+        )))
+    }
+}
+
+impl Scheduler {
+    /// Reads a scheduler from a TOML config file, servers are reached over TCP like [`Scheduler::with_tcp_server()`].
+    ///
+    /// Fails with [`ErrorKind::InvalidInput`] if the file is invalid or a job uses a server that is not in it.
+    /// ```toml
+    /// # Minutes ahead of UTC that the schedules are in.
+    /// utc_offset = 120
+    ///
+    /// [servers.survival]
+    /// address = "127.0.0.1:25575"
+    /// # Or `password = "..."` to put the password in the file.
+    /// password_env = "SURVIVAL_RCON_PASSWORD"
+    ///
+    /// [jobs.restart-warning]
+    /// server = "survival"
+    /// cron = "50 3 * * *"
+    /// # Optional, a random delay of up to 30 seconds (`s`, `m` and `h` work).
+    /// jitter = "30s"
+    /// # Optional, `skip` or `run-once` (the default).
+    /// missed = "skip"
+    /// # Steps run in order, a step with `if` only runs if the condition holds.
+    /// # The parsers are the same as in `ValueParser::from_str()`, the comparisons are <, <=, ==, !=, >= and >.
+    /// steps = [
+    ///     { command = "say Server restarts in 10 minutes", if = { command = "list", parser = "minecraft-list", comparison = ">", value = 0 } },
+    ///     "save-all",
+    /// ]
+    /// ```
+    pub fn from_config(text: &str) -> Result<Scheduler, Error> {
+        let file: ConfigFile = toml::from_str(text).map_err(invalid)?;
+        let mut scheduler = Scheduler::new().with_utc_offset(file.utc_offset);
+        for (name, server) in file.servers {
+            let profile = ServerProfile::from_file(name, server)?;
+            scheduler = scheduler.with_tcp_server(profile.name, profile.address, profile.password);
+        }
+        for (name, job) in file.jobs {
+            if !scheduler.servers.contains_key(&job.server) {
+                return Err(invalid(format!(
+                    "job {name:?} uses unknown server {:?}",
+                    job.server
+                )));
+            }
+            let schedule = job
+                .cron
+                .parse()
+                .map_err(|e| invalid(format!("job {name:?}: {e}")))?;
+            let jitter = match job.jitter {
+                Some(jitter) => parse_duration(&jitter)
+                    .ok_or_else(|| invalid(format!("job {name:?}: invalid jitter {jitter:?}")))?,
+                None => Duration::ZERO,
+            };
+            let missed = match job.missed {
+                MissedFile::Skip => MissedRun::Skip,
+                MissedFile::RunOnce => MissedRun::RunOnce,
+            };
+            let steps = job
+                .steps
+                .into_iter()
+                .map(|step| step.into_step(&name))
+                .collect::<Result<Vec<_>, Error>>()?;
+            let mut scheduled = Job::new(name, job.server, schedule)
+                .with_jitter(jitter)
+                .with_missed_run(missed);
+            scheduled.steps = steps;
+            scheduler = scheduler.with_job(scheduled);
+        }
+        Ok(scheduler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = r#"
+utc_offset = 60
+
+[servers.survival]
+address = "127.0.0.1:25575"
+password = "secret"
+
+[jobs.restart]
+server = "survival"
+cron = "50 3 * * *"
+jitter = "2m"
+missed = "skip"
+steps = [
+    { command = "say Restarting in 10 minutes", if = { command = "list", parser = "minecraft-list", comparison = ">", value = 0 } },
+    "save-all",
+]
+"#;
+        let scheduler = Scheduler::from_config(config).unwrap();
+        assert_eq!(scheduler.utc_offset_minutes, 60);
+        let job = &scheduler.jobs[0].job;
+        assert_eq!(job.jitter, Duration::from_secs(120));
+        assert_eq!(job.missed, MissedRun::Skip);
+        assert_eq!(job.steps.len(), 2);
+        let condition = job.steps[0].condition.as_ref().unwrap();
+        assert_eq!(condition.command, "list");
+        assert_eq!(condition.comparison, Comparison::Greater);
+        assert_eq!(job.steps[0].command, "say Restarting in 10 minutes");
+        assert!(job.steps[1].condition.is_none());
+
+        let unknown_server = "[jobs.restart]\nserver = \"nope\"\ncron = \"@daily\"\n";
+        assert!(Scheduler::from_config(unknown_server).is_err());
+        let missing_cron =
+            "[servers.a]\naddress = \"x\"\npassword = \"y\"\n[jobs.j]\nserver = \"a\"\n";
+        assert!(Scheduler::from_config(missing_cron).is_err());
+        let bad_comparison = config.replace("\">\"", "\"=>\"");
+        assert_eq!(
+            Scheduler::from_config(&bad_comparison).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let typo = config.replace("jitter", "jiter");
+        assert!(Scheduler::from_config(&typo).is_err());
+
+        assert_eq!(
+            ServerProfile::from_config(config).unwrap(),
+            [ServerProfile {
+                name: "survival".to_string(),
+                address: "127.0.0.1:25575".to_string(),
+                password: "secret".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Contains the implementation for [`CronSchedule`]
+
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A standard five field cron expression: `minute hour day-of-month month day-of-week`.
+///
+/// Every field is `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a comma separated list of those.
+/// Day-of-week is 0-7 where both 0 and 7 are Sunday. Like cron, if both day-of-month and day-of-week are restricted a day matches if either matches.
+/// The shortcuts `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are also accepted.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use ya_rcon::scheduler::CronSchedule;
+/// // Every day at 03:50.
+/// let schedule: CronSchedule = "50 3 * * *".parse().unwrap();
+/// let next = schedule.next_after(UNIX_EPOCH, 0).unwrap();
+/// assert_eq!(next, UNIX_EPOCH + Duration::from_secs(3 * 3600 + 50 * 60));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    days_of_week: u8,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// Parses one field into a bitset of the allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, Error> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid cron field {field:?}, expected values from {min} to {max}"),
+        )
+    };
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                // `5/15` means every 15 starting at 5.
+                None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, value)
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expanded = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid cron expression {s:?}, expected 5 fields"),
+            ));
+        };
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        // Sunday is both 0 and 7.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)? as u32,
+            days_of_month: parse_field(day_of_month, 1, 31)? as u32,
+            months: parse_field(month, 1, 12)? as u16,
+            days_of_week: (days_of_week & 0x7f) as u8,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+}
+
+/// The date of the given number of days since 1970-01-01 as (year, month, day), from Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl CronSchedule {
+    fn day_matches(&self, days: i64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as u32;
+        let dom = self.days_of_month & (1 << day) != 0;
+        let dow = self.days_of_week & (1 << weekday) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+
+    /// The first time strictly after `after` that matches the schedule, where the schedule is in local time `utc_offset_minutes` ahead of UTC.
+    /// Returns `None` if nothing matches within the next 5 years, for example for `0 0 30 2 *`.
+    pub fn next_after(&self, after: SystemTime, utc_offset_minutes: i32) -> Option<SystemTime> {
+        let offset = i64::from(utc_offset_minutes) * 60;
+        let since_epoch = match after.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        // Start at the next whole minute in local time.
+        let mut minute = (since_epoch + offset).div_euclid(60) + 1;
+        let limit = minute + 5 * 366 * 24 * 60;
+        while minute < limit {
+            let days = minute.div_euclid(24 * 60);
+            if !self.day_matches(days) {
+                minute = (days + 1) * 24 * 60;
+                continue;
+            }
+            let hour = minute.rem_euclid(24 * 60) / 60;
+            if self.hours & (1 << hour) == 0 {
+                minute = (minute.div_euclid(60) + 1) * 60;
+                continue;
+            }
+            if self.minutes & (1 << minute.rem_euclid(60)) == 0 {
+                minute += 1;
+                continue;
+            }
+            let utc = minute * 60 - offset;
+            return Some(match u64::try_from(utc) {
+                Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+                Err(_) => UNIX_EPOCH - Duration::from_secs(utc.unsigned_abs()),
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn next_matching_times() {
+        // 2024-02-28 23:59:30 UTC, a Wednesday.
+        let start = at(1_709_164_770);
+        let every_quarter: CronSchedule = "*/15 * * * *".parse().unwrap();
+        assert_eq!(every_quarter.next_after(start, 0), Some(at(1_709_164_800)));
+
+        // The leap day.
+        let leap_day: CronSchedule = "0 12 29 2 *".parse().unwrap();
+        assert_eq!(leap_day.next_after(start, 0), Some(at(1_709_208_000)));
+
+        // Monday 2024-03-04 04:00 local time at UTC+2 is 02:00 UTC.
+        let monday: CronSchedule = "0 4 * * 1".parse().unwrap();
+        assert_eq!(monday.next_after(start, 120), Some(at(1_709_517_600)));
+
+        let never: CronSchedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(never.next_after(start, 0), None);
+
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * *".parse::<CronSchedule>().is_err());
+    }
+}
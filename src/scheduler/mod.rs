@@ -0,0 +1,562 @@
+//! Contains the implementation for [`Scheduler`], which runs sequences of commands on a cron schedule.
+//!
+//! A [`Scheduler`] knows a set of named servers and [`Job`]s. Every job runs its [`Step`]s in order on one server, steps can have a [`Condition`] such as "only if players are online".
+//! Connections are opened when needed and reopened after a failure. Every run is passed to the log as a [`JobReport`].
+//!
+//! Schedulers can also be read from a TOML config file, see [`Scheduler::from_config()`].
+//!
+//! # Example
+//! ```no_run
+//! use ya_rcon::metrics::ValueParser;
+//! use ya_rcon::scheduler::{Comparison, Condition, Job, Scheduler, Step};
+//!
+//! let players_online = Condition::new(
+//!     "list".to_string(),
+//!     ValueParser::MinecraftList,
+//!     Comparison::Greater,
+//!     0.0,
+//! );
+//! let restart = Job::new("restart".to_string(), "survival".to_string(), "50 3 * * *".parse().unwrap())
+//!     .with_step(Step::new("say Server restarts in 10 minutes".to_string()).with_condition(players_online))
+//!     .with_step(Step::new("save-all".to_string()));
+//! Scheduler::new()
+//!     .with_tcp_server("survival".to_string(), "127.0.0.1:25575".to_string(), "password".to_string())
+//!     .with_job(restart)
+//!     .run();
+//! ```
+
+mod config;
+mod cron;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{Error, ErrorKind},
+    net::TcpStream,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub use config::ServerProfile;
+pub use cron::CronSchedule;
+
+use crate::{metrics::ValueParser, service::CommandService, RCONClient, SimpleIDGenerator};
+
+/// A run that starts more than this late counts as missed, see [`MissedRun`].
+const MISSED_AFTER: Duration = Duration::from_secs(60);
+
+/// What to do with a run that was missed, because an earlier job took too long or the machine was suspended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedRun {
+    /// Skip the missed run and wait for the next one.
+    Skip,
+    /// Run the job once as soon as possible, no matter how many runs were missed.
+    #[default]
+    RunOnce,
+}
+
+/// How the value of a [`Condition`] is compared to its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `>=`
+    GreaterOrEqual,
+    /// `>`
+    Greater,
+}
+
+impl Comparison {
+    fn compare(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Greater => value > threshold,
+        }
+    }
+}
+
+/// Runs a command, parses a number from the response and compares it to a threshold.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    command: String,
+    parser: ValueParser,
+    comparison: Comparison,
+    threshold: f64,
+}
+
+impl Condition {
+    /// Creates a new instance of the `Condition`.
+    pub fn new(
+        command: String,
+        parser: ValueParser,
+        comparison: Comparison,
+        threshold: f64,
+    ) -> Condition {
+        Condition {
+            command,
+            parser,
+            comparison,
+            threshold,
+        }
+    }
+
+    fn evaluate<S: CommandService + ?Sized>(&self, service: &mut S) -> Result<bool, Error> {
+        let response = service.call(self.command.clone())?;
+        let value = self.parser.parse(&response).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("No value in the response to {:?}", self.command),
+            )
+        })?;
+        Ok(self.comparison.compare(value, self.threshold))
+    }
+}
+
+/// A command in a [`Job`].
+#[derive(Debug, Clone)]
+pub struct Step {
+    command: String,
+    condition: Option<Condition>,
+}
+
+impl Step {
+    /// Creates a step that always runs.
+    pub fn new(command: String) -> Step {
+        Step {
+            command,
+            condition: None,
+        }
+    }
+
+    /// Only runs the step if the condition holds. If the condition can not be evaluated the step fails.
+    pub fn with_condition(mut self, condition: Condition) -> Step {
+        self.condition = Some(condition);
+        self
+    }
+}
+
+/// A sequence of steps that runs on one server on a schedule.
+#[derive(Debug, Clone)]
+pub struct Job {
+    name: String,
+    server: String,
+    schedule: CronSchedule,
+    steps: Vec<Step>,
+    jitter: Duration,
+    missed: MissedRun,
+}
+
+impl Job {
+    /// Creates a job without steps.
+    pub fn new(name: String, server: String, schedule: CronSchedule) -> Job {
+        Job {
+            name,
+            server,
+            schedule,
+            steps: Vec::new(),
+            jitter: Duration::ZERO,
+            missed: MissedRun::default(),
+        }
+    }
+
+    /// Adds a step after the steps that were already added.
+    pub fn with_step(mut self, step: Step) -> Job {
+        self.steps.push(step);
+        self
+    }
+
+    /// Delays every run by a random duration of up to `jitter`, so jobs on many servers do not all run at the same moment.
+    pub fn with_jitter(mut self, jitter: Duration) -> Job {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets what happens to missed runs.
+    pub fn with_missed_run(mut self, missed: MissedRun) -> Job {
+        self.missed = missed;
+        self
+    }
+
+    /// Gets the name of the job.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// What happened to a step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The step ran, with the response of the server.
+    Ran(String),
+    /// The condition of the step did not hold.
+    Skipped,
+    /// The step or its condition failed, with the error.
+    Failed(String),
+}
+
+/// The result of one run of a [`Job`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobReport {
+    /// The name of the job.
+    pub job: String,
+    /// The name of the server.
+    pub server: String,
+    /// When the run started.
+    pub started: SystemTime,
+    /// Every step with what happened to it.
+    pub steps: Vec<(String, StepOutcome)>,
+}
+
+impl JobReport {
+    /// Whether no step failed.
+    pub fn is_success(&self) -> bool {
+        !self
+            .steps
+            .iter()
+            .any(|(_, outcome)| matches!(outcome, StepOutcome::Failed(_)))
+    }
+}
+
+impl Display for JobReport {
+    /// One line per step, `<unix seconds>\t<job>\t<server>\t<command>\t<outcome>`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let started = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for (command, outcome) in &self.steps {
+            let outcome = match outcome {
+                StepOutcome::Ran(response) => format!("ran {}", response.escape_debug()),
+                StepOutcome::Skipped => "skipped".to_string(),
+                StepOutcome::Failed(error) => format!("failed {}", error.escape_debug()),
+            };
+            writeln!(
+                f,
+                "{started}\t{}\t{}\t{}\t{outcome}",
+                self.job,
+                self.server,
+                command.escape_debug()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+type Connector = Box<dyn FnMut() -> Result<Box<dyn CommandService + Send>, Error> + Send>;
+
+/// A server and its connection, which is opened when needed and dropped after a failure.
+struct Server {
+    connect: Connector,
+    connection: Option<Box<dyn CommandService + Send>>,
+}
+
+impl Server {
+    fn connection(&mut self) -> Result<&mut (dyn CommandService + Send), Error> {
+        if self.connection.is_none() {
+            self.connection = Some((self.connect)()?);
+        }
+        Ok(self.connection.as_deref_mut().expect("connected above"))
+    }
+
+    fn run_step(&mut self, step: &Step) -> StepOutcome {
+        let result = self.connection().and_then(|service| {
+            if let Some(condition) = &step.condition {
+                if !condition.evaluate(service)? {
+                    return Ok(None);
+                }
+            }
+            service.call(step.command.clone()).map(Some)
+        });
+        match result {
+            Ok(Some(response)) => StepOutcome::Ran(response),
+            Ok(None) => StepOutcome::Skipped,
+            Err(e) => {
+                // A failed command leaves the connection in an unknown state, the next step reconnects.
+                self.connection = None;
+                StepOutcome::Failed(e.to_string())
+            }
+        }
+    }
+}
+
+struct ScheduledJob {
+    job: Job,
+    next: Option<SystemTime>,
+}
+
+/// Runs [`Job`]s on their schedules, see the [module documentation](self).
+pub struct Scheduler {
+    servers: HashMap<String, Server>,
+    jobs: Vec<ScheduledJob>,
+    utc_offset_minutes: i32,
+    log: Box<dyn FnMut(&JobReport) + Send>,
+    rng: u64,
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("servers", &self.servers.keys().collect::<Vec<_>>())
+            .field(
+                "jobs",
+                &self.jobs.iter().map(|job| &job.job).collect::<Vec<_>>(),
+            )
+            .field("utc_offset_minutes", &self.utc_offset_minutes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler without servers or jobs that interprets schedules in UTC and logs reports to stderr.
+    pub fn new() -> Scheduler {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        Scheduler {
+            servers: HashMap::new(),
+            jobs: Vec::new(),
+            utc_offset_minutes: 0,
+            log: Box::new(|report| eprint!("{report}")),
+            rng: seed | 1,
+        }
+    }
+
+    /// Adds a server, `connect` is called to open a connection whenever one is needed.
+    pub fn with_server<S, F>(mut self, name: String, mut connect: F) -> Scheduler
+    where
+        S: CommandService + Send + 'static,
+        F: FnMut() -> Result<S, Error> + Send + 'static,
+    {
+        let connect: Connector = Box::new(move || {
+            connect().map(|service| Box::new(service) as Box<dyn CommandService + Send>)
+        });
+        self.servers.insert(
+            name,
+            Server {
+                connect,
+                connection: None,
+            },
+        );
+        self
+    }
+
+    /// Adds a server that is reached with an [`RCONClient`] over TCP. Reads time out after 10 seconds so a server that stops responding does not stall the scheduler.
+    pub fn with_tcp_server(self, name: String, address: String, password: String) -> Scheduler {
+        self.with_server(name, move || {
+            let stream = TcpStream::connect(address.as_str())?;
+            stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+            RCONClient::new(stream, SimpleIDGenerator::new(), password.clone())
+        })
+    }
+
+    /// Adds a job, its server must be added as well before the scheduler runs.
+    pub fn with_job(mut self, job: Job) -> Scheduler {
+        self.jobs.push(ScheduledJob { job, next: None });
+        self
+    }
+
+    /// Interprets the schedules in local time `utc_offset_minutes` ahead of UTC, for example 120 for UTC+2.
+    pub fn with_utc_offset(mut self, utc_offset_minutes: i32) -> Scheduler {
+        self.utc_offset_minutes = utc_offset_minutes;
+        self
+    }
+
+    /// Passes every [`JobReport`] to `log` instead of printing it to stderr.
+    pub fn with_log(mut self, log: impl FnMut(&JobReport) + Send + 'static) -> Scheduler {
+        self.log = Box::new(log);
+        self
+    }
+
+    fn random_jitter(&mut self, jitter: Duration) -> Duration {
+        if jitter.is_zero() {
+            return Duration::ZERO;
+        }
+        // xorshift64, good enough to spread out jobs.
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        jitter.mul_f64((self.rng >> 11) as f64 / (1u64 << 53) as f64)
+    }
+
+    fn schedule_next(&mut self, index: usize, after: SystemTime) {
+        let job = &self.jobs[index].job;
+        let jitter = job.jitter;
+        let next = job.schedule.next_after(after, self.utc_offset_minutes);
+        let jitter = self.random_jitter(jitter);
+        self.jobs[index].next = next.map(|next| next + jitter);
+    }
+
+    /// Runs the named job now, regardless of its schedule.
+    pub fn run_job(&mut self, name: &str) -> Result<JobReport, Error> {
+        let index = self
+            .jobs
+            .iter()
+            .position(|scheduled| scheduled.job.name == name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Unknown job {name:?}")))?;
+        let report = self.execute(index)?;
+        (self.log)(&report);
+        Ok(report)
+    }
+
+    fn execute(&mut self, index: usize) -> Result<JobReport, Error> {
+        let job = &self.jobs[index].job;
+        let server = self.servers.get_mut(&job.server).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("Unknown server {:?} in job {:?}", job.server, job.name),
+            )
+        })?;
+        let started = SystemTime::now();
+        let steps = job
+            .steps
+            .iter()
+            .map(|step| (step.command.clone(), server.run_step(step)))
+            .collect();
+        Ok(JobReport {
+            job: job.name.clone(),
+            server: job.server.clone(),
+            started,
+            steps,
+        })
+    }
+
+    /// Runs every job that is due at `now` and schedules its next run. Jobs that have not been scheduled yet are scheduled after `now` without running.
+    /// This is what [`Scheduler::run()`] calls in a loop, it returns when the next job is due.
+    pub fn tick(&mut self, now: SystemTime) -> Option<SystemTime> {
+        for index in 0..self.jobs.len() {
+            let Some(due) = self.jobs[index].next else {
+                self.schedule_next(index, now);
+                continue;
+            };
+            if due > now {
+                continue;
+            }
+            let missed = now.duration_since(due).unwrap_or_default() > MISSED_AFTER;
+            if !missed || self.jobs[index].job.missed == MissedRun::RunOnce {
+                let report = match self.execute(index) {
+                    Ok(report) => report,
+                    Err(e) => JobReport {
+                        job: self.jobs[index].job.name.clone(),
+                        server: self.jobs[index].job.server.clone(),
+                        started: now,
+                        steps: vec![(String::new(), StepOutcome::Failed(e.to_string()))],
+                    },
+                };
+                (self.log)(&report);
+            }
+            self.schedule_next(index, now.max(SystemTime::now()));
+        }
+        self.jobs.iter().filter_map(|job| job.next).min()
+    }
+
+    /// Runs the jobs on their schedules forever.
+    pub fn run(mut self) -> ! {
+        loop {
+            let next = self.tick(SystemTime::now());
+            let wait = match next {
+                Some(next) => next
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default()
+                    // Wake up regularly so a changed system clock is noticed.
+                    .min(MISSED_AFTER),
+                None => MISSED_AFTER,
+            };
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::service::service_fn;
+
+    fn scheduler(players: u32, log: Arc<Mutex<Vec<String>>>) -> Scheduler {
+        Scheduler::new().with_server("local".to_string(), move || {
+            let log = log.clone();
+            Ok(service_fn(move |cmd: String| {
+                log.lock().unwrap().push(cmd.clone());
+                match cmd.as_str() {
+                    "list" => Ok(format!(
+                        "There are {players} of a max of 20 players online:"
+                    )),
+                    _ => Ok(format!("ran {cmd}")),
+                }
+            }))
+        })
+    }
+
+    fn restart_job() -> Job {
+        let online = Condition::new(
+            "list".to_string(),
+            ValueParser::MinecraftList,
+            Comparison::Greater,
+            0.0,
+        );
+        Job::new(
+            "restart".to_string(),
+            "local".to_string(),
+            "*/5 * * * *".parse().unwrap(),
+        )
+        .with_step(Step::new("say restarting".to_string()).with_condition(online))
+        .with_step(Step::new("save-all".to_string()))
+    }
+
+    #[test]
+    fn conditional_steps() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut empty = scheduler(0, log.clone())
+            .with_job(restart_job())
+            .with_log(|_| {});
+        let report = empty.run_job("restart").unwrap();
+        assert_eq!(report.steps[0].1, StepOutcome::Skipped);
+        assert_eq!(
+            report.steps[1].1,
+            StepOutcome::Ran("ran save-all".to_string())
+        );
+        assert_eq!(*log.lock().unwrap(), ["list", "save-all"]);
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut busy = scheduler(3, log.clone())
+            .with_job(restart_job())
+            .with_log(|_| {});
+        assert!(busy.run_job("restart").unwrap().is_success());
+        assert_eq!(*log.lock().unwrap(), ["list", "say restarting", "save-all"]);
+    }
+
+    #[test]
+    fn missed_runs() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for (policy, expected_runs) in [(MissedRun::Skip, 0), (MissedRun::RunOnce, 1)] {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let reports = Arc::new(Mutex::new(0));
+            let counter = reports.clone();
+            let mut scheduler = scheduler(0, log)
+                .with_job(restart_job().with_missed_run(policy))
+                .with_log(move |_| *counter.lock().unwrap() += 1);
+            let due = scheduler.tick(start).unwrap();
+            // Wake up an hour late, many runs were missed.
+            scheduler.tick(due + Duration::from_secs(3600));
+            assert_eq!(*reports.lock().unwrap(), expected_runs, "{policy:?}");
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Contains the server side of the protocol, used by [`crate::proxy`] and useful as a local simulator for testing.
+
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::packet::{
+    packet_id::ID,
+    packet_kind::{ClientPacket, PacketKind, SessionState},
+    Direction, Packet, PacketType, MAX_PAYLOAD_SIZE,
+};
+
+/// Handles the commands of a single connection served with [`serve_connection()`].
+pub trait CommandHandler {
+    /// Checks the password sent by the client.
+    fn authenticate(&mut self, password: &str) -> bool;
+    /// Runs the command and returns the response body.
+    fn execute(&mut self, command: &str) -> String;
+}
+
+/// A [`CommandHandler`] that checks against a single password and passes commands to a closure.
+///
+/// # Example
+/// ```no_run
+/// use std::net::TcpListener;
+/// use ya_rcon::server::{serve_connection, PasswordHandler};
+/// let listener = TcpListener::bind("127.0.0.1:27015").unwrap();
+/// for stream in listener.incoming() {
+///     let handler = PasswordHandler::new("password".to_string(), |cmd: &str| format!("echo {cmd}"));
+///     std::thread::spawn(move || serve_connection(stream.unwrap(), handler));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PasswordHandler<F: FnMut(&str) -> String> {
+    password: String,
+    handler: F,
+}
+
+impl<F: FnMut(&str) -> String> PasswordHandler<F> {
+    /// Creates a new instance of the `PasswordHandler`.
+    pub fn new(password: String, handler: F) -> PasswordHandler<F> {
+        PasswordHandler { password, handler }
+    }
+}
+
+impl<F: FnMut(&str) -> String> CommandHandler for PasswordHandler<F> {
+    fn authenticate(&mut self, password: &str) -> bool {
+        self.password == password
+    }
+
+    fn execute(&mut self, command: &str) -> String {
+        (self.handler)(command)
+    }
+}
+
+/// Waits for the client to authenticate. Every [`PacketType::Auth`] packet is passed to `check`, which returns `Some` to accept the password.
+///
+/// A rejected password is answered with the ID -1 and the client may try again. Any other packet before a successful authentication is an error with [`ErrorKind::PermissionDenied`].
+/// Unlike some Source servers this does not send an empty [`PacketType::ResponseValue`] before the [`PacketType::AuthResponse`].
+pub fn accept_auth<S: Read + Write, U>(
+    mut stream: S,
+    mut check: impl FnMut(&str) -> Option<U>,
+) -> Result<U, Error> {
+    loop {
+        let packet = Packet::read_from(&mut stream)?;
+        if packet.get_kind(Direction::ClientToServer, SessionState::AuthPending)
+            != PacketKind::Client(ClientPacket::Auth)
+        {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "RCON client sent a command before authenticating.",
+            ));
+        }
+        match check(&packet.get_body()) {
+            Some(user) => {
+                write_packet(&mut stream, PacketType::AuthResponse, "", packet.get_id())?;
+                return Ok(user);
+            }
+            None => write_packet(&mut stream, PacketType::AuthResponse, "", ID::from(-1))?,
+        }
+    }
+}
+
+/// Sends a response to the command with the given ID. Bodies that do not fit in one packet are split into several [`PacketType::ResponseValue`] packets with the same ID.
+pub fn send_response<W: Write>(mut writer: W, id: ID, body: &str) -> Result<(), Error> {
+    let mut rest = body;
+    loop {
+        let mut split = rest.len().min(MAX_PAYLOAD_SIZE);
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        let (chunk, remaining) = rest.split_at(split);
+        write_packet(&mut writer, PacketType::ResponseValue, chunk, id)?;
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        rest = remaining;
+    }
+}
+
+/// Serves a single connection until the client disconnects: authenticates it with [`accept_auth()`] and then answers every command with [`send_response()`].
+pub fn serve_connection<S: Read + Write, H: CommandHandler>(
+    mut stream: S,
+    mut handler: H,
+) -> Result<(), Error> {
+    accept_auth(&mut stream, |password| {
+        handler.authenticate(password).then_some(())
+    })?;
+    loop {
+        let packet = match Packet::read_from(&mut stream) {
+            Ok(packet) => packet,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let response = handler.execute(&packet.get_body());
+        send_response(&mut stream, packet.get_id(), &response)?;
+    }
+}
+
+/// Writes the packet with a single `write_all()` so it is not split into several segments.
+fn write_packet<W: Write>(
+    mut writer: W,
+    pkt_type: PacketType,
+    body: &str,
+    id: ID,
+) -> Result<(), Error> {
+    writer.write_all(&Vec::from(Packet::new(pkt_type, body.to_string(), id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::{RCONClient, SimpleIDGenerator};
+
+    #[test]
+    fn client_against_simulator() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let handler =
+                    PasswordHandler::new("secret".to_string(), |cmd: &str| format!("ran {cmd}"));
+                std::thread::spawn(move || serve_connection(stream.unwrap(), handler));
+            }
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut client =
+            RCONClient::new(stream, SimpleIDGenerator::new(), "secret".to_string()).unwrap();
+        assert_eq!(
+            client.send_command("status".to_string()).unwrap(),
+            "ran status"
+        );
+
+        let stream = TcpStream::connect(address).unwrap();
+        let error =
+            RCONClient::new(stream, SimpleIDGenerator::new(), "wrong".to_string()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn long_responses_are_split() {
+        let mut output = Vec::new();
+        let body = "x".repeat(MAX_PAYLOAD_SIZE * 2);
+        send_response(&mut output, ID::from(3), &body).unwrap();
+
+        let mut reader = &output[..];
+        let mut received = String::new();
+        while !reader.is_empty() {
+            let packet = Packet::read_from(&mut reader).unwrap();
+            assert_eq!(packet.get_id(), ID::from(3));
+            received.push_str(&packet.get_body());
+        }
+        assert_eq!(received, body);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    collections::HashMap,
+    io::Error,
+    time::{Duration, Instant},
+};
+
+use super::{AsyncCommandService, CommandService, Layer};
+
+/// A [`Layer`] that caches successful responses, see [`Cache`].
+#[derive(Debug, Clone, Copy)]
+pub struct CacheLayer {
+    ttl: Duration,
+    should_cache: fn(&str) -> bool,
+}
+
+impl CacheLayer {
+    /// Creates a layer that reuses the response to a command for `ttl`.
+    /// By default every command is cached, so only use it for commands without side effects or set a filter with [`CacheLayer::with_filter()`].
+    pub fn new(ttl: Duration) -> CacheLayer {
+        CacheLayer {
+            ttl,
+            should_cache: |_| true,
+        }
+    }
+
+    /// Only caches the commands for which `should_cache` returns `true`.
+    pub fn with_filter(mut self, should_cache: fn(&str) -> bool) -> CacheLayer {
+        self.should_cache = should_cache;
+        self
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = Cache<S>;
+    fn layer(&self, inner: S) -> Cache<S> {
+        Cache {
+            inner,
+            config: *self,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+/// Caches successful responses, made by [`CacheLayer`].
+#[derive(Debug, Clone)]
+pub struct Cache<S> {
+    inner: S,
+    config: CacheLayer,
+    entries: HashMap<String, (Instant, String)>,
+}
+
+impl<S> Cache<S> {
+    fn lookup(&mut self, command: &str) -> Option<String> {
+        let now = Instant::now();
+        self.entries.retain(|_, (expires, _)| *expires > now);
+        self.entries
+            .get(command)
+            .map(|(_, response)| response.clone())
+    }
+
+    fn store(&mut self, command: String, result: &Result<String, Error>) {
+        if let Ok(response) = result {
+            if (self.config.should_cache)(&command) {
+                let expires = Instant::now() + self.config.ttl;
+                self.entries.insert(command, (expires, response.clone()));
+            }
+        }
+    }
+
+    /// Removes all cached responses.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<S: CommandService> CommandService for Cache<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        if let Some(response) = self.lookup(&command) {
+            return Ok(response);
+        }
+        let result = self.inner.call(command.clone());
+        self.store(command, &result);
+        result
+    }
+}
+
+impl<S: AsyncCommandService> AsyncCommandService for Cache<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        if let Some(response) = self.lookup(&command) {
+            return Ok(response);
+        }
+        let result = self.inner.call(command.clone()).await;
+        self.store(command, &result);
+        result
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    io::Error,
+    time::{Duration, Instant},
+};
+
+use super::{AsyncCommandService, CommandService, Layer};
+
+/// A [`Layer`] that passes every command, its result and how long it took to a callback, see [`Inspect`]. Useful for logging and metrics.
+///
+/// # Example
+/// ```
+/// use ya_rcon::service::{service_fn, CommandService, InspectLayer, Layer};
+/// let layer = InspectLayer::new(|cmd: &str, result: &std::io::Result<String>, elapsed| {
+///     println!("{cmd} took {elapsed:?}: {result:?}");
+/// });
+/// let mut service = layer.layer(service_fn(|cmd: String| Ok(cmd)));
+/// service.call("status".to_string()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct InspectLayer<F> {
+    f: F,
+}
+
+impl<F: Fn(&str, &Result<String, Error>, Duration) + Clone> InspectLayer<F> {
+    /// Creates a new instance of the `InspectLayer`.
+    pub fn new(f: F) -> InspectLayer<F> {
+        InspectLayer { f }
+    }
+}
+
+impl<S, F: Fn(&str, &Result<String, Error>, Duration) + Clone> Layer<S> for InspectLayer<F> {
+    type Service = Inspect<S, F>;
+    fn layer(&self, inner: S) -> Inspect<S, F> {
+        Inspect {
+            inner,
+            f: self.f.clone(),
+        }
+    }
+}
+
+/// Passes every command and its result to a callback, made by [`InspectLayer`].
+#[derive(Debug, Clone)]
+pub struct Inspect<S, F> {
+    inner: S,
+    f: F,
+}
+
+impl<S: CommandService, F: Fn(&str, &Result<String, Error>, Duration)> CommandService
+    for Inspect<S, F>
+{
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        let start = Instant::now();
+        let result = self.inner.call(command.clone());
+        (self.f)(&command, &result, start.elapsed());
+        result
+    }
+}
+
+impl<S: AsyncCommandService, F: Fn(&str, &Result<String, Error>, Duration)> AsyncCommandService
+    for Inspect<S, F>
+{
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        let start = Instant::now();
+        let result = self.inner.call(command.clone()).await;
+        (self.f)(&command, &result, start.elapsed());
+        result
+    }
+}
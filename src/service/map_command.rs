@@ -0,0 +1,45 @@
+use std::{future::Future, io::Error};
+
+use super::{AsyncCommandService, CommandService, Layer};
+
+/// A [`Layer`] that rewrites commands before they are run, see [`MapCommand`].
+#[derive(Debug, Clone)]
+pub struct MapCommandLayer<F> {
+    f: F,
+}
+
+impl<F: Fn(String) -> String + Clone> MapCommandLayer<F> {
+    /// Creates a layer that passes every command through `f`, for example to add a prefix required by a game.
+    pub fn new(f: F) -> MapCommandLayer<F> {
+        MapCommandLayer { f }
+    }
+}
+
+impl<S, F: Fn(String) -> String + Clone> Layer<S> for MapCommandLayer<F> {
+    type Service = MapCommand<S, F>;
+    fn layer(&self, inner: S) -> MapCommand<S, F> {
+        MapCommand {
+            inner,
+            f: self.f.clone(),
+        }
+    }
+}
+
+/// Rewrites commands before they are run, made by [`MapCommandLayer`].
+#[derive(Debug, Clone)]
+pub struct MapCommand<S, F> {
+    inner: S,
+    f: F,
+}
+
+impl<S: CommandService, F: Fn(String) -> String> CommandService for MapCommand<S, F> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.inner.call((self.f)(command))
+    }
+}
+
+impl<S: AsyncCommandService, F: Fn(String) -> String> AsyncCommandService for MapCommand<S, F> {
+    fn call(&mut self, command: String) -> impl Future<Output = Result<String, Error>> {
+        self.inner.call((self.f)(command))
+    }
+}
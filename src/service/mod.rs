@@ -0,0 +1,237 @@
+//! Contains the [`CommandService`] abstraction and composable layers (retry, timeout, rate limiting, inspection, tracing, caching, command rewriting) to wrap the clients with.
+//!
+//! This follows the design of [tower](https://docs.rs/tower) without depending on it: a service runs a command and returns the response, a [`Layer`] wraps a service in another service and a [`ServiceBuilder`] stacks layers.
+//!
+//! # Example
+//! ```no_run
+//! use std::{net::TcpStream, time::Duration};
+//! use ya_rcon::service::{CacheLayer, CommandService, RetryLayer, ServiceBuilder};
+//! use ya_rcon::{RCONClient, SimpleIDGenerator};
+//!
+//! let stream = TcpStream::connect("127.0.0.1:27015").unwrap();
+//! let client = RCONClient::new(stream, SimpleIDGenerator::new(), "password".to_string()).unwrap();
+//! let mut service = ServiceBuilder::new()
+//!     .layer(RetryLayer::new(3))
+//!     .layer(CacheLayer::new(Duration::from_secs(5)))
+//!     .service(client);
+//! let reply = service.call("status".to_string()).unwrap();
+//! ```
+
+mod cache;
+mod inspect;
+mod map_command;
+#[cfg(feature = "policy")]
+mod policy;
+mod rate_limit;
+mod retry;
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+mod timeout;
+#[cfg(feature = "tracing")]
+mod trace;
+
+use std::{
+    future::Future,
+    io::{Error, Read, Write},
+};
+
+pub use cache::{Cache, CacheLayer};
+pub use inspect::{Inspect, InspectLayer};
+pub use map_command::{MapCommand, MapCommandLayer};
+#[cfg(feature = "policy")]
+pub use policy::{PolicyLayer, PolicyService};
+pub use rate_limit::{RateLimit, RateLimitLayer};
+pub use retry::{Retry, RetryLayer};
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+pub use timeout::{Timeout, TimeoutLayer};
+#[cfg(feature = "tracing")]
+pub use trace::{Trace, TraceLayer};
+
+use crate::{packet::packet_id::ID, RCONClient};
+
+/// Runs a command and returns the response. Implemented by [`RCONClient`] and by every layer in this module.
+pub trait CommandService {
+    /// Runs the command.
+    fn call(&mut self, command: String) -> Result<String, Error>;
+}
+
+/// The async version of [`CommandService`]. Implemented by [`crate::client_async::AsyncRCONClient`] and by every layer in this module, [`RateLimit`] and [`Retry`] only with the `tokio` or `async-net` feature since they wait.
+pub trait AsyncCommandService {
+    /// Runs the command.
+    fn call(&mut self, command: String) -> impl Future<Output = Result<String, Error>>;
+}
+
+/// Wraps a service in another service.
+pub trait Layer<S> {
+    /// The wrapping service.
+    type Service;
+    /// Wraps the service.
+    fn layer(&self, inner: S) -> Self::Service;
+}
+
+/// A [`Layer`] that does nothing, used to start a [`ServiceBuilder`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl<S> Layer<S> for Identity {
+    type Service = S;
+    fn layer(&self, inner: S) -> S {
+        inner
+    }
+}
+
+/// Two layers stacked on top of each other, `outer` wraps the service made by `inner`.
+#[derive(Debug, Clone)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<S, Inner: Layer<S>, Outer: Layer<Inner::Service>> Layer<S> for Stack<Inner, Outer> {
+    type Service = Outer::Service;
+    fn layer(&self, service: S) -> Self::Service {
+        self.outer.layer(self.inner.layer(service))
+    }
+}
+
+/// Stacks layers to build a service. The first layer added is the outermost, so it sees the command first and the response last.
+#[derive(Debug, Clone)]
+pub struct ServiceBuilder<L> {
+    layer: L,
+}
+
+impl ServiceBuilder<Identity> {
+    /// Creates a new instance of the `ServiceBuilder` without any layers. Same as calling [`ServiceBuilder::default()`]
+    pub fn new() -> ServiceBuilder<Identity> {
+        ServiceBuilder { layer: Identity }
+    }
+}
+
+impl Default for ServiceBuilder<Identity> {
+    fn default() -> Self {
+        ServiceBuilder::new()
+    }
+}
+
+impl<L> ServiceBuilder<L> {
+    /// Adds a layer inside of the layers that were already added.
+    pub fn layer<T>(self, layer: T) -> ServiceBuilder<Stack<T, L>> {
+        ServiceBuilder {
+            layer: Stack {
+                inner: layer,
+                outer: self.layer,
+            },
+        }
+    }
+
+    /// Wraps the service in all the layers.
+    pub fn service<S>(&self, service: S) -> L::Service
+    where
+        L: Layer<S>,
+    {
+        self.layer.layer(service)
+    }
+}
+
+/// A service made from a closure, see [`service_fn()`].
+#[derive(Debug, Clone)]
+pub struct ServiceFn<F> {
+    f: F,
+}
+
+/// Creates a service from a closure. Returning a `Result<String, Error>` makes it a [`CommandService`], returning a future of one makes it an [`AsyncCommandService`].
+pub fn service_fn<F>(f: F) -> ServiceFn<F> {
+    ServiceFn { f }
+}
+
+impl<F: FnMut(String) -> Result<String, Error>> CommandService for ServiceFn<F> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        (self.f)(command)
+    }
+}
+
+impl<S: CommandService + ?Sized> CommandService for Box<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        (**self).call(command)
+    }
+}
+
+impl<F, Fut> AsyncCommandService for ServiceFn<F>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String, Error>>,
+{
+    fn call(&mut self, command: String) -> impl Future<Output = Result<String, Error>> {
+        (self.f)(command)
+    }
+}
+
+impl<T: Read + Write, I: Iterator<Item = ID>> CommandService for RCONClient<T, I> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.send_command(command)
+    }
+}
+
+#[cfg(feature = "async-net")]
+#[cfg(not(feature = "tokio"))]
+impl<T, I> AsyncCommandService for crate::client_async::AsyncRCONClient<T, I>
+where
+    T: futures::AsyncReadExt + futures::AsyncWriteExt + Unpin,
+    I: Iterator<Item = ID>,
+{
+    fn call(&mut self, command: String) -> impl Future<Output = Result<String, Error>> {
+        self.send_command(command)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[cfg(not(feature = "async-net"))]
+impl<T, I> AsyncCommandService for crate::client_async::AsyncRCONClient<T, I>
+where
+    T: tokio::io::AsyncReadExt + tokio::io::AsyncWriteExt + Unpin,
+    I: Iterator<Item = ID>,
+{
+    fn call(&mut self, command: String) -> impl Future<Output = Result<String, Error>> {
+        self.send_command(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn layers_are_applied_outside_in() {
+        let log = std::cell::RefCell::new(Vec::new());
+        let mut service = ServiceBuilder::new()
+            .layer(MapCommandLayer::new(|cmd: String| format!("{cmd} outer")))
+            .layer(MapCommandLayer::new(|cmd: String| format!("{cmd} inner")))
+            .service(service_fn(|cmd: String| {
+                log.borrow_mut().push(cmd.clone());
+                Ok(cmd)
+            }));
+        assert_eq!(service.call("cmd".to_string()).unwrap(), "cmd outer inner");
+        assert_eq!(log.borrow().len(), 1);
+    }
+
+    #[test]
+    fn retry_then_cache() {
+        let mut calls = 0;
+        let mut service = ServiceBuilder::new()
+            .layer(CacheLayer::new(std::time::Duration::from_secs(60)))
+            .layer(RetryLayer::new(3))
+            .service(service_fn(|cmd: String| {
+                calls += 1;
+                if calls < 3 {
+                    Err(Error::new(ErrorKind::TimedOut, "timed out"))
+                } else {
+                    Ok(format!("ran {cmd}"))
+                }
+            }));
+        assert_eq!(service.call("status".to_string()).unwrap(), "ran status");
+        assert_eq!(service.call("status".to_string()).unwrap(), "ran status");
+        drop(service);
+        assert_eq!(calls, 3);
+    }
+}
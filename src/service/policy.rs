@@ -0,0 +1,61 @@
+use std::{io::Error, sync::Arc};
+
+use super::{AsyncCommandService, CommandService, Layer};
+use crate::policy::{AuditOutcome, Policy};
+
+/// A [`Layer`] that checks every command against a [`Policy`] for a user, see [`PolicyService`].
+#[derive(Debug, Clone)]
+pub struct PolicyLayer {
+    policy: Arc<Policy>,
+    user: String,
+}
+
+impl PolicyLayer {
+    /// Creates a layer that runs the commands as the given user.
+    pub fn new(policy: Arc<Policy>, user: String) -> PolicyLayer {
+        PolicyLayer { policy, user }
+    }
+}
+
+impl<S> Layer<S> for PolicyLayer {
+    type Service = PolicyService<S>;
+    fn layer(&self, inner: S) -> PolicyService<S> {
+        PolicyService {
+            inner,
+            policy: self.policy.clone(),
+            user: self.user.clone(),
+        }
+    }
+}
+
+/// Checks every command against a [`Policy`] and records it in the audit log, made by [`PolicyLayer`]. See [`Policy::execute()`].
+#[derive(Debug, Clone)]
+pub struct PolicyService<S> {
+    inner: S,
+    policy: Arc<Policy>,
+    user: String,
+}
+
+impl<S: CommandService> CommandService for PolicyService<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.policy
+            .execute(&self.user, &command.clone(), |_| self.inner.call(command))
+    }
+}
+
+impl<S: AsyncCommandService> AsyncCommandService for PolicyService<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        if let Err(error) = self.policy.check(&self.user, &command) {
+            let outcome = AuditOutcome::Rejected(error.to_string());
+            self.policy.audit(&self.user, &command, outcome);
+            return Err(error.into());
+        }
+        let result = self.inner.call(command.clone()).await;
+        let outcome = match &result {
+            Ok(response) => AuditOutcome::Executed(response.clone()),
+            Err(error) => AuditOutcome::Failed(error.to_string()),
+        };
+        self.policy.audit(&self.user, &command, outcome);
+        result
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::Error;
+
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+use super::AsyncCommandService;
+use super::{CommandService, Layer};
+use crate::rate_limit::TokenBucket;
+
+/// A [`Layer`] that limits how often commands are run, see [`RateLimit`].
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    bucket: TokenBucket,
+}
+
+impl RateLimitLayer {
+    /// Creates a layer that takes a token from a copy of `bucket` for every command.
+    pub fn new(bucket: TokenBucket) -> RateLimitLayer {
+        RateLimitLayer { bucket }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+    fn layer(&self, inner: S) -> RateLimit<S> {
+        RateLimit {
+            inner,
+            bucket: self.bucket.clone(),
+        }
+    }
+}
+
+/// Waits until the [`TokenBucket`] has a token before running a command, made by [`RateLimitLayer`].
+/// The sync version blocks the thread, the async version is only available with the `tokio` or `async-net` feature and waits on its timer.
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    bucket: TokenBucket,
+}
+
+impl<S: CommandService> CommandService for RateLimit<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        self.bucket.acquire_blocking();
+        self.inner.call(command)
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+impl<S: AsyncCommandService> AsyncCommandService for RateLimit<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        self.bucket.acquire().await;
+        self.inner.call(command).await
+    }
+}
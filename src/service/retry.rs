@@ -0,0 +1,100 @@
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+use super::AsyncCommandService;
+use super::{CommandService, Layer};
+
+/// A [`Layer`] that retries failed commands, see [`Retry`].
+#[derive(Debug, Clone, Copy)]
+pub struct RetryLayer {
+    attempts: u32,
+    backoff: Duration,
+    should_retry: fn(&Error) -> bool,
+}
+
+impl RetryLayer {
+    /// Creates a layer that runs every command up to `attempts` times, without waiting between attempts.
+    /// By default every error except [`ErrorKind::PermissionDenied`] and [`ErrorKind::InvalidInput`] is retried, since those will not go away by themselves.
+    pub fn new(attempts: u32) -> RetryLayer {
+        RetryLayer {
+            attempts: attempts.max(1),
+            backoff: Duration::ZERO,
+            should_retry: |error| {
+                !matches!(
+                    error.kind(),
+                    ErrorKind::PermissionDenied | ErrorKind::InvalidInput
+                )
+            },
+        }
+    }
+
+    /// Waits between attempts, the wait doubles after every attempt.
+    pub fn with_backoff(mut self, backoff: Duration) -> RetryLayer {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets which errors are retried.
+    pub fn with_predicate(mut self, should_retry: fn(&Error) -> bool) -> RetryLayer {
+        self.should_retry = should_retry;
+        self
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+    fn layer(&self, inner: S) -> Retry<S> {
+        Retry {
+            inner,
+            config: *self,
+        }
+    }
+}
+
+/// Retries failed commands, made by [`RetryLayer`].
+/// The async version is only available with the `tokio` or `async-net` feature, it waits between attempts on the timer of the runtime.
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    config: RetryLayer,
+}
+
+impl<S: CommandService> CommandService for Retry<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        let mut backoff = self.config.backoff;
+        let mut attempt = 1;
+        loop {
+            match self.inner.call(command.clone()) {
+                Err(e) if attempt < self.config.attempts && (self.config.should_retry)(&e) => {
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-net"))]
+impl<S: AsyncCommandService> AsyncCommandService for Retry<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        let mut backoff = self.config.backoff;
+        let mut attempt = 1;
+        loop {
+            match self.inner.call(command.clone()).await {
+                Err(e) if attempt < self.config.attempts && (self.config.should_retry)(&e) => {
+                    if !backoff.is_zero() {
+                        crate::rate_limit::sleep(backoff).await;
+                    }
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::{io::Error, io::ErrorKind, time::Duration};
+
+use super::{AsyncCommandService, Layer};
+
+/// A [`Layer`] that fails commands that take too long, see [`Timeout`].
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    /// Creates a new instance of the `TimeoutLayer`.
+    pub fn new(timeout: Duration) -> TimeoutLayer {
+        TimeoutLayer { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+    fn layer(&self, inner: S) -> Timeout<S> {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Fails commands with [`ErrorKind::TimedOut`] if they take too long, made by [`TimeoutLayer`].
+///
+/// This is only an [`AsyncCommandService`], a blocking call can not be interrupted. For the sync client set a timeout on the socket instead, for example with [`std::net::TcpStream::set_read_timeout()`].
+/// The response to a timed out command may still arrive later.
+#[derive(Debug, Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+fn timed_out() -> Error {
+    Error::new(ErrorKind::TimedOut, "RCON command timed out")
+}
+
+#[cfg(feature = "tokio")]
+#[cfg(not(feature = "async-net"))]
+impl<S: AsyncCommandService> AsyncCommandService for Timeout<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        tokio::time::timeout(self.timeout, self.inner.call(command))
+            .await
+            .unwrap_or_else(|_| Err(timed_out()))
+    }
+}
+
+#[cfg(feature = "async-net")]
+#[cfg(not(feature = "tokio"))]
+impl<S: AsyncCommandService> AsyncCommandService for Timeout<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        use futures::FutureExt;
+        futures::select! {
+            result = std::pin::pin!(self.inner.call(command).fuse()) => result,
+            _ = async_io::Timer::after(self.timeout).fuse() => Err(timed_out()),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{io::Error, time::Instant};
+
+use tracing::{field::Empty, Instrument, Span};
+
+use super::{AsyncCommandService, CommandService, Layer};
+use crate::trace::recorded_command;
+
+/// A [`Layer`] that runs every command in a [tracing](https://docs.rs/tracing) span, see [`Trace`]. Only available with the tracing feature.
+///
+/// # Example
+/// ```
+/// use ya_rcon::service::{service_fn, CommandService, Layer, TraceLayer};
+/// let mut service = TraceLayer::new().layer(service_fn(|cmd: String| Ok(format!("ran {cmd}"))));
+/// assert_eq!(service.call("status".to_string()).unwrap(), "ran status");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceLayer;
+
+impl TraceLayer {
+    /// Creates a new instance of the `TraceLayer`. Same as calling [`TraceLayer::default()`]
+    pub fn new() -> TraceLayer {
+        TraceLayer
+    }
+}
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = Trace<S>;
+    fn layer(&self, inner: S) -> Trace<S> {
+        Trace { inner }
+    }
+}
+
+/// Opens an `rcon_service_command` span for every command, made by [`TraceLayer`].
+///
+/// The span records the command, with the value of password commands redacted like the client spans, how long it took as `elapsed_us` and the error if it failed.
+#[derive(Debug, Clone)]
+pub struct Trace<S> {
+    inner: S,
+}
+
+fn command_span(command: &str) -> Span {
+    tracing::info_span!(
+        "rcon_service_command",
+        command = %recorded_command(command),
+        elapsed_us = Empty,
+        error = Empty
+    )
+}
+
+fn record_result(span: &Span, start: Instant, result: &Result<String, Error>) {
+    let elapsed_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+    span.record("elapsed_us", elapsed_us);
+    if let Err(e) = result {
+        span.record("error", tracing::field::display(e));
+    }
+}
+
+impl<S: CommandService> CommandService for Trace<S> {
+    fn call(&mut self, command: String) -> Result<String, Error> {
+        let span = command_span(&command);
+        let start = Instant::now();
+        let result = span.in_scope(|| self.inner.call(command));
+        record_result(&span, start, &result);
+        result
+    }
+}
+
+impl<S: AsyncCommandService> AsyncCommandService for Trace<S> {
+    async fn call(&mut self, command: String) -> Result<String, Error> {
+        let span = command_span(&command);
+        let start = Instant::now();
+        let result = self.inner.call(command).instrument(span.clone()).await;
+        record_result(&span, start, &result);
+        result
+    }
+}
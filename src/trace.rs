@@ -0,0 +1,143 @@
+//! Contains the helpers used to instrument the clients with [tracing](https://docs.rs/tracing), gated with the tracing feature. Without the feature they do nothing.
+
+use std::borrow::Cow;
+
+use crate::packet::{Packet, PacketType};
+
+/// The body of the packet as it is recorded, the password of [`PacketType::Auth`] packets is never logged.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) fn recorded_body(packet: &Packet) -> Cow<'_, str> {
+    match packet.get_type() {
+        PacketType::Auth => Cow::Borrowed("<redacted>"),
+        _ => String::from_utf8_lossy(packet.get_body_bytes()),
+    }
+}
+
+/// The command as it is recorded, the value of commands that set a password such as `rcon_password` or `sv_password` is never logged.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) fn recorded_command(command: &str) -> Cow<'_, str> {
+    let name = command.split_whitespace().next().unwrap_or_default();
+    if name.to_ascii_lowercase().contains("password") && name.len() < command.trim().len() {
+        Cow::Owned(format!("{name} <redacted>"))
+    } else {
+        Cow::Borrowed(command)
+    }
+}
+
+/// Records a packet written to the server.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn packet_sent(packet: &Packet) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        id = i32::from(packet.get_id()),
+        packet_type = ?packet.get_type(),
+        size = packet.encoded_len(),
+        body = %recorded_body(packet),
+        "sent packet"
+    );
+}
+
+/// Records a packet read from the server.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn packet_received(packet: &Packet) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        id = i32::from(packet.get_id()),
+        packet_type = ?packet.get_type(),
+        size = packet.encoded_len(),
+        body = %recorded_body(packet),
+        "received packet"
+    );
+}
+
+/// Records a packet that does not match the request it should answer.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn unexpected_packet(expected_type: PacketType, expected_id: i32, packet: &Packet) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        expected_id,
+        expected_type = ?expected_type,
+        id = i32::from(packet.get_id()),
+        packet_type = ?packet.get_type(),
+        body = %recorded_body(packet),
+        "received unexpected packet"
+    );
+}
+
+/// Records a response to another outstanding request, kept until that request asks for it.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn buffered_packet(expected_id: i32, packet: &Packet) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        expected_id,
+        id = i32::from(packet.get_id()),
+        packet_type = ?packet.get_type(),
+        "buffered response to another request"
+    );
+}
+
+/// Records a response nobody waits for any more, such as a late response to a command that timed out.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn stale_packet(expected_id: i32, packet: &Packet) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        expected_id,
+        id = i32::from(packet.get_id()),
+        packet_type = ?packet.get_type(),
+        body = %recorded_body(packet),
+        "discarded stale packet"
+    );
+}
+
+/// Creates the span that the spans of all commands on a connection are children of.
+#[cfg(feature = "tracing")]
+pub(crate) fn connection_span(encoding: crate::TextEncoding) -> tracing::Span {
+    tracing::info_span!("rcon_connection", encoding = ?encoding)
+}
+
+/// Records how long a command took once it is dropped, create it at the start of the instrumented function.
+#[derive(Debug)]
+pub(crate) struct Timer {
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Timer {
+        Timer {
+            #[cfg(feature = "tracing")]
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        tracing::debug!(
+            elapsed_us = u64::try_from(self.start.elapsed().as_micros()).unwrap_or(u64::MAX),
+            "finished"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::packet_id::ID;
+
+    #[test]
+    fn auth_body_is_redacted() {
+        let auth = Packet::new(PacketType::Auth, "hunter2".to_string(), ID::from(1)).unwrap();
+        assert_eq!(recorded_body(&auth), "<redacted>");
+        let command =
+            Packet::new(PacketType::ExecCommand, "status".to_string(), ID::from(2)).unwrap();
+        assert_eq!(recorded_body(&command), "status");
+        assert_eq!(recorded_command("status"), "status");
+        assert_eq!(
+            recorded_command("rcon_password hunter2"),
+            "rcon_password <redacted>"
+        );
+        assert_eq!(recorded_command("sv_password"), "sv_password");
+    }
+}
@@ -0,0 +1,59 @@
+//! Contains the implementation for [`TranscriptRecord`]
+
+use std::time::SystemTime;
+
+use crate::packet::{Direction, Packet};
+
+/// A single packet of a recorded RCON session, with the direction it travelled and when it was seen.
+///
+/// With the `serde` feature this can be serialized, for example as one JSON object per line, and read back by tooling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TranscriptRecord {
+    /// Which way the packet travelled.
+    pub direction: Direction,
+    /// When the packet was sent or received.
+    pub timestamp: SystemTime,
+    /// The packet itself.
+    pub packet: Packet,
+}
+
+impl TranscriptRecord {
+    /// Creates a new record timestamped with [`SystemTime::now()`].
+    pub fn new(direction: Direction, packet: Packet) -> TranscriptRecord {
+        TranscriptRecord {
+            direction,
+            timestamp: SystemTime::now(),
+            packet,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "serde")]
+mod tests {
+    use super::*;
+    use crate::packet::{packet_id::ID, PacketType};
+
+    #[test]
+    fn transcript_round_trip() {
+        let records = [
+            TranscriptRecord::new(
+                Direction::ClientToServer,
+                Packet::new(PacketType::ExecCommand, "status".to_string(), ID::from(3)).unwrap(),
+            ),
+            TranscriptRecord::new(
+                Direction::ServerToClient,
+                Packet::new_bytes(PacketType::ResponseValue, vec![0xE9, b'!'], ID::from(3))
+                    .unwrap(),
+            ),
+        ];
+        for record in records {
+            let json = serde_json::to_string(&record).unwrap();
+            assert_eq!(
+                serde_json::from_str::<TranscriptRecord>(&json).unwrap(),
+                record
+            );
+        }
+    }
+}
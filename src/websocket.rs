@@ -0,0 +1,395 @@
+//! Contains the implementation for [`Bridge`], a WebSocket endpoint that relays commands from a browser to a game server and pushes the responses and unsolicited server messages back as they arrive.
+//!
+//! Every WebSocket opens its own RCON connection to the server, built on [`RconCodec`] so packets the server sends on its own (chat, kill feeds, log lines on some games) are not lost.
+//!
+//! # Protocol
+//! Connect to `/servers/{name}/ws?token=<token>`, an `Authorization: Bearer <token>` header works as well for clients that can set one.
+//! Every WebSocket message is a JSON object with a `type` field. This protocol is stable, new fields may be added but existing fields will not change meaning.
+//!
+//! Sent by the browser:
+//! * `{"type": "command", "id": 7, "command": "status"}` runs a command. The `id` is optional and chosen by the browser, it is echoed in the response.
+//!
+//! Sent by the bridge:
+//! * `{"type": "ready"}` once the bridge is authenticated with the server.
+//! * `{"type": "response", "id": 7, "body": "..."}` for every packet of the response to a command, long responses can arrive as several messages with the same `id`.
+//! * `{"type": "message", "body": "..."}` for a packet that does not belong to any command.
+//! * `{"type": "error", "id": 7, "kind": "bad_request", "message": "..."}` when something fails, the `id` is `null` if the error does not belong to a command.
+//!   The kinds are the same as [`GatewayErrorKind`]. After errors of the kinds `connection`, `auth_failed` and `protocol` the bridge closes the WebSocket.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use ya_rcon::gateway::ServerConfig;
+//! use ya_rcon::websocket::Bridge;
+//!
+//! let bridge = Bridge::new()
+//!     .with_token("secret-token".to_string())
+//!     .with_server(
+//!         "survival".to_string(),
+//!         ServerConfig::new("127.0.0.1:25575".to_string(), "password".to_string()),
+//!     );
+//! bridge.serve(tokio::net::TcpListener::bind("0.0.0.0:8080").await?).await
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Error,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+use crate::{
+    codec::RconCodec,
+    gateway::{is_authorized, GatewayError, GatewayErrorKind, ServerConfig},
+    packet::{
+        packet_id::ID,
+        packet_kind::{PacketKind, ServerPacket, SessionState},
+        Direction, Packet, PacketType,
+    },
+};
+
+/// How many commands per WebSocket are remembered to route their responses, older commands are forgotten and late responses to them are sent as `message`.
+const MAX_PENDING: usize = 64;
+
+/// A message sent by the browser, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Runs a command.
+    Command {
+        /// Chosen by the browser and echoed in the response.
+        #[serde(default)]
+        id: Option<u64>,
+        /// The command to run.
+        command: String,
+    },
+}
+
+/// A message sent by the bridge, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The bridge is authenticated with the server.
+    Ready,
+    /// A packet of the response to a command.
+    Response {
+        /// The `id` of the command.
+        id: Option<u64>,
+        /// The body of the packet.
+        body: String,
+    },
+    /// A packet that does not belong to any command.
+    Message {
+        /// The body of the packet.
+        body: String,
+    },
+    /// Something failed.
+    Error {
+        /// The `id` of the command that failed, if any.
+        id: Option<u64>,
+        /// The classification of the error.
+        kind: GatewayErrorKind,
+        /// The description of the error.
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    fn error(id: Option<u64>, error: GatewayError) -> ServerMessage {
+        ServerMessage::Error {
+            id,
+            kind: error.get_kind(),
+            message: error.get_message().to_string(),
+        }
+    }
+}
+
+/// A WebSocket bridge to a set of named servers, see the [module documentation](self) for the protocol.
+#[derive(Debug, Clone, Default)]
+pub struct Bridge {
+    servers: HashMap<String, ServerConfig>,
+    tokens: Vec<String>,
+}
+
+impl Bridge {
+    /// Creates a bridge without servers or tokens. A bridge without tokens rejects every connection.
+    pub fn new() -> Bridge {
+        Bridge::default()
+    }
+
+    /// Adds a server that is reachable as `/servers/{name}/ws`. The connection limit of the [`ServerConfig`] is not used, every WebSocket has its own connection.
+    pub fn with_server(mut self, name: String, config: ServerConfig) -> Bridge {
+        self.servers.insert(name, config);
+        self
+    }
+
+    /// Adds a token that is allowed to connect.
+    pub fn with_token(mut self, token: String) -> Bridge {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Creates the axum [`Router`], it can be merged with [`crate::gateway::Gateway::router()`].
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/servers/{name}/ws", get(upgrade))
+            .with_state(Arc::new(self))
+    }
+
+    /// Serves the bridge on the listener until it fails.
+    pub async fn serve(self, listener: tokio::net::TcpListener) -> Result<(), Error> {
+        axum::serve(listener, self.router()).await
+    }
+}
+
+async fn upgrade(
+    State(bridge): State<Arc<Bridge>>,
+    Path(server): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !is_authorized(
+        &bridge.tokens,
+        &headers,
+        query.get("token").map(String::as_str),
+    ) {
+        return (StatusCode::UNAUTHORIZED, "Missing or unknown token").into_response();
+    }
+    let Some(config) = bridge.servers.get(&server).cloned() else {
+        return (StatusCode::NOT_FOUND, "Unknown server").into_response();
+    };
+    ws.on_upgrade(move |socket| async move {
+        // The browser was already told what went wrong, if it is still there.
+        let _ = relay(socket, config).await;
+    })
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), Error> {
+    let text = serde_json::to_string(message)?;
+    socket
+        .send(Message::Text(text.into()))
+        .await
+        .map_err(Error::other)
+}
+
+async fn connect(config: &ServerConfig) -> Result<Framed<TcpStream, RconCodec>, Error> {
+    let stream = TcpStream::connect(config.address.as_str()).await?;
+    let mut rcon = Framed::new(stream, RconCodec::with_encoding(config.encoding));
+    let auth_id = ID::from(0);
+    rcon.send(Packet::new_encoded(
+        PacketType::Auth,
+        &config.password,
+        auth_id,
+        config.encoding,
+    )?)
+    .await?;
+    loop {
+        let packet = rcon.next().await.ok_or_else(closed)??;
+        match packet.get_kind(Direction::ServerToClient, SessionState::AuthPending) {
+            // Some Source servers send an empty response first.
+            PacketKind::Server(ServerPacket::ResponseValue) => continue,
+            PacketKind::Server(ServerPacket::AuthResponse) if packet.get_id() == auth_id => {
+                return Ok(rcon)
+            }
+            PacketKind::Server(ServerPacket::AuthResponse) => {
+                return Err(Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "Authentication with the RCONserver failed.",
+                ))
+            }
+            _ => return Err(crate::packet::PacketError::UnexpectedType.into()),
+        }
+    }
+}
+
+fn closed() -> Error {
+    Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "The RCON server closed the connection",
+    )
+}
+
+async fn relay(mut socket: WebSocket, config: ServerConfig) -> Result<(), Error> {
+    let connected = tokio::time::timeout(config.timeout, connect(&config)).await;
+    let mut rcon = match connected {
+        Ok(Ok(rcon)) => rcon,
+        Ok(Err(e)) => return send(&mut socket, &ServerMessage::error(None, e.into())).await,
+        Err(_) => {
+            let error = GatewayError::new(GatewayErrorKind::Timeout, "Connecting timed out");
+            return send(&mut socket, &ServerMessage::error(None, error)).await;
+        }
+    };
+    send(&mut socket, &ServerMessage::Ready).await?;
+
+    let mut ids = (1..).map(ID::from_wrapping);
+    let mut pending: VecDeque<(ID, Option<u64>)> = VecDeque::new();
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                };
+                let (id, command) = match serde_json::from_str(text.as_str()) {
+                    Ok(ClientMessage::Command { id, command }) => (id, command),
+                    Err(e) => {
+                        let error = GatewayError::new(GatewayErrorKind::BadRequest, e.to_string());
+                        send(&mut socket, &ServerMessage::error(None, error)).await?;
+                        continue;
+                    }
+                };
+                let packet_id = ids.next().expect("the ID generator is infinite");
+                let packet = Packet::new_encoded(PacketType::ExecCommand, &command, packet_id, config.encoding);
+                let packet = match packet {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        let error = GatewayError::new(GatewayErrorKind::BadRequest, Error::from(e).to_string());
+                        send(&mut socket, &ServerMessage::error(id, error)).await?;
+                        continue;
+                    }
+                };
+                if let Err(e) = rcon.send(packet).await {
+                    return send(&mut socket, &ServerMessage::error(id, e.into())).await;
+                }
+                if pending.len() == MAX_PENDING {
+                    pending.pop_front();
+                }
+                pending.push_back((packet_id, id));
+            }
+            packet = rcon.next() => {
+                let packet = match packet {
+                    Some(Ok(packet)) => packet,
+                    Some(Err(e)) => return send(&mut socket, &ServerMessage::error(None, e.into())).await,
+                    None => return send(&mut socket, &ServerMessage::error(None, closed().into())).await,
+                };
+                // The codec only lets through bodies that are valid in the encoding.
+                let body = packet.decode_body(config.encoding).unwrap_or_else(|_| packet.get_body());
+                let message = match pending.iter().find(|(packet_id, _)| *packet_id == packet.get_id()) {
+                    Some((_, id)) => ServerMessage::Response { id: *id, body },
+                    None => ServerMessage::Message { body },
+                };
+                send(&mut socket, &message).await?;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::io::Write;
+
+    use tokio_tungstenite::tungstenite;
+
+    use super::*;
+    use crate::server::{accept_auth, send_response};
+    use crate::TextEncoding;
+
+    async fn recv_json<S>(ws: &mut S) -> serde_json::Value
+    where
+        S: futures::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        let message = ws.next().await.unwrap().unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio_macros::test]
+    async fn bridge_relays_commands_and_messages() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let rcon_address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_auth(&mut stream, |password| (password == "secret").then_some(())).unwrap();
+            let packet = Packet::read_from(&mut stream).unwrap();
+            let body = format!("ran {}", packet.get_body());
+            send_response(&mut stream, packet.get_id(), &body).unwrap();
+            send_response(&mut stream, ID::from(999), "player joined").unwrap();
+            stream.flush().unwrap();
+            // Keep the connection open until the bridge goes away.
+            let _ = Packet::read_from(&mut stream);
+        });
+
+        let bridge = Bridge::new().with_token("token".to_string()).with_server(
+            "local".to_string(),
+            ServerConfig::new(rcon_address, "secret".to_string()),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(bridge.serve(listener));
+
+        let url = format!("ws://{address}/servers/local/ws?token=wrong");
+        assert!(tokio_tungstenite::connect_async(url).await.is_err());
+
+        let url = format!("ws://{address}/servers/local/ws?token=token");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        assert_eq!(
+            recv_json(&mut ws).await,
+            serde_json::json!({"type": "ready"})
+        );
+
+        let command = r#"{"type": "command", "id": 7, "command": "status"}"#;
+        ws.send(tungstenite::Message::text(command)).await.unwrap();
+        assert_eq!(
+            recv_json(&mut ws).await,
+            serde_json::json!({"type": "response", "id": 7, "body": "ran status"})
+        );
+        assert_eq!(
+            recv_json(&mut ws).await,
+            serde_json::json!({"type": "message", "body": "player joined"})
+        );
+    }
+
+    #[tokio_macros::test]
+    async fn bridge_uses_the_configured_encoding() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let rcon_address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            accept_auth(&mut stream, |password| (password == "secret").then_some(())).unwrap();
+            let packet = Packet::read_from(&mut stream).unwrap();
+            assert_eq!(packet.get_body_bytes(), b"say caf\xe9");
+            let response = Packet::new_encoded(
+                PacketType::ResponseValue,
+                "sent caf\u{e9}",
+                packet.get_id(),
+                TextEncoding::Windows1252,
+            );
+            stream.write_all(&Vec::from(response.unwrap())).unwrap();
+            let _ = Packet::read_from(&mut stream);
+        });
+
+        let bridge = Bridge::new().with_token("token".to_string()).with_server(
+            "local".to_string(),
+            ServerConfig::new(rcon_address, "secret".to_string())
+                .with_encoding(TextEncoding::Windows1252),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(bridge.serve(listener));
+
+        let url = format!("ws://{address}/servers/local/ws?token=token");
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        recv_json(&mut ws).await;
+        let command = r#"{"type": "command", "id": 1, "command": "say caf\u00e9"}"#;
+        ws.send(tungstenite::Message::text(command)).await.unwrap();
+        assert_eq!(
+            recv_json(&mut ws).await,
+            serde_json::json!({"type": "response", "id": 1, "body": "sent caf\u{e9}"})
+        );
+    }
+}